@@ -140,6 +140,8 @@ enum SubCommand {
     Session(subcommands::Session),
     /// User account management
     Users(subcommands::Users),
+    /// API keys, for automation such as CI pipelines
+    ApiKeys(subcommands::ApiKeys),
     /// Password reset functions
     PasswordReset(subcommands::PasswordReset),
     /// Test management
@@ -148,6 +150,12 @@ enum SubCommand {
     TestResults(subcommands::TestResults),
     /// Runner logs
     RunnerLogs(subcommands::RunnerLogs),
+    /// Maintenance windows, during which test failures don't send notifications
+    MaintenanceWindows(subcommands::MaintenanceWindows),
+    /// Escalation policies, for reminders and escalation of ongoing test failures
+    EscalationPolicies(subcommands::EscalationPolicies),
+    /// Incidents, created when a test starts failing
+    Incidents(subcommands::Incidents),
     /// Queue the test runner to re-run immediately
     ReRun(subcommands::ReRun),
 }
@@ -176,10 +184,14 @@ impl Runnable for SubCommand {
         match &self {
             SubCommand::Session(a) => a.run(opts),
             SubCommand::Users(a) => a.run(opts),
+            SubCommand::ApiKeys(a) => a.run(opts),
             SubCommand::PasswordReset(a) => a.run(opts),
             SubCommand::Tests(a) => a.run(opts),
             SubCommand::TestResults(a) => a.run(opts),
             SubCommand::RunnerLogs(a) => a.run(opts),
+            SubCommand::MaintenanceWindows(a) => a.run(opts),
+            SubCommand::EscalationPolicies(a) => a.run(opts),
+            SubCommand::Incidents(a) => a.run(opts),
             SubCommand::ReRun(a) => a.run(opts),
         }
     }
@@ -0,0 +1,82 @@
+use crate::profile::Profile;
+use crate::response::ResponseExt;
+use crate::{CalpolError, GlobalOpts, Runnable, CLIENT};
+use calpol_model::api_v1::{CreateApiKeyRequest, Scope};
+use clap::{Parser, Subcommand};
+
+#[derive(Parser, Debug)]
+pub struct ApiKeys {
+    #[clap(subcommand)]
+    op: Operations,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum Operations {
+    /// List the current user's API keys
+    List,
+    /// Create a new API key
+    Create(Create),
+    /// Revoke an API key by id
+    Delete(Delete),
+}
+
+impl Runnable for ApiKeys {
+    fn run(&self, opts: &GlobalOpts) -> Result<String, CalpolError> {
+        let profile = Profile::load_profile(opts.profile.as_ref())?;
+        match &self.op {
+            Operations::List => list(opts, &profile),
+            Operations::Create(c) => create(opts, &profile, c),
+            Operations::Delete(d) => delete(opts, &profile, d),
+        }
+    }
+}
+
+fn list(_: &GlobalOpts, profile: &Profile) -> Result<String, CalpolError> {
+    CLIENT
+        .get(profile.route_url("api/v1/api_keys"))
+        .bearer_auth(&profile.token)
+        .send()?
+        .verify_success()?
+        .json_pretty()
+}
+
+#[derive(Parser, Debug)]
+pub struct Create {
+    name: String,
+    /// Scope the key may be used for (e.g. tests:write, results:read, re_run), may be repeated
+    #[clap(long = "scope", required = true)]
+    scopes: Vec<Scope>,
+    /// When the key expires (RFC3339 timestamp) (defaults to never)
+    #[clap(long)]
+    expires: Option<String>,
+}
+
+fn create(_: &GlobalOpts, profile: &Profile, args: &Create) -> Result<String, CalpolError> {
+    let item = CreateApiKeyRequest {
+        name: args.name.clone(),
+        scopes: args.scopes.clone(),
+        expires: args.expires.clone(),
+    };
+    CLIENT
+        .post(profile.route_url("api/v1/api_keys"))
+        .bearer_auth(&profile.token)
+        .json(&item)
+        .send()?
+        .verify_success()?
+        .json_pretty()
+}
+
+#[derive(Parser, Debug)]
+pub struct Delete {
+    /// ID of API key to revoke
+    id: i32,
+}
+
+fn delete(_: &GlobalOpts, profile: &Profile, args: &Delete) -> Result<String, CalpolError> {
+    CLIENT
+        .delete(profile.route_url_with_id("api/v1/api_keys/", &args.id))
+        .bearer_auth(&profile.token)
+        .send()?
+        .verify_success()?;
+    Ok(format!("Successfully revoked API key {}", args.id))
+}
@@ -0,0 +1,171 @@
+use crate::profile::Profile;
+use crate::response::ResponseExt;
+use crate::{CalpolError, GlobalOpts, Runnable, CLIENT};
+use calpol_model::api_v1::{CreateEscalationPolicyRequest, UpdateEscalationPolicyRequest};
+use clap::{Parser, Subcommand};
+
+#[derive(Parser, Debug)]
+pub struct EscalationPolicies {
+    #[clap(subcommand)]
+    op: Operations,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum Operations {
+    /// List escalation policies
+    List(List),
+    /// Create a new escalation policy
+    Create(Create),
+    /// Get an escalation policy by id
+    Get(Get),
+    /// Delete an escalation policy by id
+    Delete(Delete),
+    /// Update an escalation policy by id
+    Update(Update),
+}
+
+impl Runnable for EscalationPolicies {
+    fn run(&self, opts: &GlobalOpts) -> Result<String, CalpolError> {
+        let profile = Profile::load_profile(opts.profile.as_ref())?;
+        match &self.op {
+            Operations::List(l) => list(opts, &profile, l),
+            Operations::Create(c) => create(opts, &profile, c),
+            Operations::Get(g) => get(opts, &profile, g),
+            Operations::Delete(d) => delete(opts, &profile, d),
+            Operations::Update(u) => update(opts, &profile, u),
+        }
+    }
+}
+
+#[derive(Parser, Debug)]
+pub struct List {}
+
+fn list(_: &GlobalOpts, profile: &Profile, _: &List) -> Result<String, CalpolError> {
+    CLIENT
+        .get(profile.route_url("api/v1/escalation_policies"))
+        .bearer_auth(&profile.token)
+        .send()?
+        .verify_success()?
+        .json_pretty()
+}
+
+#[derive(Parser, Debug)]
+pub struct Create {
+    name: String,
+    /// Minutes between reminders while a test is still failing
+    #[clap(long)]
+    repeat_interval: Option<u16>,
+    /// Minutes a test can be failing before it is escalated
+    #[clap(long)]
+    escalate_after: Option<u16>,
+    /// ID of a user to notify when escalating, may be repeated
+    #[clap(long = "user")]
+    users: Vec<i32>,
+    /// Name of a notification channel to notify when escalating, may be repeated
+    #[clap(long = "channel")]
+    channels: Vec<String>,
+    /// Name of a test the policy applies to, may be repeated
+    #[clap(long = "test")]
+    tests: Vec<String>,
+}
+
+fn create(_: &GlobalOpts, profile: &Profile, args: &Create) -> Result<String, CalpolError> {
+    let item = CreateEscalationPolicyRequest {
+        name: args.name.clone(),
+        repeat_interval: args.repeat_interval,
+        escalate_after: args.escalate_after,
+        escalate_to_users: args.users.clone(),
+        escalate_to_channels: args.channels.clone(),
+        tests: args.tests.clone(),
+    };
+    CLIENT
+        .post(profile.route_url("api/v1/escalation_policies"))
+        .bearer_auth(&profile.token)
+        .json(&item)
+        .send()?
+        .verify_success()?
+        .json_pretty()
+}
+
+#[derive(Parser, Debug)]
+pub struct Get {
+    /// ID of escalation policy to get
+    id: i32,
+}
+
+fn get(_: &GlobalOpts, profile: &Profile, args: &Get) -> Result<String, CalpolError> {
+    CLIENT
+        .get(profile.route_url_with_id("api/v1/escalation_policies/", &args.id))
+        .bearer_auth(&profile.token)
+        .send()?
+        .verify_success()?
+        .json_pretty()
+}
+
+#[derive(Parser, Debug)]
+pub struct Update {
+    /// ID of escalation policy to update
+    id: i32,
+    #[clap(long)]
+    name: Option<String>,
+    /// Minutes between reminders while a test is still failing (0 to disable)
+    #[clap(long)]
+    repeat_interval: Option<u16>,
+    /// Minutes a test can be failing before it is escalated (0 to disable)
+    #[clap(long)]
+    escalate_after: Option<u16>,
+    /// ID of a user to notify when escalating, may be repeated (replaces existing users)
+    #[clap(long = "user")]
+    users: Vec<i32>,
+    /// Name of a notification channel to notify when escalating, may be repeated (replaces
+    /// existing channels)
+    #[clap(long = "channel")]
+    channels: Vec<String>,
+    /// Name of a test the policy applies to, may be repeated (replaces existing tests)
+    #[clap(long = "test")]
+    tests: Vec<String>,
+}
+
+fn update(_: &GlobalOpts, profile: &Profile, args: &Update) -> Result<String, CalpolError> {
+    let item = UpdateEscalationPolicyRequest {
+        name: args.name.clone(),
+        repeat_interval: args.repeat_interval,
+        escalate_after: args.escalate_after,
+        escalate_to_users: non_empty(&args.users),
+        escalate_to_channels: non_empty(&args.channels),
+        tests: non_empty(&args.tests),
+    };
+    CLIENT
+        .put(profile.route_url_with_id("api/v1/escalation_policies/", &args.id))
+        .bearer_auth(&profile.token)
+        .json(&item)
+        .send()?
+        .verify_success()?
+        .json_pretty()
+}
+
+fn non_empty<T: Clone>(values: &[T]) -> Option<Vec<T>> {
+    if values.is_empty() {
+        None
+    } else {
+        Some(values.to_vec())
+    }
+}
+
+#[derive(Parser, Debug)]
+pub struct Delete {
+    /// ID of escalation policy to delete
+    id: i32,
+}
+
+fn delete(_: &GlobalOpts, profile: &Profile, args: &Delete) -> Result<String, CalpolError> {
+    CLIENT
+        .delete(profile.route_url_with_id("api/v1/escalation_policies/", &args.id))
+        .bearer_auth(&profile.token)
+        .send()?
+        .verify_success()?;
+    Ok(format!(
+        "Successfully deleted escalation policy {}",
+        args.id
+    ))
+}
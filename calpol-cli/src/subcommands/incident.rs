@@ -0,0 +1,125 @@
+use crate::profile::Profile;
+use crate::response::ResponseExt;
+use crate::{CalpolError, GlobalOpts, Runnable, CLIENT};
+use calpol_model::api_v1::{CreateIncidentNoteRequest, ListIncidentsRequest};
+use clap::{Parser, Subcommand};
+
+#[derive(Parser, Debug)]
+pub struct Incidents {
+    #[clap(subcommand)]
+    op: Operations,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum Operations {
+    /// List incidents, most recent first, with the mean time to resolve
+    List(List),
+    /// Show an incident by id, including its timeline
+    Show(Show),
+    /// Acknowledge an incident, stopping any reminders or escalation
+    Ack(Ack),
+    /// Resolve an incident, even if the test is still failing
+    Resolve(Resolve),
+    /// Add a note to the timeline of an incident
+    Note(Note),
+}
+
+impl Runnable for Incidents {
+    fn run(&self, opts: &GlobalOpts) -> Result<String, CalpolError> {
+        let profile = Profile::load_profile(opts.profile.as_ref())?;
+        match &self.op {
+            Operations::List(l) => list(opts, &profile, l),
+            Operations::Show(s) => show(opts, &profile, s),
+            Operations::Ack(a) => ack(opts, &profile, a),
+            Operations::Resolve(r) => resolve(opts, &profile, r),
+            Operations::Note(n) => note(opts, &profile, n),
+        }
+    }
+}
+
+#[derive(Parser, Debug)]
+pub struct List {
+    /// Page number
+    page: Option<u32>,
+    /// Only list incidents of this test
+    #[clap(long)]
+    test: Option<String>,
+}
+
+fn list(opts: &GlobalOpts, profile: &Profile, args: &List) -> Result<String, CalpolError> {
+    CLIENT
+        .get(profile.route_url("api/v1/incidents"))
+        .bearer_auth(&profile.token)
+        .json(&ListIncidentsRequest {
+            limit: opts.page_size,
+            offset: opts.get_offset(args.page)?,
+            test: args.test.clone(),
+        })
+        .send()?
+        .verify_success()?
+        .json_pretty()
+}
+
+#[derive(Parser, Debug)]
+pub struct Show {
+    /// ID of incident to show
+    id: i32,
+}
+
+fn show(_: &GlobalOpts, profile: &Profile, args: &Show) -> Result<String, CalpolError> {
+    CLIENT
+        .get(profile.route_url_with_id("api/v1/incidents/", &args.id))
+        .bearer_auth(&profile.token)
+        .send()?
+        .verify_success()?
+        .json_pretty()
+}
+
+#[derive(Parser, Debug)]
+pub struct Ack {
+    /// ID of incident to acknowledge
+    id: i32,
+}
+
+fn ack(_: &GlobalOpts, profile: &Profile, args: &Ack) -> Result<String, CalpolError> {
+    CLIENT
+        .post(profile.route_url_with_id_and("api/v1/incidents/", &args.id, "acknowledge"))
+        .bearer_auth(&profile.token)
+        .send()?
+        .verify_success()?
+        .json_pretty()
+}
+
+#[derive(Parser, Debug)]
+pub struct Resolve {
+    /// ID of incident to resolve
+    id: i32,
+}
+
+fn resolve(_: &GlobalOpts, profile: &Profile, args: &Resolve) -> Result<String, CalpolError> {
+    CLIENT
+        .post(profile.route_url_with_id_and("api/v1/incidents/", &args.id, "resolve"))
+        .bearer_auth(&profile.token)
+        .send()?
+        .verify_success()?
+        .json_pretty()
+}
+
+#[derive(Parser, Debug)]
+pub struct Note {
+    /// ID of incident to add the note to
+    id: i32,
+    message: String,
+}
+
+fn note(_: &GlobalOpts, profile: &Profile, args: &Note) -> Result<String, CalpolError> {
+    CLIENT
+        .post(profile.route_url_with_id_and("api/v1/incidents/", &args.id, "notes"))
+        .bearer_auth(&profile.token)
+        .json(&CreateIncidentNoteRequest {
+            message: args.message.clone(),
+        })
+        .send()?
+        .verify_success()?
+        .json_pretty()
+}
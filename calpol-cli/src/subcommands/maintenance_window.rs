@@ -0,0 +1,145 @@
+use crate::profile::Profile;
+use crate::response::ResponseExt;
+use crate::{CalpolError, GlobalOpts, Runnable, CLIENT};
+use calpol_model::api_v1::{CreateMaintenanceWindowRequest, UpdateMaintenanceWindowRequest};
+use clap::{Parser, Subcommand};
+
+#[derive(Parser, Debug)]
+pub struct MaintenanceWindows {
+    #[clap(subcommand)]
+    op: Operations,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum Operations {
+    /// List maintenance windows
+    List(List),
+    /// Create a new maintenance window
+    Create(Create),
+    /// Get a maintenance window by id
+    Get(Get),
+    /// Delete a maintenance window by id
+    Delete(Delete),
+    /// Update a maintenance window by id
+    Update(Update),
+}
+
+impl Runnable for MaintenanceWindows {
+    fn run(&self, opts: &GlobalOpts) -> Result<String, CalpolError> {
+        let profile = Profile::load_profile(opts.profile.as_ref())?;
+        match &self.op {
+            Operations::List(l) => list(opts, &profile, l),
+            Operations::Create(c) => create(opts, &profile, c),
+            Operations::Get(g) => get(opts, &profile, g),
+            Operations::Delete(d) => delete(opts, &profile, d),
+            Operations::Update(u) => update(opts, &profile, u),
+        }
+    }
+}
+
+#[derive(Parser, Debug)]
+pub struct List {}
+
+fn list(_: &GlobalOpts, profile: &Profile, _: &List) -> Result<String, CalpolError> {
+    CLIENT
+        .get(profile.route_url("api/v1/maintenance_windows"))
+        .bearer_auth(&profile.token)
+        .send()?
+        .verify_success()?
+        .json_pretty()
+}
+
+#[derive(Parser, Debug)]
+pub struct Create {
+    name: String,
+    /// End of the window (RFC3339 timestamp)
+    end: String,
+    /// Start of the window (RFC3339 timestamp) (defaults to now)
+    #[clap(long)]
+    start: Option<String>,
+    /// Name of a test in maintenance, may be repeated (defaults to all tests)
+    #[clap(long = "test")]
+    tests: Vec<String>,
+}
+
+fn create(_: &GlobalOpts, profile: &Profile, args: &Create) -> Result<String, CalpolError> {
+    let item = CreateMaintenanceWindowRequest {
+        name: args.name.clone(),
+        start: args.start.clone(),
+        end: args.end.clone(),
+        tests: if args.tests.is_empty() {
+            None
+        } else {
+            Some(args.tests.clone())
+        },
+    };
+    CLIENT
+        .post(profile.route_url("api/v1/maintenance_windows"))
+        .bearer_auth(&profile.token)
+        .json(&item)
+        .send()?
+        .verify_success()?
+        .json_pretty()
+}
+
+#[derive(Parser, Debug)]
+pub struct Get {
+    /// ID of maintenance window to get
+    id: i32,
+}
+
+fn get(_: &GlobalOpts, profile: &Profile, args: &Get) -> Result<String, CalpolError> {
+    CLIENT
+        .get(profile.route_url_with_id("api/v1/maintenance_windows/", &args.id))
+        .bearer_auth(&profile.token)
+        .send()?
+        .verify_success()?
+        .json_pretty()
+}
+
+#[derive(Parser, Debug)]
+pub struct Update {
+    /// ID of maintenance window to update
+    id: i32,
+    #[clap(long)]
+    name: Option<String>,
+    /// Start of the window (RFC3339 timestamp)
+    #[clap(long)]
+    start: Option<String>,
+    /// End of the window (RFC3339 timestamp)
+    #[clap(long)]
+    end: Option<String>,
+}
+
+fn update(_: &GlobalOpts, profile: &Profile, args: &Update) -> Result<String, CalpolError> {
+    let item = UpdateMaintenanceWindowRequest {
+        name: args.name.clone(),
+        start: args.start.clone(),
+        end: args.end.clone(),
+    };
+    CLIENT
+        .put(profile.route_url_with_id("api/v1/maintenance_windows/", &args.id))
+        .bearer_auth(&profile.token)
+        .json(&item)
+        .send()?
+        .verify_success()?
+        .json_pretty()
+}
+
+#[derive(Parser, Debug)]
+pub struct Delete {
+    /// ID of maintenance window to delete
+    id: i32,
+}
+
+fn delete(_: &GlobalOpts, profile: &Profile, args: &Delete) -> Result<String, CalpolError> {
+    CLIENT
+        .delete(profile.route_url_with_id("api/v1/maintenance_windows/", &args.id))
+        .bearer_auth(&profile.token)
+        .send()?
+        .verify_success()?;
+    Ok(format!(
+        "Successfully deleted maintenance window {}",
+        args.id
+    ))
+}
@@ -1,3 +1,7 @@
+mod api_key;
+mod escalation_policy;
+mod incident;
+mod maintenance_window;
 mod password_reset;
 mod re_run;
 mod runner_logs;
@@ -6,6 +10,10 @@ mod test;
 mod test_results;
 mod user;
 
+pub use api_key::ApiKeys;
+pub use escalation_policy::EscalationPolicies;
+pub use incident::Incidents;
+pub use maintenance_window::MaintenanceWindows;
 pub use password_reset::PasswordReset;
 pub use re_run::ReRun;
 pub use runner_logs::RunnerLogs;
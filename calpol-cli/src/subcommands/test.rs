@@ -141,6 +141,10 @@ fn upsert(_: &GlobalOpts, profile: &Profile, args: &Upsert) -> Result<String, Ca
             config: Some(item.config),
             enabled: Some(item.enabled),
             failure_threshold: Some(item.failure_threshold),
+            interval_minutes: Some(item.interval_minutes),
+            timeout_seconds: Some(item.timeout_seconds),
+            tags: Some(item.tags),
+            severity: Some(item.severity),
         };
         CLIENT
             .put(profile.route_url_with_id("api/v1/tests/", &item.name))
@@ -1,7 +1,10 @@
 use crate::profile::Profile;
 use crate::response::ResponseExt;
 use crate::{CalpolError, ClientError, GlobalOpts, Runnable, CLIENT};
-use calpol_model::api_v1::{CreateUserRequest, ListUsersRequest, UpdateUserRequest};
+use calpol_model::api_v1::{
+    CreateUserRequest, ListUsersRequest, QuietHours, QuietHoursSms, Role, Severity,
+    UpdateUserRequest, UserSubscriptions,
+};
 use clap::{Parser, Subcommand};
 
 #[derive(Parser, Debug)]
@@ -26,6 +29,10 @@ pub enum Operations {
     TestEmail(TestEmail),
     /// Send a test SMS to the user
     TestSms(TestSms),
+    /// Get the tests a user is notified about
+    Subscriptions(Subscriptions),
+    /// Set the tests a user is notified about
+    Subscribe(Subscribe),
 }
 
 impl Runnable for Users {
@@ -39,6 +46,8 @@ impl Runnable for Users {
             Operations::Update(u) => update(opts, &profile, u),
             Operations::TestEmail(a) => test_email(opts, &profile, a),
             Operations::TestSms(a) => test_sms(opts, &profile, a),
+            Operations::Subscriptions(a) => subscriptions(opts, &profile, a),
+            Operations::Subscribe(a) => subscribe(opts, &profile, a),
         }
     }
 }
@@ -69,12 +78,16 @@ fn list(opts: &GlobalOpts, profile: &Profile, args: &List) -> Result<String, Cal
 pub struct Create {
     name: String,
     email: String,
+    /// Role of the user (viewer, operator or admin)
+    #[clap(long, default_value = "viewer")]
+    role: Role,
 }
 
 fn create(_: &GlobalOpts, profile: &Profile, args: &Create) -> Result<String, CalpolError> {
     let item = CreateUserRequest {
         name: args.name.clone(),
         email: args.email.clone(),
+        role: args.role,
     };
     CLIENT
         .post(profile.route_url("api/v1/users"))
@@ -109,12 +122,33 @@ pub struct Update {
     name: Option<String>,
     #[clap(long)]
     email: Option<String>,
+    /// Role of the user (viewer, operator or admin), may only be changed by admins
+    #[clap(long)]
+    role: Option<Role>,
     #[clap(long)]
     phone_number: Option<String>,
     #[clap(long)]
     sms_notifications: Option<bool>,
     #[clap(long)]
     email_notifications: Option<bool>,
+    /// Only email about tests of at least this severity (low, medium, high or critical)
+    #[clap(long)]
+    email_min_severity: Option<Severity>,
+    /// Only send SMS about tests of at least this severity (low, medium, high or critical)
+    #[clap(long)]
+    sms_min_severity: Option<Severity>,
+    /// IANA timezone the quiet hours are in, e.g. Europe/London
+    #[clap(long)]
+    timezone: Option<String>,
+    /// Start of the daily quiet hours (HH:MM), when SMS are only sent for critical tests
+    #[clap(long, requires = "quiet_hours_end")]
+    quiet_hours_start: Option<String>,
+    /// End of the daily quiet hours (HH:MM), set to the start time to turn quiet hours off
+    #[clap(long, requires = "quiet_hours_start")]
+    quiet_hours_end: Option<String>,
+    /// What to do with SMS during quiet hours (defer until they end, or email instead)
+    #[clap(long, requires = "quiet_hours_start")]
+    quiet_hours_sms: Option<QuietHoursSms>,
 }
 
 fn update(_: &GlobalOpts, profile: &Profile, args: &Update) -> Result<String, CalpolError> {
@@ -122,9 +156,22 @@ fn update(_: &GlobalOpts, profile: &Profile, args: &Update) -> Result<String, Ca
     let item = UpdateUserRequest {
         name: args.name.clone(),
         email: args.email.clone(),
+        role: args.role,
         phone_number: args.phone_number.clone(),
         sms_notifications: args.sms_notifications,
         email_notifications: args.email_notifications,
+        email_min_severity: args.email_min_severity,
+        sms_min_severity: args.sms_min_severity,
+        timezone: args.timezone.clone(),
+        quiet_hours: args
+            .quiet_hours_start
+            .as_ref()
+            .zip(args.quiet_hours_end.as_ref())
+            .map(|(start, end)| QuietHours {
+                start: start.clone(),
+                end: end.clone(),
+                sms: args.quiet_hours_sms.unwrap_or_default(),
+            }),
     };
     CLIENT
         .put(profile.route_url_with_id("api/v1/users/", &id))
@@ -183,6 +230,65 @@ fn test_sms(_: &GlobalOpts, profile: &Profile, args: &TestSms) -> Result<String,
     Ok(format!("Successfully sent test SMS for user {}", args.id))
 }
 
+#[derive(Parser, Debug)]
+pub struct Subscriptions {
+    /// ID of user to get the subscriptions of
+    id: String,
+}
+
+fn subscriptions(
+    _: &GlobalOpts,
+    profile: &Profile,
+    args: &Subscriptions,
+) -> Result<String, CalpolError> {
+    let id = resolve_user_id(&args.id, profile)?;
+    CLIENT
+        .get(profile.route_url_with_id_and("api/v1/users/", &id, "subscriptions"))
+        .bearer_auth(&profile.token)
+        .send()?
+        .verify_success()?
+        .json_pretty()
+}
+
+#[derive(Parser, Debug)]
+pub struct Subscribe {
+    /// ID of user to set the subscriptions of
+    id: String,
+    /// Only be notified about the tests and tags that are subscribed to
+    #[clap(long)]
+    only: bool,
+    /// Name of a test to subscribe to, may be repeated
+    #[clap(long = "test")]
+    tests: Vec<String>,
+    /// Subscribe to tests with this tag, may be repeated
+    #[clap(long = "tag")]
+    tags: Vec<String>,
+    /// Name of a test to never be notified about, may be repeated
+    #[clap(long = "exclude-test")]
+    excluded_tests: Vec<String>,
+    /// Never be notified about tests with this tag, may be repeated
+    #[clap(long = "exclude-tag")]
+    excluded_tags: Vec<String>,
+}
+
+fn subscribe(_: &GlobalOpts, profile: &Profile, args: &Subscribe) -> Result<String, CalpolError> {
+    let id = resolve_user_id(&args.id, profile)?;
+    let item = UserSubscriptions {
+        all_tests: !args.only,
+        tests: args.tests.clone(),
+        tags: args.tags.clone(),
+        excluded_tests: args.excluded_tests.clone(),
+        excluded_tags: args.excluded_tags.clone(),
+    };
+    CLIENT
+        .put(profile.route_url_with_id_and("api/v1/users/", &id, "subscriptions"))
+        .bearer_auth(&profile.token)
+        .json(&item)
+        .send()?
+        .verify_success()?
+        .json_pretty()
+}
+
 fn resolve_user_id(input: &str, profile: &Profile) -> Result<i32, ClientError> {
     if input.to_ascii_lowercase() == "self" {
         return Ok(profile.user.id);
@@ -1,5 +1,7 @@
 use crate::tests::TestConfig;
 use serde::{Deserialize, Serialize};
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
 #[cfg(feature = "validator")]
 use validator::Validate;
 
@@ -10,6 +12,243 @@ type EmailAddress = lettre::address::Address;
 #[cfg(not(feature = "lettre"))]
 type EmailAddress = String;
 
+/// What a user is allowed to do, each role includes the permissions of the roles before it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Role {
+    /// Can view tests, results and incidents, and manage their own account
+    Viewer,
+    /// Can also manage tests, maintenance windows, escalation policies and incidents
+    Operator,
+    /// Can also manage other users
+    Admin,
+}
+
+impl Default for Role {
+    fn default() -> Self {
+        Role::Viewer
+    }
+}
+
+impl Role {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Role::Viewer => "viewer",
+            Role::Operator => "operator",
+            Role::Admin => "admin",
+        }
+    }
+}
+
+impl Display for Role {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for Role {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "viewer" => Ok(Role::Viewer),
+            "operator" => Ok(Role::Operator),
+            "admin" => Ok(Role::Admin),
+            _ => Err(format!("Unknown role: {}", s)),
+        }
+    }
+}
+
+/// What an API key may be used for, the role of the user that owns the key still applies.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Scope {
+    /// View tests
+    #[serde(rename = "tests:read")]
+    TestsRead,
+    /// Create, update and delete tests
+    #[serde(rename = "tests:write")]
+    TestsWrite,
+    /// View test results and runner logs
+    #[serde(rename = "results:read")]
+    ResultsRead,
+    /// Queue the test runner to re-run immediately
+    #[serde(rename = "re_run")]
+    ReRun,
+    /// View incidents
+    #[serde(rename = "incidents:read")]
+    IncidentsRead,
+    /// Acknowledge, resolve and add notes to incidents
+    #[serde(rename = "incidents:write")]
+    IncidentsWrite,
+    /// View maintenance windows
+    #[serde(rename = "maintenance_windows:read")]
+    MaintenanceWindowsRead,
+    /// Create, update and delete maintenance windows
+    #[serde(rename = "maintenance_windows:write")]
+    MaintenanceWindowsWrite,
+    /// View escalation policies
+    #[serde(rename = "escalation_policies:read")]
+    EscalationPoliciesRead,
+    /// Create, update and delete escalation policies
+    #[serde(rename = "escalation_policies:write")]
+    EscalationPoliciesWrite,
+    /// View users and their subscriptions
+    #[serde(rename = "users:read")]
+    UsersRead,
+    /// Create, update and delete users
+    #[serde(rename = "users:write")]
+    UsersWrite,
+}
+
+impl Scope {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Scope::TestsRead => "tests:read",
+            Scope::TestsWrite => "tests:write",
+            Scope::ResultsRead => "results:read",
+            Scope::ReRun => "re_run",
+            Scope::IncidentsRead => "incidents:read",
+            Scope::IncidentsWrite => "incidents:write",
+            Scope::MaintenanceWindowsRead => "maintenance_windows:read",
+            Scope::MaintenanceWindowsWrite => "maintenance_windows:write",
+            Scope::EscalationPoliciesRead => "escalation_policies:read",
+            Scope::EscalationPoliciesWrite => "escalation_policies:write",
+            Scope::UsersRead => "users:read",
+            Scope::UsersWrite => "users:write",
+        }
+    }
+}
+
+impl Display for Scope {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for Scope {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "tests:read" => Ok(Scope::TestsRead),
+            "tests:write" => Ok(Scope::TestsWrite),
+            "results:read" => Ok(Scope::ResultsRead),
+            "re_run" => Ok(Scope::ReRun),
+            "incidents:read" => Ok(Scope::IncidentsRead),
+            "incidents:write" => Ok(Scope::IncidentsWrite),
+            "maintenance_windows:read" => Ok(Scope::MaintenanceWindowsRead),
+            "maintenance_windows:write" => Ok(Scope::MaintenanceWindowsWrite),
+            "escalation_policies:read" => Ok(Scope::EscalationPoliciesRead),
+            "escalation_policies:write" => Ok(Scope::EscalationPoliciesWrite),
+            "users:read" => Ok(Scope::UsersRead),
+            "users:write" => Ok(Scope::UsersWrite),
+            _ => Err(format!("Unknown scope: {}", s)),
+        }
+    }
+}
+
+/// What happens to SMS notifications during a user's quiet hours.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum QuietHoursSms {
+    /// Hold back the SMS until the quiet hours end
+    Defer,
+    /// Send an email instead of the SMS
+    Email,
+}
+
+impl Default for QuietHoursSms {
+    fn default() -> Self {
+        QuietHoursSms::Defer
+    }
+}
+
+impl QuietHoursSms {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            QuietHoursSms::Defer => "defer",
+            QuietHoursSms::Email => "email",
+        }
+    }
+}
+
+impl Display for QuietHoursSms {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for QuietHoursSms {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "defer" => Ok(QuietHoursSms::Defer),
+            "email" => Ok(QuietHoursSms::Email),
+            _ => Err(format!("Unknown quiet hours SMS option: {}", s)),
+        }
+    }
+}
+
+/// A daily window, in the user's timezone, during which SMS notifications are not sent unless the
+/// test is critical.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct QuietHours {
+    /// Start time, in HH:MM format
+    pub start: String,
+    /// End time, in HH:MM format. If this is before the start the quiet hours run overnight
+    pub end: String,
+    #[serde(default)]
+    pub sms: QuietHoursSms,
+}
+
+/// How important a test is, used to route notifications.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Severity {
+    Low,
+    Medium,
+    High,
+    Critical,
+}
+
+impl Default for Severity {
+    fn default() -> Self {
+        Severity::Medium
+    }
+}
+
+impl Severity {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Severity::Low => "low",
+            Severity::Medium => "medium",
+            Severity::High => "high",
+            Severity::Critical => "critical",
+        }
+    }
+}
+
+impl Display for Severity {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for Severity {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "low" => Ok(Severity::Low),
+            "medium" => Ok(Severity::Medium),
+            "high" => Ok(Severity::High),
+            "critical" => Ok(Severity::Critical),
+            _ => Err(format!("Unknown severity: {}", s)),
+        }
+    }
+}
+
 #[cfg_attr(feature = "validator", derive(Validate))]
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct LoginRequest {
@@ -22,9 +261,22 @@ pub struct UserSummary {
     pub id: i32,
     pub name: String,
     pub email: String,
+    // Defaults allow loading CLI profiles saved before these fields were added
+    #[serde(default)]
+    pub role: Role,
     pub phone_number: Option<String>,
     pub sms_notifications: bool,
     pub email_notifications: bool,
+    /// Only email about tests of at least this severity
+    #[serde(default)]
+    pub email_min_severity: Severity,
+    /// Only send SMS about tests of at least this severity
+    #[serde(default)]
+    pub sms_min_severity: Severity,
+    /// IANA timezone of the user, e.g. `Europe/London`
+    #[serde(default)]
+    pub timezone: String,
+    pub quiet_hours: Option<QuietHours>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -43,6 +295,34 @@ pub struct LoginResponse {
     pub token: String,
 }
 
+#[cfg_attr(feature = "validator", derive(Validate))]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CreateApiKeyRequest {
+    #[cfg_attr(feature = "validator", validate(length(min = 1, max = 255)))]
+    pub name: String,
+    #[cfg_attr(feature = "validator", validate(length(min = 1)))]
+    pub scopes: Vec<Scope>,
+    /// RFC3339 timestamp (defaults to never expiring)
+    pub expires: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ApiKeySummary {
+    pub id: i32,
+    pub name: String,
+    pub scopes: Vec<Scope>,
+    pub created: i64,
+    pub expires: Option<i64>,
+    pub last_used: Option<i64>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CreateApiKeyResponse {
+    pub api_key: ApiKeySummary,
+    /// The key is only shown once, it should be sent as a bearer token
+    pub token: String,
+}
+
 #[cfg_attr(feature = "validator", derive(Validate))]
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(default)]
@@ -75,6 +355,8 @@ pub struct CreateUserRequest {
     #[cfg_attr(feature = "validator", validate(length(min = 1, max = 255)))]
     pub name: String,
     pub email: EmailAddress,
+    #[serde(default)]
+    pub role: Role,
 }
 
 #[cfg_attr(feature = "validator", derive(Validate))]
@@ -83,10 +365,47 @@ pub struct UpdateUserRequest {
     #[cfg_attr(feature = "validator", validate(length(min = 1, max = 255)))]
     pub name: Option<String>,
     pub email: Option<EmailAddress>,
+    /// Only admins may change roles
+    pub role: Option<Role>,
     #[cfg_attr(feature = "validator", validate(phone))]
     pub phone_number: Option<String>,
     pub sms_notifications: Option<bool>,
     pub email_notifications: Option<bool>,
+    pub email_min_severity: Option<Severity>,
+    pub sms_min_severity: Option<Severity>,
+    #[cfg_attr(feature = "validator", validate(length(min = 1, max = 64)))]
+    pub timezone: Option<String>,
+    /// Setting the start and end to the same time turns off quiet hours
+    pub quiet_hours: Option<QuietHours>,
+}
+
+/// The tests a user is notified about. Exclusions take priority over subscriptions.
+#[cfg_attr(feature = "validator", derive(Validate))]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct UserSubscriptions {
+    /// Subscribe to all tests, other than those excluded
+    pub all_tests: bool,
+    /// Names of tests to subscribe to
+    pub tests: Vec<String>,
+    /// Subscribe to tests that have any of these tags
+    pub tags: Vec<String>,
+    /// Names of tests to never be notified about
+    pub excluded_tests: Vec<String>,
+    /// Never be notified about tests that have any of these tags
+    pub excluded_tags: Vec<String>,
+}
+
+impl Default for UserSubscriptions {
+    fn default() -> Self {
+        UserSubscriptions {
+            all_tests: true,
+            tests: vec![],
+            tags: vec![],
+            excluded_tests: vec![],
+            excluded_tags: vec![],
+        }
+    }
 }
 
 #[cfg_attr(feature = "validator", derive(Validate))]
@@ -113,6 +432,19 @@ pub struct CreateTestRequest {
     #[serde(default = "default_test_failure_threshold")]
     #[cfg_attr(feature = "validator", validate(range(min = 1)))]
     pub failure_threshold: u8,
+    /// How frequently to run the test (defaults to the runner interval)
+    #[serde(alias = "interval")]
+    #[cfg_attr(feature = "validator", validate(range(min = 1)))]
+    pub interval_minutes: Option<u16>,
+    /// Maximum time the test is allowed to run (defaults to the runner timeout)
+    #[serde(alias = "timeout")]
+    #[cfg_attr(feature = "validator", validate(range(min = 1)))]
+    pub timeout_seconds: Option<u16>,
+    /// Labels used to subscribe to groups of tests
+    #[serde(default)]
+    pub tags: Vec<String>,
+    #[serde(default)]
+    pub severity: Severity,
 }
 
 fn default_test_failure_threshold() -> u8 {
@@ -129,6 +461,33 @@ pub struct UpdateTestRequest {
     pub config: Option<TestConfig>,
     pub enabled: Option<bool>,
     pub failure_threshold: Option<u8>,
+    /// Set to `null` to use the runner interval again
+    #[serde(
+        default,
+        alias = "interval",
+        deserialize_with = "deserialize_some",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub interval_minutes: Option<Option<u16>>,
+    /// Set to `null` to use the runner timeout again
+    #[serde(
+        default,
+        alias = "timeout",
+        deserialize_with = "deserialize_some",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub timeout_seconds: Option<Option<u16>>,
+    pub tags: Option<Vec<String>>,
+    pub severity: Option<Severity>,
+}
+
+/// Distinguishes a field that is `null` (`Some(None)`) from one that is missing (`None`).
+fn deserialize_some<'de, T, D>(deserializer: D) -> Result<Option<T>, D::Error>
+where
+    T: Deserialize<'de>,
+    D: serde::Deserializer<'de>,
+{
+    T::deserialize(deserializer).map(Some)
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -138,6 +497,10 @@ pub struct TestSummary {
     pub enabled: bool,
     pub failure_threshold: u8,
     pub failing: bool,
+    pub interval_minutes: Option<u16>,
+    pub timeout_seconds: Option<u16>,
+    pub tags: Vec<String>,
+    pub severity: Severity,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -147,6 +510,17 @@ pub struct TestResultSummary {
     pub failure_reason: Option<String>,
     pub time_started: String,
     pub time_finished: String,
+    pub timings: TestTimings,
+}
+
+/// Time taken by each phase of a test in milliseconds, if applicable to the test type.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TestTimings {
+    pub dns_ms: Option<i32>,
+    pub connect_ms: Option<i32>,
+    pub tls_ms: Option<i32>,
+    pub first_byte_ms: Option<i32>,
+    pub total_ms: Option<i32>,
 }
 
 #[cfg_attr(feature = "validator", derive(Validate))]
@@ -180,3 +554,155 @@ pub struct RunnerLogSummary {
     pub tests_failed: Option<i32>,
     pub tests_skipped: Option<i32>,
 }
+
+#[cfg_attr(feature = "validator", derive(Validate))]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CreateMaintenanceWindowRequest {
+    #[cfg_attr(feature = "validator", validate(length(min = 1, max = 255)))]
+    pub name: String,
+    /// RFC3339 timestamp (defaults to now)
+    pub start: Option<String>,
+    /// RFC3339 timestamp
+    pub end: String,
+    /// Names of the tests in maintenance (defaults to all tests)
+    #[cfg_attr(feature = "validator", validate(length(min = 1)))]
+    pub tests: Option<Vec<String>>,
+}
+
+#[cfg_attr(feature = "validator", derive(Validate))]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UpdateMaintenanceWindowRequest {
+    #[cfg_attr(feature = "validator", validate(length(min = 1, max = 255)))]
+    pub name: Option<String>,
+    /// RFC3339 timestamp
+    pub start: Option<String>,
+    /// RFC3339 timestamp
+    pub end: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MaintenanceWindowSummary {
+    pub id: i32,
+    pub name: String,
+    pub start: String,
+    pub end: String,
+    /// Names of the tests in maintenance, `None` if the window applies to all tests
+    pub tests: Option<Vec<String>>,
+}
+
+#[cfg_attr(feature = "validator", derive(Validate))]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CreateEscalationPolicyRequest {
+    #[cfg_attr(feature = "validator", validate(length(min = 1, max = 255)))]
+    pub name: String,
+    /// Minutes between reminders while a test is still failing (defaults to no reminders)
+    #[cfg_attr(feature = "validator", validate(range(min = 1)))]
+    pub repeat_interval: Option<u16>,
+    /// Minutes a test can be failing before it is escalated (defaults to never escalating)
+    #[cfg_attr(feature = "validator", validate(range(min = 1)))]
+    pub escalate_after: Option<u16>,
+    /// IDs of users to notify when escalating
+    #[serde(default)]
+    pub escalate_to_users: Vec<i32>,
+    /// Names of notification channels to notify when escalating, e.g. `pagerduty`
+    #[serde(default)]
+    pub escalate_to_channels: Vec<String>,
+    /// Names of the tests the policy applies to
+    #[serde(default)]
+    pub tests: Vec<String>,
+}
+
+#[cfg_attr(feature = "validator", derive(Validate))]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UpdateEscalationPolicyRequest {
+    #[cfg_attr(feature = "validator", validate(length(min = 1, max = 255)))]
+    pub name: Option<String>,
+    /// Minutes between reminders while a test is still failing (0 to disable)
+    pub repeat_interval: Option<u16>,
+    /// Minutes a test can be failing before it is escalated (0 to disable)
+    pub escalate_after: Option<u16>,
+    pub escalate_to_users: Option<Vec<i32>>,
+    pub escalate_to_channels: Option<Vec<String>>,
+    pub tests: Option<Vec<String>>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EscalationPolicySummary {
+    pub id: i32,
+    pub name: String,
+    pub repeat_interval: Option<u16>,
+    pub escalate_after: Option<u16>,
+    /// IDs of the users to notify when escalating
+    pub escalate_to_users: Vec<i32>,
+    pub escalate_to_channels: Vec<String>,
+    /// Names of the tests the policy applies to
+    pub tests: Vec<String>,
+}
+
+#[cfg_attr(feature = "validator", derive(Validate))]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ListIncidentsRequest {
+    #[cfg_attr(feature = "validator", validate(range(min = 1, max = 100)))]
+    pub limit: u32,
+    pub offset: u32,
+    /// Only list the incidents of this test
+    pub test: Option<String>,
+}
+
+impl Default for ListIncidentsRequest {
+    fn default() -> Self {
+        ListIncidentsRequest {
+            limit: DEFAULT_LIMIT,
+            offset: 0,
+            test: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ListIncidentsResponse {
+    pub incidents: Vec<IncidentSummary>,
+    pub total: i64,
+    /// Mean duration in seconds of the incidents that have ended
+    pub mean_time_to_resolve: Option<f64>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct IncidentSummary {
+    pub id: i32,
+    pub test_name: String,
+    /// The reason the test was failing when the incident started
+    pub failure_reason: Option<String>,
+    pub started: String,
+    pub ended: Option<String>,
+    pub escalated: Option<String>,
+    pub acknowledged_at: Option<String>,
+    /// The user that acknowledged the incident, `None` if not acknowledged or the user was deleted
+    pub acknowledged_by: Option<UserSummary>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct IncidentDetails {
+    #[serde(flatten)]
+    pub incident: IncidentSummary,
+    /// Notifications, acknowledgements and notes, in the order they occurred
+    pub timeline: Vec<IncidentEventSummary>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct IncidentEventSummary {
+    pub time: String,
+    /// One of `opened`, `notified`, `escalated`, `acknowledged`, `note`, `resolved` or `ended`
+    pub kind: String,
+    /// The user that caused the event, `None` if caused by the runner
+    pub user_id: Option<i32>,
+    pub message: Option<String>,
+}
+
+#[cfg_attr(feature = "validator", derive(Validate))]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CreateIncidentNoteRequest {
+    #[cfg_attr(feature = "validator", validate(length(min = 1, max = 10000)))]
+    pub message: String,
+}
@@ -1,25 +1,156 @@
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 use url::Url;
 #[cfg(feature = "validator")]
 use validator::{Validate, ValidationErrors};
 
+/// Returned by the API in place of secrets, such as passwords and header values. If a test is
+/// updated with this placeholder the existing secret is kept.
+pub const REDACTED: &str = "<redacted>";
+
 #[cfg_attr(feature = "validator", derive(Validate))]
 #[derive(Clone, Serialize, Deserialize, Debug)]
 pub struct TestConfig {
     #[serde(default)]
     pub ip_version: IpVersion,
+    /// Restricts when the test may be run.
+    pub schedule: Option<Schedule>,
+    /// Fail if the test takes longer than this to respond, e.g. the HTTP request and response.
+    #[cfg_attr(feature = "validator", validate(range(min = 1)))]
+    pub max_response_time_ms: Option<u32>,
     #[serde(flatten)]
     #[cfg_attr(feature = "validator", validate)]
     pub variant: TestVariant,
 }
 
+impl TestConfig {
+    /// Replaces the secrets in the config with [REDACTED].
+    pub fn redact(&mut self) {
+        if let TestVariant::Http(http) = &mut self.variant {
+            for value in http.headers.values_mut() {
+                *value = REDACTED.to_string();
+            }
+            match &mut http.authentication {
+                Some(HttpAuthentication::Basic {
+                    password: Some(password),
+                    ..
+                }) => *password = REDACTED.to_string(),
+                Some(HttpAuthentication::Bearer { token }) => *token = REDACTED.to_string(),
+                _ => {}
+            }
+        }
+    }
+
+    /// Replaces any [REDACTED] placeholders with the secrets from the existing config.
+    /// Returns `false` if a placeholder has no existing secret to replace it with.
+    pub fn restore_redacted(&mut self, existing: Option<&TestConfig>) -> bool {
+        let http = match &mut self.variant {
+            TestVariant::Http(http) => http,
+            _ => return true,
+        };
+        let existing = match existing.map(|e| &e.variant) {
+            Some(TestVariant::Http(existing)) => Some(existing),
+            _ => None,
+        };
+        let mut restored = true;
+        for (name, value) in http.headers.iter_mut() {
+            if value == REDACTED {
+                match existing.and_then(|e| e.headers.get(name)) {
+                    Some(secret) => *value = secret.clone(),
+                    None => restored = false,
+                }
+            }
+        }
+        let existing_authentication = existing.and_then(|e| e.authentication.as_ref());
+        match (&mut http.authentication, existing_authentication) {
+            (
+                Some(HttpAuthentication::Basic {
+                    password: Some(password),
+                    ..
+                }),
+                existing,
+            ) if password == REDACTED => match existing {
+                Some(HttpAuthentication::Basic {
+                    password: Some(secret),
+                    ..
+                }) => *password = secret.clone(),
+                _ => restored = false,
+            },
+            (Some(HttpAuthentication::Bearer { token }), existing) if token == REDACTED => {
+                match existing {
+                    Some(HttpAuthentication::Bearer { token: secret }) => *token = secret.clone(),
+                    _ => restored = false,
+                }
+            }
+            _ => {}
+        }
+        restored
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct Schedule {
+    /// Cron expression determining when the test is run, replaces the test's interval.
+    /// E.g. `*/5 9-17 * * MON-FRI` (an optional leading seconds field is also accepted)
+    pub cron: Option<String>,
+    /// If set, the test will only be run during these windows, runs outside them are skipped.
+    #[serde(default)]
+    pub active_hours: Vec<ActiveHours>,
+    /// IANA timezone the schedule is evaluated in, e.g. `Europe/London`.
+    #[serde(default = "default_schedule_timezone")]
+    pub timezone: String,
+}
+
+fn default_schedule_timezone() -> String {
+    String::from("UTC")
+}
+
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct ActiveHours {
+    /// Days of the week the window applies to (defaults to every day).
+    #[serde(default = "default_active_days")]
+    pub days: Vec<Weekday>,
+    /// Start of the window in `HH:MM` format.
+    pub start: String,
+    /// End of the window in `HH:MM` format. (If before the start, the window ends the next day)
+    pub end: String,
+}
+
+fn default_active_days() -> Vec<Weekday> {
+    vec![
+        Weekday::Mon,
+        Weekday::Tue,
+        Weekday::Wed,
+        Weekday::Thu,
+        Weekday::Fri,
+        Weekday::Sat,
+        Weekday::Sun,
+    ]
+}
+
+#[derive(Copy, Clone, Serialize, Deserialize, Debug, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Weekday {
+    Mon,
+    Tue,
+    Wed,
+    Thu,
+    Fri,
+    Sat,
+    Sun,
+}
+
 #[derive(Clone, Serialize, Deserialize, Debug)]
 #[serde(tag = "type")]
 #[serde(rename_all = "snake_case")]
 pub enum TestVariant {
-    Http(Http),
+    Http(Box<Http>),
     Smtp(Smtp),
     Tcp(Tcp),
+    Dns(Dns),
+    Icmp(Icmp),
+    Tls(Tls),
+    Udp(Udp),
 }
 
 #[cfg(feature = "validator")]
@@ -29,6 +160,10 @@ impl Validate for TestVariant {
             TestVariant::Http(t) => t.validate(),
             TestVariant::Smtp(t) => t.validate(),
             TestVariant::Tcp(t) => t.validate(),
+            TestVariant::Dns(t) => t.validate(),
+            TestVariant::Icmp(t) => t.validate(),
+            TestVariant::Tls(t) => t.validate(),
+            TestVariant::Udp(t) => t.validate(),
         }
     }
 }
@@ -65,8 +200,23 @@ pub struct Http {
     /// HTTP request method.
     #[serde(default = "default_http_request_method")]
     pub method: String,
+    /// Additional HTTP request headers.
+    #[serde(default)]
+    pub headers: BTreeMap<String, String>,
+    /// HTTP request body.
+    pub body: Option<HttpRequestBody>,
+    pub authentication: Option<HttpAuthentication>,
+    /// Overrides the default User-Agent header.
+    pub user_agent: Option<String>,
     /// Expected HTTP response code (defaults to any success)
     pub expected_code: Option<u16>,
+    /// Assertions that must all hold for the response body.
+    #[serde(default)]
+    #[cfg_attr(feature = "validator", validate)]
+    pub body_assertions: Vec<BodyAssertion>,
+    /// Maximum number of bytes of the response body to read, the rest is ignored.
+    #[serde(default = "default_http_max_body_size")]
+    pub max_body_size: u32,
 }
 
 fn default_http_verify_ssl() -> bool {
@@ -85,6 +235,70 @@ fn default_http_request_method() -> String {
     String::from("GET")
 }
 
+fn default_http_max_body_size() -> u32 {
+    1024 * 1024
+}
+
+#[derive(Clone, Serialize, Deserialize, Debug)]
+#[serde(rename_all = "snake_case", tag = "type")]
+pub enum HttpRequestBody {
+    /// Sent as is, with an optional Content-Type header.
+    Raw {
+        content: String,
+        content_type: Option<String>,
+    },
+    /// Serialized as JSON, with the `application/json` Content-Type.
+    Json { content: serde_json::Value },
+}
+
+#[derive(Clone, Serialize, Deserialize, Debug)]
+#[serde(rename_all = "snake_case", tag = "type")]
+pub enum HttpAuthentication {
+    Basic {
+        username: String,
+        password: Option<String>,
+    },
+    Bearer {
+        token: String,
+    },
+}
+
+#[derive(Clone, Serialize, Deserialize, Debug)]
+#[serde(rename_all = "snake_case", tag = "type")]
+pub enum BodyAssertion {
+    /// The body must contain the value.
+    Contains { value: String },
+    /// The body must not contain the value, e.g. a maintenance banner.
+    NotContains { value: String },
+    /// The body must match the regular expression.
+    Regex { pattern: String },
+    /// The body must be JSON, with the value at the pointer (RFC 6901) equal to the expected value.
+    JsonPointer {
+        pointer: String,
+        value: serde_json::Value,
+    },
+}
+
+#[cfg(feature = "validator")]
+impl Validate for BodyAssertion {
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        let mut errors = ValidationErrors::new();
+        if let BodyAssertion::JsonPointer { pointer, .. } = self {
+            if !pointer.is_empty() && !pointer.starts_with('/') {
+                errors.add(
+                    "pointer",
+                    validator::ValidationError::new("JSON pointer must start with '/'"),
+                );
+            }
+        }
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+}
+
 #[cfg_attr(feature = "validator", derive(Validate))]
 #[cfg_attr(
     feature = "validator",
@@ -143,4 +357,261 @@ impl Default for SmtpEncryption {
 pub struct Tcp {
     pub host: String,
     pub port: u16,
+    /// Payload to send once connected.
+    pub send: Option<Payload>,
+    /// Expected response, e.g. a protocol banner.
+    pub expect: Option<ResponseMatch>,
+    /// Time to wait for the expected response.
+    #[serde(default = "default_response_timeout_ms")]
+    pub response_timeout_ms: u32,
+}
+
+fn default_response_timeout_ms() -> u32 {
+    5000
+}
+
+#[cfg_attr(feature = "validator", derive(Validate))]
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct Udp {
+    pub host: String,
+    pub port: u16,
+    /// Payload to send as a single datagram.
+    pub send: Payload,
+    /// Expected reply (defaults to any reply).
+    pub expect: Option<ResponseMatch>,
+    /// Time to wait for the reply.
+    #[serde(default = "default_response_timeout_ms")]
+    pub response_timeout_ms: u32,
+}
+
+#[derive(Clone, Serialize, Deserialize, Debug)]
+#[serde(rename_all = "snake_case", tag = "encoding", content = "value")]
+pub enum Payload {
+    /// UTF-8 text, sent as is.
+    Text(String),
+    /// Hex encoded bytes.
+    Hex(String),
+}
+
+#[derive(Clone, Serialize, Deserialize, Debug)]
+#[serde(rename_all = "snake_case", tag = "type")]
+pub enum ResponseMatch {
+    /// The response must contain the text.
+    Contains { value: String },
+    /// The response must match the regular expression.
+    Regex { pattern: String },
+    /// The response must contain the hex encoded bytes.
+    Hex { value: String },
+}
+
+#[cfg_attr(feature = "validator", derive(Validate))]
+#[cfg_attr(
+    feature = "validator",
+    validate(schema(function = "validate_dns", skip_on_field_errors = false))
+)]
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct Dns {
+    /// The domain name to query.
+    #[cfg_attr(feature = "validator", validate(length(min = 1, max = 253)))]
+    pub name: String,
+    pub record_type: DnsRecordType,
+    /// IP address (and optional port) of the nameserver to query (defaults to Google Public DNS).
+    /// The `ip_version` selects which nameservers may be used.
+    pub nameserver: Option<String>,
+    /// Values that must all be present in the response, e.g. `192.0.2.1`, `10 mail.contoso.com`
+    /// (MX), or `0 5 5060 sip.contoso.com` (SRV).
+    #[serde(default)]
+    pub expected_values: Vec<String>,
+    /// Fail if fewer than this many records are returned.
+    #[serde(default = "default_dns_minimum_records")]
+    #[cfg_attr(feature = "validator", validate(range(min = 1)))]
+    pub minimum_records: u16,
+    /// Fail if the TTL (seconds) of any record is below this.
+    pub minimum_ttl: Option<u32>,
+    /// Fail if the TTL (seconds) of any record is above this.
+    pub maximum_ttl: Option<u32>,
+}
+
+fn default_dns_minimum_records() -> u16 {
+    1
+}
+
+#[cfg(feature = "validator")]
+fn validate_dns(dns: &Dns) -> Result<(), validator::ValidationError> {
+    if let Some(nameserver) = &dns.nameserver {
+        if nameserver.parse::<std::net::IpAddr>().is_err()
+            && nameserver.parse::<std::net::SocketAddr>().is_err()
+        {
+            return Err(validator::ValidationError::new(
+                "Nameserver must be an IP address",
+            ));
+        }
+    }
+    if let (Some(min), Some(max)) = (dns.minimum_ttl, dns.maximum_ttl) {
+        if min > max {
+            return Err(validator::ValidationError::new(
+                "Minimum TTL must not be greater than the maximum TTL",
+            ));
+        }
+    }
+    Ok(())
+}
+
+#[derive(Copy, Clone, Serialize, Deserialize, Debug, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum DnsRecordType {
+    A,
+    AAAA,
+    CNAME,
+    MX,
+    TXT,
+    NS,
+    SRV,
+    CAA,
+}
+
+#[cfg_attr(feature = "validator", derive(Validate))]
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct Icmp {
+    pub host: String,
+    /// Number of echo requests to send.
+    #[serde(default = "default_icmp_count")]
+    #[cfg_attr(feature = "validator", validate(range(min = 1, max = 50)))]
+    pub count: u8,
+    /// Fail if more than this percentage of echo requests go unanswered.
+    #[serde(default = "default_icmp_max_packet_loss")]
+    #[cfg_attr(feature = "validator", validate(range(max = 100)))]
+    pub max_packet_loss_percent: u8,
+    /// Fail if the average round trip time is greater than this.
+    pub max_average_rtt_ms: Option<u32>,
+}
+
+fn default_icmp_count() -> u8 {
+    5
+}
+
+fn default_icmp_max_packet_loss() -> u8 {
+    20
+}
+
+#[cfg_attr(feature = "validator", derive(Validate))]
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct Tls {
+    pub host: String,
+    pub port: u16,
+    /// Server name to use for SNI and to check the certificate against (defaults to the host).
+    pub server_name: Option<String>,
+    /// Fail if the certificate chain could not be verified.
+    #[serde(default = "default_tls_verify")]
+    pub verify_chain: bool,
+    /// Fail if the server name doesn't match the certificate's subject alternative names.
+    #[serde(default = "default_tls_verify")]
+    pub verify_hostname: bool,
+    /// Fail if the expiry date of the certificate is less than X hours in the future.
+    #[serde(default = "default_minimum_cert_expiry")]
+    pub minimum_certificate_expiry_hours: u16,
+    /// Fail if the server doesn't support at least this protocol version.
+    pub minimum_protocol_version: Option<TlsVersion>,
+}
+
+fn default_tls_verify() -> bool {
+    true
+}
+
+#[derive(Copy, Clone, Serialize, Deserialize, Debug, PartialEq)]
+pub enum TlsVersion {
+    #[serde(rename = "tls1_0")]
+    Tls10,
+    #[serde(rename = "tls1_1")]
+    Tls11,
+    #[serde(rename = "tls1_2")]
+    Tls12,
+}
+
+#[cfg(test)]
+mod redaction_tests {
+    use super::*;
+
+    fn http_config(
+        headers: &[(&str, &str)],
+        authentication: Option<HttpAuthentication>,
+    ) -> TestConfig {
+        serde_json::from_value(serde_json::json!({
+            "type": "http",
+            "url": "https://contoso.com",
+            "headers": headers.iter().cloned().collect::<BTreeMap<_, _>>(),
+            "authentication": authentication,
+        }))
+        .unwrap()
+    }
+
+    fn http(config: &TestConfig) -> &Http {
+        match &config.variant {
+            TestVariant::Http(http) => http,
+            _ => unreachable!(),
+        }
+    }
+
+    #[test]
+    fn redact_replaces_secrets() {
+        let mut config = http_config(
+            &[("X-Api-Key", "secret")],
+            Some(HttpAuthentication::Basic {
+                username: "user".to_string(),
+                password: Some("password".to_string()),
+            }),
+        );
+        config.redact();
+        let http = http(&config);
+        assert_eq!(http.headers["X-Api-Key"], REDACTED);
+        match &http.authentication {
+            Some(HttpAuthentication::Basic { username, password }) => {
+                assert_eq!(username, "user");
+                assert_eq!(password.as_deref(), Some(REDACTED));
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    #[test]
+    fn restore_redacted_keeps_existing_secrets() {
+        let existing = http_config(
+            &[("X-Api-Key", "secret")],
+            Some(HttpAuthentication::Bearer {
+                token: "token".to_string(),
+            }),
+        );
+        let mut config = existing.clone();
+        config.redact();
+        assert!(config.restore_redacted(Some(&existing)));
+        let http = http(&config);
+        assert_eq!(http.headers["X-Api-Key"], "secret");
+        assert!(matches!(
+            &http.authentication,
+            Some(HttpAuthentication::Bearer { token }) if token == "token"
+        ));
+    }
+
+    #[test]
+    fn restore_redacted_without_existing_secret_fails() {
+        let mut config = http_config(&[("X-Api-Key", REDACTED)], None);
+        assert!(!config.restore_redacted(None));
+        let existing = http_config(&[("X-Other", "secret")], None);
+        assert!(!config.restore_redacted(Some(&existing)));
+
+        let mut config = http_config(
+            &[],
+            Some(HttpAuthentication::Bearer {
+                token: REDACTED.to_string(),
+            }),
+        );
+        let existing = http_config(
+            &[],
+            Some(HttpAuthentication::Basic {
+                username: "user".to_string(),
+                password: Some("password".to_string()),
+            }),
+        );
+        assert!(!config.restore_redacted(Some(&existing)));
+    }
 }
@@ -1,5 +1,9 @@
 use crate::api::error::{CalpolApiError, UnexpectedError};
-use crate::database::{Session, SessionRepository, SessionRepositoryImpl, User};
+use crate::database::{
+    ApiKey, ApiKeyRepository, ApiKeyRepositoryImpl, Session, SessionRepository,
+    SessionRepositoryImpl, User,
+};
+use crate::settings::Settings;
 use crate::state::AppState;
 use actix_web::dev::{Payload, ServiceRequest};
 use actix_web::http::header::{HeaderMap, USER_AGENT};
@@ -7,19 +11,81 @@ use actix_web::http::StatusCode;
 use actix_web::web::Data;
 use actix_web::{web, FromRequest, HttpMessage, HttpRequest};
 use actix_web_httpauth::extractors::bearer::BearerAuth;
+use calpol_model::api_v1::{Role, Scope};
 use chrono::Utc;
 use diesel_repository::CrudRepository;
 use futures::future::{err, ok, Ready};
+use hmac::{Hmac, Mac};
 use http_api_problem::ApiError;
 use rand::distributions::Standard;
 use rand::Rng;
+use sha2::Sha256;
 use std::net::IpAddr;
 
+/// Prefix of API key tokens, used to tell them apart from session tokens.
+pub const API_KEY_PREFIX: &str = "calpol_";
+
 pub struct Auth {
-    pub session: Session,
+    pub credential: Credential,
     pub user: User,
 }
 
+/// How the request was authenticated.
+pub enum Credential {
+    Session(Session),
+    ApiKey(ApiKey),
+}
+
+impl Auth {
+    pub fn role(&self) -> Role {
+        // Fall back to the least privileged role, the database should prevent this happening
+        self.user.role.parse().unwrap_or(Role::Viewer)
+    }
+
+    /// Returns a forbidden error unless the user has at least the role.
+    pub fn require_role(&self, role: Role) -> Result<(), CalpolApiError> {
+        if self.role() < role {
+            return Err(ApiError::builder(StatusCode::FORBIDDEN)
+                .message(format!("This requires the {} role", role))
+                .finish()
+                .into());
+        }
+        Ok(())
+    }
+
+    /// Returns a forbidden error if using an API key without the scope, sessions have every scope.
+    pub fn require_scope(&self, scope: Scope) -> Result<(), CalpolApiError> {
+        if let Credential::ApiKey(api_key) = &self.credential {
+            if !api_key.scopes.iter().any(|s| s == scope.as_str()) {
+                return Err(ApiError::builder(StatusCode::FORBIDDEN)
+                    .message(format!("This requires an API key with the {} scope", scope))
+                    .finish()
+                    .into());
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns the session, or a forbidden error if using an API key.
+    pub fn session(&self) -> Result<&Session, CalpolApiError> {
+        match &self.credential {
+            Credential::Session(session) => Ok(session),
+            Credential::ApiKey(_) => Err(ApiError::builder(StatusCode::FORBIDDEN)
+                .message("This requires logging in, API keys can't be used")
+                .finish()
+                .into()),
+        }
+    }
+
+    /// Users may manage their own account, otherwise the admin role is required.
+    pub fn require_self_or_admin(&self, user_id: i32) -> Result<(), CalpolApiError> {
+        if self.user.id == user_id {
+            return Ok(());
+        }
+        self.require_role(Role::Admin)
+    }
+}
+
 pub fn get_user_agent(map: &HeaderMap) -> Result<String, ApiError> {
     map.get(USER_AGENT)
         .and_then(|v| v.to_str().ok())
@@ -46,6 +112,22 @@ pub fn generate_token() -> String {
     base64::encode(&v)
 }
 
+pub fn generate_api_key() -> String {
+    format!("{}{}", API_KEY_PREFIX, generate_token())
+}
+
+/// Hex encoded HMAC-SHA256 of a token, only this is stored so a database leak doesn't expose usable tokens.
+pub fn hash_token(settings: &Settings, token: &str) -> String {
+    hash_token_with_key(&settings.token_key, token)
+}
+
+fn hash_token_with_key(key: &str, token: &str) -> String {
+    let mut mac =
+        Hmac::<Sha256>::new_from_slice(key.as_bytes()).expect("HMAC accepts any key length");
+    mac.update(token.as_bytes());
+    hex::encode(mac.finalize().into_bytes())
+}
+
 pub async fn authenticator(
     req: ServiceRequest,
     auth: Option<BearerAuth>,
@@ -59,37 +141,74 @@ pub async fn authenticator(
         .app_data::<Data<AppState>>()
         .expect("AppState missing")
         .clone();
-    let ip_addr = req
-        .connection_info()
-        .realip_remote_addr()
-        .unwrap()
-        .parse::<IpAddr>()
-        .unwrap();
-    let user_agent = get_user_agent(req.headers())?;
-    let auth = web::block(move || -> Result<_, CalpolApiError> {
-        let ip_addr = ip_addr;
-        let ip_bin = bincode::serialize(&ip_addr).unwrap();
-        let database = state.database();
-        let session_repository = SessionRepositoryImpl::new(&database);
-        let (mut session, user) =
-            session_repository
-                .find_by_token(auth.token())?
-                .ok_or_else(|| {
-                    ApiError::builder(StatusCode::UNAUTHORIZED)
-                        .message("Invalid session token")
-                        .finish()
-                })?;
-        session.last_ip = ip_bin;
-        session.user_agent = user_agent;
-        session.last_used = Utc::now();
-        session_repository.update(&session)?;
-        Ok(Auth { session, user })
-    })
-    .await??;
+    let auth = if auth.token().starts_with(API_KEY_PREFIX) {
+        web::block(move || authenticate_api_key(&state, auth.token())).await??
+    } else {
+        let ip_addr = req
+            .connection_info()
+            .realip_remote_addr()
+            .unwrap()
+            .parse::<IpAddr>()
+            .unwrap();
+        let user_agent = get_user_agent(req.headers())?;
+        web::block(move || authenticate_session(&state, auth.token(), ip_addr, user_agent))
+            .await??
+    };
     req.extensions_mut().insert(auth);
     Ok(req)
 }
 
+fn authenticate_session(
+    state: &AppState,
+    token: &str,
+    ip_addr: IpAddr,
+    user_agent: String,
+) -> Result<Auth, CalpolApiError> {
+    let ip_bin = bincode::serialize(&ip_addr).unwrap();
+    let database = state.database();
+    let session_repository = SessionRepositoryImpl::new(&database);
+    let (mut session, user) = session_repository
+        .find_by_token_hash(&hash_token(&state.settings, token))?
+        .ok_or_else(|| {
+            ApiError::builder(StatusCode::UNAUTHORIZED)
+                .message("Invalid session token")
+                .finish()
+        })?;
+    session.last_ip = ip_bin;
+    session.user_agent = user_agent;
+    session.last_used = Utc::now();
+    session_repository.update(&session)?;
+    Ok(Auth {
+        credential: Credential::Session(session),
+        user,
+    })
+}
+
+fn authenticate_api_key(state: &AppState, token: &str) -> Result<Auth, CalpolApiError> {
+    let database = state.database();
+    let api_key_repository = ApiKeyRepositoryImpl::new(&database);
+    let (mut api_key, user) = api_key_repository
+        .find_by_token_hash(&hash_token(&state.settings, token))?
+        .ok_or_else(|| {
+            ApiError::builder(StatusCode::UNAUTHORIZED)
+                .message("Invalid API key")
+                .finish()
+        })?;
+    let now = Utc::now();
+    if matches!(api_key.expires, Some(expires) if expires <= now) {
+        return Err(ApiError::builder(StatusCode::UNAUTHORIZED)
+            .message("API key has expired")
+            .finish()
+            .into());
+    }
+    api_key.last_used = Some(now);
+    api_key_repository.update(&api_key)?;
+    Ok(Auth {
+        credential: Credential::ApiKey(api_key),
+        user,
+    })
+}
+
 impl FromRequest for Auth {
     type Error = CalpolApiError;
     type Future = Ready<Result<Self, Self::Error>>;
@@ -102,3 +221,42 @@ impl FromRequest for Auth {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const KEY: &str = "0123456789abcdef0123456789abcdef";
+
+    #[test]
+    fn hash_is_hmac_sha256_hex() {
+        assert_eq!(
+            hash_token_with_key(KEY, "calpol_token"),
+            "0f015a28e1a94323ac66c42d19189ae94ddffb241fd253e151b21bd41e10565c"
+        );
+    }
+
+    #[test]
+    fn hash_depends_on_key_and_token() {
+        let token = generate_token();
+        assert_eq!(
+            hash_token_with_key(KEY, &token),
+            hash_token_with_key(KEY, &token)
+        );
+        assert_ne!(
+            hash_token_with_key(KEY, &token),
+            hash_token_with_key("fedcba9876543210fedcba9876543210", &token)
+        );
+        assert_ne!(
+            hash_token_with_key(KEY, &token),
+            hash_token_with_key(KEY, &generate_token())
+        );
+    }
+
+    #[test]
+    fn api_keys_are_prefixed() {
+        let key = generate_api_key();
+        assert!(key.starts_with(API_KEY_PREFIX));
+        assert!(!generate_token().starts_with(API_KEY_PREFIX));
+    }
+}
@@ -117,9 +117,9 @@ impl From<smtp::Error> for CalpolApiError {
     }
 }
 
-impl From<crate::messagebird::Error> for CalpolApiError {
-    fn from(e: crate::messagebird::Error) -> Self {
-        CalpolApiError::InternalServerError("messagebird::Error", Box::new(e))
+impl From<crate::sms::Error> for CalpolApiError {
+    fn from(e: crate::sms::Error) -> Self {
+        CalpolApiError::InternalServerError("sms::Error", Box::new(e))
     }
 }
 
@@ -0,0 +1,111 @@
+use crate::api::auth::{self, authenticator, Auth};
+use crate::api::error::CalpolApiError;
+use crate::api::{api_resource, api_scope, JsonResponse};
+use crate::database::{ApiKeyRepository, ApiKeyRepositoryImpl, NewApiKey};
+use crate::state::AppState;
+use actix_web::http::StatusCode;
+use actix_web::web::{Data, Path, ServiceConfig};
+use actix_web::{web, HttpResponse};
+use actix_web_httpauth::middleware::HttpAuthentication;
+use calpol_model::api_v1::{ApiKeySummary, CreateApiKeyRequest, CreateApiKeyResponse};
+use chrono::{DateTime, Utc};
+use diesel_repository::CrudRepository;
+use http_api_problem::ApiError;
+
+pub fn configure(v1: &mut ServiceConfig) {
+    let auth = HttpAuthentication::with_fn(authenticator);
+    v1.service(
+        api_scope("api_keys")
+            .service(
+                api_resource("")
+                    .route(web::get().to(list))
+                    .route(web::post().to(create)),
+            )
+            .service(api_resource("{api_key_id}").route(web::delete().to(delete)))
+            .wrap(auth),
+    );
+}
+
+async fn list(auth: Auth, state: Data<AppState>) -> Result<HttpResponse, CalpolApiError> {
+    auth.session()?;
+    web::block(move || -> Result<_, CalpolApiError> {
+        let database = state.database();
+        let api_key_repository = ApiKeyRepositoryImpl::new(&database);
+        let api_keys: Vec<ApiKeySummary> = api_key_repository
+            .find_all_belonging_to_user(&auth.user)?
+            .into_iter()
+            .map(|k| k.into())
+            .collect();
+        Ok(api_keys)
+    })
+    .await?
+    .map(JsonResponse::json_response)
+}
+
+async fn create(
+    auth: Auth,
+    state: Data<AppState>,
+    json: actix_web_validator::Json<CreateApiKeyRequest>,
+) -> Result<HttpResponse, CalpolApiError> {
+    auth.session()?;
+    web::block(move || -> Result<_, CalpolApiError> {
+        let expires = match &json.expires {
+            None => None,
+            Some(expires) => Some(parse_expiry(expires)?),
+        };
+        let mut scopes: Vec<String> = json.scopes.iter().map(|s| s.to_string()).collect();
+        scopes.sort();
+        scopes.dedup();
+        let token = auth::generate_api_key();
+        let database = state.database();
+        let api_key_repository = ApiKeyRepositoryImpl::new(&database);
+        let api_key = api_key_repository.insert(NewApiKey {
+            user_id: auth.user.id,
+            name: json.name.clone(),
+            token_hash: auth::hash_token(&state.settings, &token),
+            scopes,
+            expires,
+        })?;
+        Ok(CreateApiKeyResponse {
+            api_key: api_key.into(),
+            token,
+        })
+    })
+    .await?
+    .map(JsonResponse::json_response)
+}
+
+async fn delete(
+    auth: Auth,
+    api_key_id: Path<i32>,
+    state: Data<AppState>,
+) -> Result<HttpResponse, CalpolApiError> {
+    auth.session()?;
+    web::block(move || -> Result<_, CalpolApiError> {
+        let database = state.database();
+        let api_key_repository = ApiKeyRepositoryImpl::new(&database);
+        if !api_key_repository.delete_by_id_and_user(*api_key_id, &auth.user)? {
+            return Err(ApiError::new(StatusCode::NOT_FOUND).into());
+        }
+        Ok(())
+    })
+    .await?
+    .map(JsonResponse::json_response)
+}
+
+fn parse_expiry(expires: &str) -> Result<DateTime<Utc>, CalpolApiError> {
+    let expires = DateTime::parse_from_rfc3339(expires)
+        .map(|t| t.with_timezone(&Utc))
+        .map_err(|e| {
+            ApiError::builder(StatusCode::BAD_REQUEST)
+                .message(format!("Invalid expires time, expected RFC3339: {}", e))
+                .finish()
+        })?;
+    if expires <= Utc::now() {
+        return Err(ApiError::builder(StatusCode::BAD_REQUEST)
+            .message("API key must expire in the future")
+            .finish()
+            .into());
+    }
+    Ok(expires)
+}
@@ -1,7 +1,8 @@
 use crate::api::error::{CalpolApiError, UnexpectedError};
 use crate::database;
-use crate::database::RunnerLog;
+use crate::database::{EscalationPolicy, Incident, IncidentEvent, MaintenanceWindow, RunnerLog};
 use calpol_model::api_v1::*;
+use calpol_model::tests::TestConfig;
 use serde::__private::TryFrom;
 use std::net::IpAddr;
 
@@ -11,9 +12,21 @@ impl From<database::User> for UserSummary {
             id: user.id,
             name: user.name,
             email: user.email,
+            role: user.role.parse().unwrap_or_default(),
             phone_number: user.phone_number,
             sms_notifications: user.sms_notifications,
             email_notifications: user.email_notifications,
+            email_min_severity: user.email_min_severity.parse().unwrap_or_default(),
+            sms_min_severity: user.sms_min_severity.parse().unwrap_or_default(),
+            quiet_hours: user
+                .quiet_hours_start
+                .zip(user.quiet_hours_end)
+                .map(|(start, end)| QuietHours {
+                    start: start.format("%H:%M").to_string(),
+                    end: end.format("%H:%M").to_string(),
+                    sms: user.quiet_hours_sms.parse().unwrap_or_default(),
+                }),
+            timezone: user.timezone,
         }
     }
 }
@@ -33,18 +46,41 @@ impl From<database::Session> for SessionSummary {
     }
 }
 
+impl From<database::ApiKey> for ApiKeySummary {
+    fn from(api_key: database::ApiKey) -> Self {
+        Self {
+            id: api_key.id,
+            name: api_key.name,
+            // Unknown scopes are ignored, the API only allows creating keys with valid scopes
+            scopes: api_key
+                .scopes
+                .iter()
+                .filter_map(|s| s.parse().ok())
+                .collect(),
+            created: api_key.created.timestamp(),
+            expires: api_key.expires.map(|t| t.timestamp()),
+            last_used: api_key.last_used.map(|t| t.timestamp()),
+        }
+    }
+}
+
 impl TryFrom<database::Test> for TestSummary {
     type Error = CalpolApiError;
 
     fn try_from(test: database::Test) -> Result<Self, Self::Error> {
-        let config =
+        let mut config: TestConfig =
             serde_json::from_value(test.config).map_err(UnexpectedError::TestDeserialization)?;
+        config.redact();
         Ok(Self {
             name: test.name,
             config,
             enabled: test.enabled,
             failure_threshold: test.failure_threshold as u8,
             failing: test.failing,
+            interval_minutes: test.run_interval.map(|i| i as u16),
+            timeout_seconds: test.run_timeout.map(|t| t as u16),
+            tags: test.tags,
+            severity: test.severity.parse().unwrap_or_default(),
         })
     }
 }
@@ -59,6 +95,13 @@ pub fn test_and_result_to_summary(
         failure_reason: result.failure_reason,
         time_started: result.time_started.to_string(),
         time_finished: result.time_finished.to_string(),
+        timings: TestTimings {
+            dns_ms: result.dns_ms,
+            connect_ms: result.connect_ms,
+            tls_ms: result.tls_ms,
+            first_byte_ms: result.first_byte_ms,
+            total_ms: result.total_ms,
+        },
     }
 }
 
@@ -76,3 +119,65 @@ impl From<crate::database::RunnerLog> for RunnerLogSummary {
         }
     }
 }
+
+impl From<(MaintenanceWindow, Vec<database::Test>)> for MaintenanceWindowSummary {
+    fn from((window, tests): (MaintenanceWindow, Vec<database::Test>)) -> Self {
+        MaintenanceWindowSummary {
+            id: window.id,
+            name: window.name,
+            start: window.start_time.to_string(),
+            end: window.end_time.to_string(),
+            tests: if window.all_tests {
+                None
+            } else {
+                Some(tests.into_iter().map(|t| t.name).collect())
+            },
+        }
+    }
+}
+
+impl From<(EscalationPolicy, Vec<database::Test>, Vec<database::User>)>
+    for EscalationPolicySummary
+{
+    fn from(
+        (policy, tests, users): (EscalationPolicy, Vec<database::Test>, Vec<database::User>),
+    ) -> Self {
+        EscalationPolicySummary {
+            id: policy.id,
+            name: policy.name,
+            repeat_interval: policy.repeat_interval.map(|i| i as u16),
+            escalate_after: policy.escalate_after.map(|i| i as u16),
+            escalate_to_users: users.into_iter().map(|u| u.id).collect(),
+            escalate_to_channels: policy.escalate_channels,
+            tests: tests.into_iter().map(|t| t.name).collect(),
+        }
+    }
+}
+
+impl From<(Incident, database::Test, Option<database::User>)> for IncidentSummary {
+    fn from(
+        (incident, test, acknowledged_by): (Incident, database::Test, Option<database::User>),
+    ) -> Self {
+        IncidentSummary {
+            id: incident.id,
+            test_name: test.name,
+            failure_reason: incident.failure_reason,
+            started: incident.started.to_string(),
+            ended: incident.ended.map(|t| t.to_string()),
+            escalated: incident.escalated.map(|t| t.to_string()),
+            acknowledged_at: incident.acknowledged_at.map(|t| t.to_string()),
+            acknowledged_by: acknowledged_by.map(UserSummary::from),
+        }
+    }
+}
+
+impl From<IncidentEvent> for IncidentEventSummary {
+    fn from(event: IncidentEvent) -> Self {
+        IncidentEventSummary {
+            time: event.time.to_string(),
+            kind: event.kind,
+            user_id: event.user_id,
+            message: event.message,
+        }
+    }
+}
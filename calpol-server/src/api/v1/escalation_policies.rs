@@ -0,0 +1,222 @@
+use crate::api::auth::{authenticator, Auth};
+use crate::api::error::{CalpolApiError, MapDieselUniqueViolation};
+use crate::api::v1::tests::retrieve_tests;
+use crate::api::v1::users::retrieve_user;
+use crate::api::{api_resource, api_scope, JsonResponse};
+use crate::database::{
+    Connection, EscalationPolicy, EscalationPolicyRepository, EscalationPolicyRepositoryImpl,
+    NewEscalationPolicy, TestRepositoryImpl, User, UserRepositoryImpl,
+};
+use crate::state::AppState;
+use actix_web::http::StatusCode;
+use actix_web::web::{Data, Path, ServiceConfig};
+use actix_web::{web, HttpResponse};
+use actix_web_httpauth::middleware::HttpAuthentication;
+use calpol_model::api_v1::{
+    CreateEscalationPolicyRequest, EscalationPolicySummary, Role, Scope,
+    UpdateEscalationPolicyRequest,
+};
+use diesel::Connection as _;
+use diesel_repository::CrudRepository;
+use http_api_problem::ApiError;
+
+pub fn configure(v1: &mut ServiceConfig) {
+    let auth = HttpAuthentication::with_fn(authenticator);
+    v1.service(
+        api_scope("escalation_policies")
+            .service(
+                api_resource("")
+                    .route(web::get().to(list))
+                    .route(web::post().to(create)),
+            )
+            .service(
+                api_resource("{policy_id}")
+                    .route(web::get().to(get))
+                    .route(web::put().to(update))
+                    .route(web::delete().to(delete)),
+            )
+            .wrap(auth),
+    );
+}
+
+fn summarise(
+    policy_repository: &EscalationPolicyRepositoryImpl,
+    policy: EscalationPolicy,
+) -> Result<EscalationPolicySummary, CalpolApiError> {
+    let tests = policy_repository.find_tests(&policy)?;
+    let users = policy_repository.find_users(&policy)?;
+    Ok(EscalationPolicySummary::from((policy, tests, users)))
+}
+
+async fn list(auth: Auth, state: Data<AppState>) -> Result<HttpResponse, CalpolApiError> {
+    auth.require_scope(Scope::EscalationPoliciesRead)?;
+    web::block(move || -> Result<_, CalpolApiError> {
+        let database = state.database();
+        let policy_repository = EscalationPolicyRepositoryImpl::new(&database);
+        policy_repository
+            .find_all()?
+            .into_iter()
+            .map(|policy| summarise(&policy_repository, policy))
+            .collect::<Result<Vec<_>, _>>()
+    })
+    .await?
+    .map(JsonResponse::json_response)
+}
+
+async fn create(
+    auth: Auth,
+    state: Data<AppState>,
+    json: actix_web_validator::Json<CreateEscalationPolicyRequest>,
+) -> Result<HttpResponse, CalpolApiError> {
+    auth.require_scope(Scope::EscalationPoliciesWrite)?;
+    auth.require_role(Role::Operator)?;
+    web::block(move || -> Result<_, CalpolApiError> {
+        validate_channels(&state, &json.escalate_to_channels)?;
+        let database = state.database();
+        let policy_repository = EscalationPolicyRepositoryImpl::new(&database);
+        let tests = retrieve_tests(&TestRepositoryImpl::new(&database), &json.tests)?;
+        let users = retrieve_users(&database, &json.escalate_to_users)?;
+        database.transaction(|| -> Result<_, CalpolApiError> {
+            let policy = policy_repository
+                .insert(NewEscalationPolicy {
+                    name: json.name.clone(),
+                    repeat_interval: json.repeat_interval.map(i32::from),
+                    escalate_after: json.escalate_after.map(i32::from),
+                    escalate_channels: json.escalate_to_channels.clone(),
+                })
+                .map_unique_violation(name_taken)?;
+            policy_repository.set_tests(&policy, &tests)?;
+            policy_repository.set_users(&policy, &users)?;
+            summarise(&policy_repository, policy)
+        })
+    })
+    .await?
+    .map(JsonResponse::json_response)
+}
+
+fn retrieve_policy<'p, P>(
+    policy_repository: &P,
+    policy_id: i32,
+) -> Result<EscalationPolicy, CalpolApiError>
+where
+    P: EscalationPolicyRepository + 'p,
+{
+    policy_repository.find_by_id(policy_id)?.ok_or_else(|| {
+        ApiError::builder(StatusCode::NOT_FOUND)
+            .message("Escalation policy id not found")
+            .finish()
+            .into()
+    })
+}
+
+async fn get(
+    auth: Auth,
+    policy_id: Path<i32>,
+    state: Data<AppState>,
+) -> Result<HttpResponse, CalpolApiError> {
+    auth.require_scope(Scope::EscalationPoliciesRead)?;
+    web::block(move || -> Result<_, CalpolApiError> {
+        let database = state.database();
+        let policy_repository = EscalationPolicyRepositoryImpl::new(&database);
+        let policy = retrieve_policy(&policy_repository, *policy_id)?;
+        summarise(&policy_repository, policy)
+    })
+    .await?
+    .map(JsonResponse::json_response)
+}
+
+async fn update(
+    auth: Auth,
+    policy_id: Path<i32>,
+    json: actix_web_validator::Json<UpdateEscalationPolicyRequest>,
+    state: Data<AppState>,
+) -> Result<HttpResponse, CalpolApiError> {
+    auth.require_scope(Scope::EscalationPoliciesWrite)?;
+    auth.require_role(Role::Operator)?;
+    web::block(move || -> Result<_, CalpolApiError> {
+        let database = state.database();
+        let policy_repository = EscalationPolicyRepositoryImpl::new(&database);
+        let mut policy = retrieve_policy(&policy_repository, *policy_id)?;
+        if let Some(name) = &json.name {
+            policy.name = name.clone();
+        }
+        if let Some(repeat_interval) = json.repeat_interval {
+            policy.repeat_interval = Some(repeat_interval as i32).filter(|i| *i > 0);
+        }
+        if let Some(escalate_after) = json.escalate_after {
+            policy.escalate_after = Some(escalate_after as i32).filter(|i| *i > 0);
+        }
+        if let Some(channels) = &json.escalate_to_channels {
+            validate_channels(&state, channels)?;
+            policy.escalate_channels = channels.clone();
+        }
+        let tests = match &json.tests {
+            None => None,
+            Some(names) => Some(retrieve_tests(&TestRepositoryImpl::new(&database), names)?),
+        };
+        let users = match &json.escalate_to_users {
+            None => None,
+            Some(ids) => Some(retrieve_users(&database, ids)?),
+        };
+        database.transaction(|| -> Result<_, CalpolApiError> {
+            policy_repository
+                .update(&policy)
+                .map_unique_violation(name_taken)?;
+            if let Some(tests) = &tests {
+                policy_repository.set_tests(&policy, tests)?;
+            }
+            if let Some(users) = &users {
+                policy_repository.set_users(&policy, users)?;
+            }
+            Ok(())
+        })?;
+        summarise(&policy_repository, policy)
+    })
+    .await?
+    .map(JsonResponse::json_response)
+}
+
+async fn delete(
+    auth: Auth,
+    policy_id: Path<i32>,
+    state: Data<AppState>,
+) -> Result<HttpResponse, CalpolApiError> {
+    auth.require_scope(Scope::EscalationPoliciesWrite)?;
+    auth.require_role(Role::Operator)?;
+    web::block(move || -> Result<_, CalpolApiError> {
+        let database = state.database();
+        let policy_repository = EscalationPolicyRepositoryImpl::new(&database);
+        let policy = retrieve_policy(&policy_repository, *policy_id)?;
+        policy_repository.delete(policy)?;
+        Ok(())
+    })
+    .await?
+    .map(JsonResponse::json_response)
+}
+
+fn name_taken(_: &dyn diesel::result::DatabaseErrorInformation) -> CalpolApiError {
+    ApiError::builder(StatusCode::CONFLICT)
+        .message("Escalation policy with this name already exists")
+        .finish()
+        .into()
+}
+
+fn retrieve_users(database: &Connection, ids: &[i32]) -> Result<Vec<User>, CalpolApiError> {
+    let user_repository = UserRepositoryImpl::new(database);
+    ids.iter()
+        .map(|id| retrieve_user(&user_repository, *id))
+        .collect()
+}
+
+/// Channels must be the name of a configured notifier.
+fn validate_channels(state: &AppState, channels: &[String]) -> Result<(), CalpolApiError> {
+    for channel in channels {
+        if !state.notifiers.iter().any(|n| n.name() == channel) {
+            return Err(ApiError::builder(StatusCode::BAD_REQUEST)
+                .message(format!("Notification channel not configured: {}", channel))
+                .finish()
+                .into());
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,230 @@
+use crate::api::auth::{authenticator, Auth};
+use crate::api::error::CalpolApiError;
+use crate::api::v1::tests::retrieve_test;
+use crate::api::{api_resource, api_scope, JsonResponse};
+use crate::database::{
+    Connection, Incident, IncidentEventKind, IncidentEventRepository, IncidentEventRepositoryImpl,
+    IncidentRepository, IncidentRepositoryImpl, NewIncidentEvent, Test, TestRepositoryImpl,
+    UserRepositoryImpl,
+};
+use crate::state::AppState;
+use actix_web::http::StatusCode;
+use actix_web::web::{Data, Path, ServiceConfig};
+use actix_web::{web, HttpResponse};
+use actix_web_httpauth::middleware::HttpAuthentication;
+use calpol_model::api_v1::{
+    CreateIncidentNoteRequest, IncidentDetails, IncidentEventSummary, IncidentSummary,
+    ListIncidentsRequest, ListIncidentsResponse, Role, Scope,
+};
+use chrono::Utc;
+use diesel::Connection as _;
+use diesel_repository::CrudRepository;
+use http_api_problem::ApiError;
+
+pub fn configure(v1: &mut ServiceConfig) {
+    let auth = HttpAuthentication::with_fn(authenticator);
+    v1.service(
+        api_scope("incidents")
+            .service(api_resource("").route(web::get().to(list)))
+            .service(api_resource("{incident_id}").route(web::get().to(get)))
+            .service(api_resource("{incident_id}/acknowledge").route(web::post().to(acknowledge)))
+            .service(api_resource("{incident_id}/resolve").route(web::post().to(resolve)))
+            .service(api_resource("{incident_id}/notes").route(web::post().to(add_note)))
+            .wrap(auth),
+    );
+}
+
+fn summarise(
+    database: &Connection,
+    (incident, test): (Incident, Test),
+) -> Result<IncidentSummary, CalpolApiError> {
+    let acknowledged_by = match incident.acknowledged_by {
+        Some(user_id) => UserRepositoryImpl::new(database).find_by_id(user_id)?,
+        None => None,
+    };
+    Ok(IncidentSummary::from((incident, test, acknowledged_by)))
+}
+
+fn retrieve_incident<'i, I>(
+    incident_repository: &I,
+    incident_id: i32,
+) -> Result<(Incident, Test), CalpolApiError>
+where
+    I: IncidentRepository + 'i,
+{
+    incident_repository
+        .find_by_id_with_test(incident_id)?
+        .ok_or_else(|| {
+            ApiError::builder(StatusCode::NOT_FOUND)
+                .message("Incident id not found")
+                .finish()
+                .into()
+        })
+}
+
+fn ensure_ongoing(incident: &Incident) -> Result<(), CalpolApiError> {
+    if incident.ended.is_some() {
+        return Err(ApiError::builder(StatusCode::BAD_REQUEST)
+            .message("Incident has already ended")
+            .finish()
+            .into());
+    }
+    Ok(())
+}
+
+async fn list(
+    auth: Auth,
+    state: Data<AppState>,
+    json: actix_web_validator::Json<ListIncidentsRequest>,
+) -> Result<HttpResponse, CalpolApiError> {
+    auth.require_scope(Scope::IncidentsRead)?;
+    web::block(move || -> Result<_, CalpolApiError> {
+        let database = state.database();
+        let incident_repository = IncidentRepositoryImpl::new(&database);
+        let test = match &json.test {
+            Some(name) => Some(retrieve_test(&TestRepositoryImpl::new(&database), name)?),
+            None => None,
+        };
+        let incidents = match &test {
+            Some(test) => incident_repository.find_all_by_test(test, json.limit, json.offset)?,
+            None => IncidentRepository::find_all(&incident_repository, json.limit, json.offset)?,
+        };
+        Ok(ListIncidentsResponse {
+            incidents: incidents
+                .results
+                .into_iter()
+                .map(|incident| summarise(&database, incident))
+                .collect::<Result<Vec<_>, _>>()?,
+            total: incidents.count,
+            mean_time_to_resolve: incident_repository.mean_time_to_resolve(test.as_ref())?,
+        })
+    })
+    .await?
+    .map(JsonResponse::json_response)
+}
+
+async fn get(
+    auth: Auth,
+    incident_id: Path<i32>,
+    state: Data<AppState>,
+) -> Result<HttpResponse, CalpolApiError> {
+    auth.require_scope(Scope::IncidentsRead)?;
+    web::block(move || -> Result<_, CalpolApiError> {
+        let database = state.database();
+        let incident_repository = IncidentRepositoryImpl::new(&database);
+        let event_repository = IncidentEventRepositoryImpl::new(&database);
+        let (incident, test) = retrieve_incident(&incident_repository, *incident_id)?;
+        let timeline = event_repository
+            .find_belonging_to(&incident)?
+            .into_iter()
+            .map(IncidentEventSummary::from)
+            .collect();
+        Ok(IncidentDetails {
+            incident: summarise(&database, (incident, test))?,
+            timeline,
+        })
+    })
+    .await?
+    .map(JsonResponse::json_response)
+}
+
+/// Acknowledging an incident stops any further reminders or escalation.
+async fn acknowledge(
+    auth: Auth,
+    incident_id: Path<i32>,
+    state: Data<AppState>,
+) -> Result<HttpResponse, CalpolApiError> {
+    auth.require_scope(Scope::IncidentsWrite)?;
+    auth.require_role(Role::Operator)?;
+    web::block(move || -> Result<_, CalpolApiError> {
+        let database = state.database();
+        let incident_repository = IncidentRepositoryImpl::new(&database);
+        let event_repository = IncidentEventRepositoryImpl::new(&database);
+        let (mut incident, test) = retrieve_incident(&incident_repository, *incident_id)?;
+        ensure_ongoing(&incident)?;
+        if incident.acknowledged_at.is_some() {
+            return Err(ApiError::builder(StatusCode::CONFLICT)
+                .message("Incident has already been acknowledged")
+                .finish()
+                .into());
+        }
+        incident.acknowledged_at = Some(Utc::now());
+        incident.acknowledged_by = Some(auth.user.id);
+        database.transaction(|| -> Result<_, CalpolApiError> {
+            incident_repository.update(&incident)?;
+            event_repository.insert(NewIncidentEvent::new(
+                &incident,
+                IncidentEventKind::Acknowledged,
+                Some(auth.user.id),
+                None,
+            ))?;
+            Ok(())
+        })?;
+        log::info!(
+            "Incident {} for test {} acknowledged by user {}",
+            incident.id,
+            test.name,
+            auth.user.id
+        );
+        Ok(IncidentSummary::from((incident, test, Some(auth.user))))
+    })
+    .await?
+    .map(JsonResponse::json_response)
+}
+
+/// Manually ends an incident, for example if the test has been disabled while failing.
+async fn resolve(
+    auth: Auth,
+    incident_id: Path<i32>,
+    state: Data<AppState>,
+) -> Result<HttpResponse, CalpolApiError> {
+    auth.require_scope(Scope::IncidentsWrite)?;
+    auth.require_role(Role::Operator)?;
+    web::block(move || -> Result<_, CalpolApiError> {
+        let database = state.database();
+        let incident_repository = IncidentRepositoryImpl::new(&database);
+        let event_repository = IncidentEventRepositoryImpl::new(&database);
+        let (mut incident, test) = retrieve_incident(&incident_repository, *incident_id)?;
+        ensure_ongoing(&incident)?;
+        incident.ended = Some(Utc::now());
+        database.transaction(|| -> Result<_, CalpolApiError> {
+            incident_repository.update(&incident)?;
+            event_repository.insert(NewIncidentEvent::new(
+                &incident,
+                IncidentEventKind::Resolved,
+                Some(auth.user.id),
+                None,
+            ))?;
+            Ok(())
+        })?;
+        summarise(&database, (incident, test))
+    })
+    .await?
+    .map(JsonResponse::json_response)
+}
+
+/// Adds a note to the incident's timeline, e.g. for writing post-mortems.
+async fn add_note(
+    auth: Auth,
+    incident_id: Path<i32>,
+    json: actix_web_validator::Json<CreateIncidentNoteRequest>,
+    state: Data<AppState>,
+) -> Result<HttpResponse, CalpolApiError> {
+    auth.require_scope(Scope::IncidentsWrite)?;
+    auth.require_role(Role::Operator)?;
+    web::block(move || -> Result<_, CalpolApiError> {
+        let database = state.database();
+        let incident_repository = IncidentRepositoryImpl::new(&database);
+        let event_repository = IncidentEventRepositoryImpl::new(&database);
+        let (incident, _) = retrieve_incident(&incident_repository, *incident_id)?;
+        let event = event_repository.insert(NewIncidentEvent::new(
+            &incident,
+            IncidentEventKind::Note,
+            Some(auth.user.id),
+            Some(json.message.clone()),
+        ))?;
+        Ok(IncidentEventSummary::from(event))
+    })
+    .await?
+    .map(JsonResponse::json_response)
+}
@@ -0,0 +1,196 @@
+use crate::api::auth::{authenticator, Auth};
+use crate::api::error::CalpolApiError;
+use crate::api::v1::tests::retrieve_tests;
+use crate::api::{api_resource, api_scope, JsonResponse};
+use crate::database::{
+    MaintenanceWindow, MaintenanceWindowRepository, MaintenanceWindowRepositoryImpl,
+    NewMaintenanceWindow, TestRepositoryImpl,
+};
+use crate::state::AppState;
+use actix_web::http::StatusCode;
+use actix_web::web::{Data, Path, ServiceConfig};
+use actix_web::{web, HttpResponse};
+use actix_web_httpauth::middleware::HttpAuthentication;
+use calpol_model::api_v1::{
+    CreateMaintenanceWindowRequest, MaintenanceWindowSummary, Role, Scope,
+    UpdateMaintenanceWindowRequest,
+};
+use chrono::{DateTime, Utc};
+use diesel::Connection;
+use diesel_repository::CrudRepository;
+use http_api_problem::ApiError;
+
+pub fn configure(v1: &mut ServiceConfig) {
+    let auth = HttpAuthentication::with_fn(authenticator);
+    v1.service(
+        api_scope("maintenance_windows")
+            .service(
+                api_resource("")
+                    .route(web::get().to(list))
+                    .route(web::post().to(create)),
+            )
+            .service(
+                api_resource("{window_id}")
+                    .route(web::get().to(get))
+                    .route(web::put().to(update))
+                    .route(web::delete().to(delete)),
+            )
+            .wrap(auth),
+    );
+}
+
+async fn list(auth: Auth, state: Data<AppState>) -> Result<HttpResponse, CalpolApiError> {
+    auth.require_scope(Scope::MaintenanceWindowsRead)?;
+    web::block(move || -> Result<_, CalpolApiError> {
+        let database = state.database();
+        let window_repository = MaintenanceWindowRepositoryImpl::new(&database);
+        let windows = window_repository
+            .find_all()?
+            .into_iter()
+            .map(|window| {
+                let tests = window_repository.find_tests(&window)?;
+                Ok(MaintenanceWindowSummary::from((window, tests)))
+            })
+            .collect::<Result<Vec<_>, CalpolApiError>>()?;
+        Ok(windows)
+    })
+    .await?
+    .map(JsonResponse::json_response)
+}
+
+async fn create(
+    auth: Auth,
+    state: Data<AppState>,
+    json: actix_web_validator::Json<CreateMaintenanceWindowRequest>,
+) -> Result<HttpResponse, CalpolApiError> {
+    auth.require_scope(Scope::MaintenanceWindowsWrite)?;
+    auth.require_role(Role::Operator)?;
+    web::block(move || -> Result<_, CalpolApiError> {
+        let database = state.database();
+        let window_repository = MaintenanceWindowRepositoryImpl::new(&database);
+        let test_repository = TestRepositoryImpl::new(&database);
+        let start_time = match &json.start {
+            None => Utc::now(),
+            Some(start) => parse_time(start, "start")?,
+        };
+        let end_time = parse_time(&json.end, "end")?;
+        validate_times(start_time, end_time)?;
+        let tests = match &json.tests {
+            None => Vec::new(),
+            Some(names) => retrieve_tests(&test_repository, names)?,
+        };
+        database.transaction(|| -> Result<_, CalpolApiError> {
+            let window = window_repository.insert(NewMaintenanceWindow {
+                name: json.name.clone(),
+                start_time,
+                end_time,
+                all_tests: json.tests.is_none(),
+            })?;
+            window_repository.insert_tests(&window, &tests)?;
+            Ok(MaintenanceWindowSummary::from((window, tests)))
+        })
+    })
+    .await?
+    .map(JsonResponse::json_response)
+}
+
+fn retrieve_window<'w, W>(
+    window_repository: &W,
+    window_id: i32,
+) -> Result<MaintenanceWindow, CalpolApiError>
+where
+    W: MaintenanceWindowRepository + 'w,
+{
+    window_repository.find_by_id(window_id)?.ok_or_else(|| {
+        ApiError::builder(StatusCode::NOT_FOUND)
+            .message("Maintenance window id not found")
+            .finish()
+            .into()
+    })
+}
+
+async fn get(
+    auth: Auth,
+    window_id: Path<i32>,
+    state: Data<AppState>,
+) -> Result<HttpResponse, CalpolApiError> {
+    auth.require_scope(Scope::MaintenanceWindowsRead)?;
+    web::block(move || -> Result<_, CalpolApiError> {
+        let database = state.database();
+        let window_repository = MaintenanceWindowRepositoryImpl::new(&database);
+        let window = retrieve_window(&window_repository, *window_id)?;
+        let tests = window_repository.find_tests(&window)?;
+        Ok(MaintenanceWindowSummary::from((window, tests)))
+    })
+    .await?
+    .map(JsonResponse::json_response)
+}
+
+async fn update(
+    auth: Auth,
+    window_id: Path<i32>,
+    json: actix_web_validator::Json<UpdateMaintenanceWindowRequest>,
+    state: Data<AppState>,
+) -> Result<HttpResponse, CalpolApiError> {
+    auth.require_scope(Scope::MaintenanceWindowsWrite)?;
+    auth.require_role(Role::Operator)?;
+    web::block(move || -> Result<_, CalpolApiError> {
+        let database = state.database();
+        let window_repository = MaintenanceWindowRepositoryImpl::new(&database);
+        let mut window = retrieve_window(&window_repository, *window_id)?;
+        if let Some(name) = &json.name {
+            window.name = name.clone();
+        }
+        if let Some(start) = &json.start {
+            window.start_time = parse_time(start, "start")?;
+        }
+        if let Some(end) = &json.end {
+            window.end_time = parse_time(end, "end")?;
+        }
+        validate_times(window.start_time, window.end_time)?;
+        window_repository.update(&window)?;
+        let tests = window_repository.find_tests(&window)?;
+        Ok(MaintenanceWindowSummary::from((window, tests)))
+    })
+    .await?
+    .map(JsonResponse::json_response)
+}
+
+async fn delete(
+    auth: Auth,
+    window_id: Path<i32>,
+    state: Data<AppState>,
+) -> Result<HttpResponse, CalpolApiError> {
+    auth.require_scope(Scope::MaintenanceWindowsWrite)?;
+    auth.require_role(Role::Operator)?;
+    web::block(move || -> Result<_, CalpolApiError> {
+        let database = state.database();
+        let window_repository = MaintenanceWindowRepositoryImpl::new(&database);
+        let window = retrieve_window(&window_repository, *window_id)?;
+        window_repository.delete(window)?;
+        Ok(())
+    })
+    .await?
+    .map(JsonResponse::json_response)
+}
+
+fn parse_time(time: &str, field: &str) -> Result<DateTime<Utc>, CalpolApiError> {
+    DateTime::parse_from_rfc3339(time)
+        .map(|t| t.with_timezone(&Utc))
+        .map_err(|e| {
+            ApiError::builder(StatusCode::BAD_REQUEST)
+                .message(format!("Invalid {} time, expected RFC3339: {}", field, e))
+                .finish()
+                .into()
+        })
+}
+
+fn validate_times(start: DateTime<Utc>, end: DateTime<Utc>) -> Result<(), CalpolApiError> {
+    if end <= start {
+        return Err(ApiError::builder(StatusCode::BAD_REQUEST)
+            .message("Maintenance window must end after it starts")
+            .finish()
+            .into());
+    }
+    Ok(())
+}
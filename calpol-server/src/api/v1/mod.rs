@@ -1,4 +1,8 @@
+mod api_keys;
 mod converters;
+mod escalation_policies;
+mod incidents;
+mod maintenance_windows;
 mod password_reset;
 mod runner_logs;
 mod sessions;
@@ -6,7 +10,7 @@ mod test_results;
 mod tests;
 mod users;
 
-use crate::api::auth::authenticator;
+use crate::api::auth::{authenticator, Auth};
 use crate::api::error::CalpolApiError;
 use crate::api::{api_resource, api_scope, JsonResponse};
 use crate::AppState;
@@ -14,6 +18,7 @@ use actix_extensible_rate_limit::backend::memory::InMemoryBackend;
 use actix_web::web::{Data, ServiceConfig};
 use actix_web::{web, HttpResponse};
 use actix_web_httpauth::middleware::HttpAuthentication;
+use calpol_model::api_v1::{Role, Scope};
 
 pub fn configure(api: &mut ServiceConfig, rate_limit_backend: &InMemoryBackend) {
     let auth = HttpAuthentication::with_fn(authenticator);
@@ -21,10 +26,14 @@ pub fn configure(api: &mut ServiceConfig, rate_limit_backend: &InMemoryBackend)
         api_scope("v1")
             .configure(|v1| sessions::configure(v1, rate_limit_backend))
             .configure(users::configure)
+            .configure(api_keys::configure)
             .configure(|v1| password_reset::configure(v1, rate_limit_backend))
             .configure(tests::configure)
             .configure(test_results::configure)
             .configure(runner_logs::configure)
+            .configure(maintenance_windows::configure)
+            .configure(escalation_policies::configure)
+            .configure(incidents::configure)
             .service(
                 api_resource("re_run")
                     .route(web::post().to(re_run))
@@ -33,7 +42,9 @@ pub fn configure(api: &mut ServiceConfig, rate_limit_backend: &InMemoryBackend)
     );
 }
 
-async fn re_run(state: Data<AppState>) -> Result<HttpResponse, CalpolApiError> {
+async fn re_run(auth: Auth, state: Data<AppState>) -> Result<HttpResponse, CalpolApiError> {
+    auth.require_scope(Scope::ReRun)?;
+    auth.require_role(Role::Operator)?;
     state.queue_test_run();
     Ok(().json_response())
 }
@@ -27,17 +27,14 @@ pub fn configure(v1: &mut ServiceConfig, rl_backend: &InMemoryBackend) {
 pub async fn send_reset_email<M, E>(
     mailer: &M,
     user: &User,
+    token: &str,
     settings: &Settings,
 ) -> Result<(), CalpolApiError>
 where
     M: lettre::AsyncTransport<Error = E> + Sync,
     E: Into<CalpolApiError>,
 {
-    let body = format!(
-        "Hello {}\n\nYour reset token is: \n{}",
-        user.name,
-        user.password_reset_token.as_ref().unwrap_or(&String::new())
-    );
+    let body = format!("Hello {}\n\nYour reset token is: \n{}", user.name, token);
     let message = Message::builder()
         .to(user
             .get_mailbox()
@@ -56,6 +53,8 @@ async fn request(
     json: actix_web_validator::Json<ResetPasswordRequest>,
 ) -> Result<HttpResponse, CalpolApiError> {
     let database = state.database();
+    let token = auth::generate_token();
+    let token_hash = auth::hash_token(&state.settings, &token);
     let user = web::block(move || -> Result<_, CalpolApiError> {
         let user_repository = UserRepositoryImpl::new(&database);
         let mut user = user_repository.find_by_email(&json.email)?.ok_or_else(|| {
@@ -63,13 +62,13 @@ async fn request(
                 .message("Email not found")
                 .finish()
         })?;
-        user.password_reset_token = Some(auth::generate_token());
+        user.password_reset_token_hash = Some(token_hash);
         user.password_reset_token_creation = Some(Utc::now());
         user_repository.update(&user)?;
         Ok(user)
     })
     .await??;
-    send_reset_email(&state.mailer, &user, &state.settings).await?;
+    send_reset_email(&state.mailer, &user, &token, &state.settings).await?;
     Ok(().json_response())
 }
 
@@ -81,7 +80,7 @@ async fn submit(
         let database = state.database();
         let user_repository = UserRepositoryImpl::new(&database);
         let mut user = user_repository
-            .find_by_reset_token(&json.token)?
+            .find_by_reset_token_hash(&auth::hash_token(&state.settings, &json.token))?
             .ok_or_else(|| {
                 ApiError::builder(StatusCode::BAD_REQUEST)
                     .message("Invalid reset token")
@@ -100,7 +99,7 @@ async fn submit(
                 .into());
         }
         user.password_hash = Some(bcrypt::hash(&json.new_password, bcrypt::DEFAULT_COST)?);
-        user.password_reset_token = None;
+        user.password_reset_token_hash = None;
         user.password_reset_token_creation = None;
         user_repository.update(&user)?;
         Ok(())
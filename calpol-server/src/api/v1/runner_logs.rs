@@ -1,4 +1,4 @@
-use crate::api::auth::authenticator;
+use crate::api::auth::{authenticator, Auth};
 use crate::api::error::CalpolApiError;
 use crate::api::{api_resource, api_scope, JsonResponse};
 use crate::database::{RunnerLogRepository, RunnerLogRepositoryImpl};
@@ -6,7 +6,7 @@ use crate::state::AppState;
 use actix_web::web::{Data, ServiceConfig};
 use actix_web::{web, HttpResponse};
 use actix_web_httpauth::middleware::HttpAuthentication;
-use calpol_model::api_v1::{ListRunnerLogsRequest, ListRunnerLogsResponse};
+use calpol_model::api_v1::{ListRunnerLogsRequest, ListRunnerLogsResponse, Scope};
 
 pub fn configure(v1: &mut ServiceConfig) {
     let auth = HttpAuthentication::with_fn(authenticator);
@@ -18,9 +18,11 @@ pub fn configure(v1: &mut ServiceConfig) {
 }
 
 async fn list(
+    auth: Auth,
     state: Data<AppState>,
     json: actix_web_validator::Json<ListRunnerLogsRequest>,
 ) -> Result<HttpResponse, CalpolApiError> {
+    auth.require_scope(Scope::ResultsRead)?;
     web::block(move || -> Result<_, CalpolApiError> {
         let database = state.database();
         let log_repository = RunnerLogRepositoryImpl::new(&database);
@@ -11,7 +11,6 @@ use actix_web::web::{Data, Path, ServiceConfig};
 use actix_web::{web, HttpRequest, HttpResponse};
 use actix_web_httpauth::middleware::HttpAuthentication;
 use calpol_model::api_v1::{LoginRequest, LoginResponse, SessionSummary};
-use chrono::Utc;
 use diesel_repository::CrudRepository;
 use http_api_problem::ApiError;
 use std::net::IpAddr;
@@ -77,27 +76,18 @@ async fn login(
         let session_repository = SessionRepositoryImpl::new(&database);
         let ip_addr = ip_addr;
         let ip_bin = bincode::serialize(&ip_addr).unwrap();
-        let existing_session = session_repository.find_belonging_to_user_by_ip_and_agent(
-            &user,
-            &ip_bin,
-            &user_agent,
-        )?;
-        let session = match existing_session {
-            Some(mut existing_session) => {
-                existing_session.last_used = Utc::now();
-                session_repository.update(&existing_session)?;
-                existing_session
-            }
-            None => session_repository.insert(NewSession {
-                user_id: user.id,
-                token: auth::generate_token(),
-                last_ip: ip_bin,
-                user_agent,
-            })?,
-        };
+        // Only the hash of the token is stored, so the token of an existing session can't be
+        // returned again, each login creates a new session instead.
+        let token = auth::generate_token();
+        let session = session_repository.insert(NewSession {
+            user_id: user.id,
+            token_hash: auth::hash_token(&state.settings, &token),
+            last_ip: ip_bin,
+            user_agent,
+        })?;
         Ok(LoginResponse {
             user: user.into(),
-            token: session.token.clone(),
+            token,
             session: session.into(),
         })
     })
@@ -107,9 +97,10 @@ async fn login(
 
 async fn logout(auth: Auth, state: Data<AppState>) -> Result<HttpResponse, CalpolApiError> {
     web::block(move || -> Result<_, CalpolApiError> {
+        let session = auth.session()?;
         let database = state.database();
         let session_repository = SessionRepositoryImpl::new(&database);
-        session_repository.delete(auth.session)?;
+        session_repository.delete_by_id_and_user(session.id, &auth.user)?;
         Ok(())
     })
     .await?
@@ -117,6 +108,7 @@ async fn logout(auth: Auth, state: Data<AppState>) -> Result<HttpResponse, Calpo
 }
 
 async fn list(auth: Auth, state: Data<AppState>) -> Result<HttpResponse, CalpolApiError> {
+    auth.session()?;
     web::block(move || -> Result<_, CalpolApiError> {
         let database = state.database();
         let session_repository = SessionRepositoryImpl::new(&database);
@@ -136,6 +128,7 @@ async fn delete(
     session_id: Path<i32>,
     state: Data<AppState>,
 ) -> Result<HttpResponse, CalpolApiError> {
+    auth.session()?;
     web::block(move || -> Result<_, CalpolApiError> {
         let database = state.database();
         let session_repository = SessionRepositoryImpl::new(&database);
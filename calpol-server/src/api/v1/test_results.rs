@@ -1,4 +1,4 @@
-use crate::api::auth::authenticator;
+use crate::api::auth::{authenticator, Auth};
 use crate::api::error::CalpolApiError;
 use crate::api::v1::converters;
 use crate::api::v1::tests::retrieve_test;
@@ -8,7 +8,7 @@ use crate::state::AppState;
 use actix_web::web::{Data, Path, ServiceConfig};
 use actix_web::{web, HttpResponse};
 use actix_web_httpauth::middleware::HttpAuthentication;
-use calpol_model::api_v1::GetTestResultsRequest;
+use calpol_model::api_v1::{GetTestResultsRequest, Scope};
 use diesel_repository::CrudRepository;
 
 pub fn configure(v1: &mut ServiceConfig) {
@@ -21,7 +21,8 @@ pub fn configure(v1: &mut ServiceConfig) {
     );
 }
 
-async fn list(state: Data<AppState>) -> Result<HttpResponse, CalpolApiError> {
+async fn list(auth: Auth, state: Data<AppState>) -> Result<HttpResponse, CalpolApiError> {
+    auth.require_scope(Scope::ResultsRead)?;
     web::block(move || -> Result<_, CalpolApiError> {
         let database = state.database();
         let test_repository = TestRepositoryImpl::new(&database);
@@ -46,10 +47,12 @@ async fn list(state: Data<AppState>) -> Result<HttpResponse, CalpolApiError> {
 }
 
 async fn get(
+    auth: Auth,
     state: Data<AppState>,
     test_name: Path<String>,
     json: actix_web_validator::Json<GetTestResultsRequest>,
 ) -> Result<HttpResponse, CalpolApiError> {
+    auth.require_scope(Scope::ResultsRead)?;
     web::block(move || -> Result<_, CalpolApiError> {
         let database = state.database();
         let test_repository = TestRepositoryImpl::new(&database);
@@ -1,20 +1,28 @@
-use crate::api::auth::authenticator;
-use crate::api::error::{CalpolApiError, MapDieselUniqueViolation};
+use crate::api::auth::{authenticator, Auth};
+use crate::api::error::{CalpolApiError, MapDieselUniqueViolation, UnexpectedError};
 use crate::api::{api_resource, api_scope, JsonResponse};
 use crate::database::{
     NewTest, Test, TestRepository, TestRepositoryImpl, TestResultRepository,
     TestResultRepositoryImpl,
 };
+use crate::settings::RunnerSetting;
 use crate::state::AppState;
+use crate::test_runner::{payload_bytes, ParsedSchedule, ResponseMatcher};
+use actix_web::http::header::{HeaderName, HeaderValue};
 use actix_web::http::StatusCode;
 use actix_web::web::{Data, Path, ServiceConfig};
 use actix_web::{web, HttpResponse};
 use actix_web_httpauth::middleware::HttpAuthentication;
-use calpol_model::api_v1::{CreateTestRequest, TestSummary, UpdateTestRequest};
+use calpol_model::api_v1::{CreateTestRequest, Role, Scope, TestSummary, UpdateTestRequest};
+use calpol_model::tests::{
+    BodyAssertion, Payload, ResponseMatch, TestConfig, TestVariant, REDACTED,
+};
 use diesel::Connection;
 use diesel_repository::CrudRepository;
 use http_api_problem::ApiError;
+use regex::Regex;
 use std::convert::TryFrom;
+use std::str::FromStr;
 
 pub fn configure(v1: &mut ServiceConfig) {
     let auth = HttpAuthentication::with_fn(authenticator);
@@ -35,7 +43,8 @@ pub fn configure(v1: &mut ServiceConfig) {
     );
 }
 
-async fn list(state: Data<AppState>) -> Result<HttpResponse, CalpolApiError> {
+async fn list(auth: Auth, state: Data<AppState>) -> Result<HttpResponse, CalpolApiError> {
+    auth.require_scope(Scope::TestsRead)?;
     web::block(move || {
         let database = state.database();
         let test_repository = TestRepositoryImpl::new(&database);
@@ -51,19 +60,32 @@ async fn list(state: Data<AppState>) -> Result<HttpResponse, CalpolApiError> {
 }
 
 async fn create(
+    auth: Auth,
     state: Data<AppState>,
     json: actix_web_validator::Json<CreateTestRequest>,
 ) -> Result<HttpResponse, CalpolApiError> {
+    auth.require_scope(Scope::TestsWrite)?;
+    auth.require_role(Role::Operator)?;
     web::block(move || {
+        let mut config = json.config.clone();
+        restore_secrets(&mut config, None)?;
+        validate_config(&config)?;
+        let run_interval = json.interval_minutes.map(i32::from);
+        let run_timeout = json.timeout_seconds.map(i32::from);
+        validate_run_timeout(&state.settings.runner, run_interval, run_timeout)?;
         let database = state.database();
         let test_repository = TestRepositoryImpl::new(&database);
         let test = test_repository
             .insert(&NewTest {
                 name: json.name.clone(),
                 enabled: json.enabled,
-                config: serde_json::to_value(&json.config).unwrap(),
+                config: serde_json::to_value(&config).unwrap(),
                 failing: false,
                 failure_threshold: json.failure_threshold as i32,
+                run_interval,
+                run_timeout,
+                tags: json.tags.clone(),
+                severity: json.severity.as_str().to_string(),
             })
             .map_unique_violation(|_| {
                 ApiError::builder(StatusCode::CONFLICT)
@@ -77,6 +99,107 @@ async fn create(
     .map(JsonResponse::json_response)
 }
 
+/// Check the test's timeout (in seconds) is less than how often it runs (in minutes), which may be
+/// the runner interval if the test doesn't have its own.
+fn validate_run_timeout(
+    runner: &RunnerSetting,
+    run_interval: Option<i32>,
+    run_timeout: Option<i32>,
+) -> Result<(), CalpolApiError> {
+    if run_interval.map(|i| i < 1).unwrap_or(false) || run_timeout.map(|t| t < 1).unwrap_or(false) {
+        return Err(ApiError::builder(StatusCode::BAD_REQUEST)
+            .message("Interval and timeout must be at least 1")
+            .finish()
+            .into());
+    }
+    let interval_seconds = run_interval.unwrap_or(runner.interval as i32) * 60;
+    if let Some(timeout) = run_timeout {
+        if timeout >= interval_seconds {
+            return Err(ApiError::builder(StatusCode::BAD_REQUEST)
+                .message("Timeout must be less than the run interval")
+                .finish()
+                .into());
+        }
+    }
+    Ok(())
+}
+
+/// Secrets are redacted when tests are returned by the API, so that they can be updated without
+/// knowing the secrets any placeholders are replaced with the existing secrets.
+fn restore_secrets(
+    config: &mut TestConfig,
+    existing: Option<&TestConfig>,
+) -> Result<(), CalpolApiError> {
+    if !config.restore_redacted(existing) {
+        return Err(ApiError::builder(StatusCode::BAD_REQUEST)
+            .message(format!(
+                "The test doesn't have an existing secret to replace `{}` with",
+                REDACTED
+            ))
+            .finish()
+            .into());
+    }
+    Ok(())
+}
+
+/// Check parts of the config that can't be validated by the model, so that the test doesn't
+/// fail unexpectedly when it is run.
+fn validate_config(config: &TestConfig) -> Result<(), CalpolApiError> {
+    if let Some(schedule) = &config.schedule {
+        ParsedSchedule::try_from(schedule).map_err(|e| {
+            ApiError::builder(StatusCode::BAD_REQUEST)
+                .message(format!("Invalid schedule: {:#}", e))
+                .finish()
+        })?;
+    }
+    if let TestVariant::Http(http) = &config.variant {
+        for (name, value) in &http.headers {
+            if HeaderName::from_str(name).is_err() || HeaderValue::from_str(value).is_err() {
+                return Err(ApiError::builder(StatusCode::BAD_REQUEST)
+                    .message(format!("Invalid http header: {}", name))
+                    .finish()
+                    .into());
+            }
+        }
+        for assertion in &http.body_assertions {
+            if let BodyAssertion::Regex { pattern } = assertion {
+                Regex::new(pattern).map_err(|e| {
+                    ApiError::builder(StatusCode::BAD_REQUEST)
+                        .message(format!("Invalid body regex: {}", e))
+                        .finish()
+                })?;
+            }
+        }
+    }
+    match &config.variant {
+        TestVariant::Tcp(tcp) => validate_exchange(tcp.send.as_ref(), tcp.expect.as_ref())?,
+        TestVariant::Udp(udp) => validate_exchange(Some(&udp.send), udp.expect.as_ref())?,
+        _ => {}
+    }
+    Ok(())
+}
+
+fn validate_exchange(
+    send: Option<&Payload>,
+    expect: Option<&ResponseMatch>,
+) -> Result<(), CalpolApiError> {
+    if let Some(send) = send {
+        payload_bytes(send).map_err(|e| {
+            ApiError::builder(StatusCode::BAD_REQUEST)
+                .message(format!("{:#}", e))
+                .finish()
+        })?;
+    }
+    if let Some(expect) = expect {
+        ResponseMatcher::try_from(expect).map_err(|e| {
+            ApiError::builder(StatusCode::BAD_REQUEST)
+                .message(format!("{:#}", e))
+                .finish()
+        })?;
+    }
+    Ok(())
+}
+
 pub fn retrieve_test<'t, T>(test_repository: &T, test_name: &str) -> Result<Test, CalpolApiError>
 where
     T: TestRepository + 't,
@@ -89,10 +212,33 @@ where
     })
 }
 
+/// Finds each of the named tests, returning an error if any do not exist.
+pub fn retrieve_tests<'t, T>(
+    test_repository: &T,
+    names: &[String],
+) -> Result<Vec<Test>, CalpolApiError>
+where
+    T: TestRepository + 't,
+{
+    let tests = test_repository.find_by_names(names)?;
+    if let Some(missing) = names
+        .iter()
+        .find(|name| !tests.iter().any(|t| &t.name == *name))
+    {
+        return Err(ApiError::builder(StatusCode::NOT_FOUND)
+            .message(format!("Test name not found: {}", missing))
+            .finish()
+            .into());
+    }
+    Ok(tests)
+}
+
 async fn get(
+    auth: Auth,
     state: Data<AppState>,
     test_name: Path<String>,
 ) -> Result<HttpResponse, CalpolApiError> {
+    auth.require_scope(Scope::TestsRead)?;
     web::block(move || {
         let database = state.database();
         let test_repository = TestRepositoryImpl::new(&database);
@@ -104,10 +250,13 @@ async fn get(
 }
 
 async fn update(
+    auth: Auth,
     test_name: Path<String>,
     json: actix_web_validator::Json<UpdateTestRequest>,
     state: Data<AppState>,
 ) -> Result<HttpResponse, CalpolApiError> {
+    auth.require_scope(Scope::TestsWrite)?;
+    auth.require_role(Role::Operator)?;
     web::block(move || {
         let database = state.database();
         let body = json.into_inner();
@@ -116,12 +265,29 @@ async fn update(
         if let Some(enabled) = body.enabled {
             test.enabled = enabled;
         }
-        if let Some(config) = body.config {
+        if let Some(mut config) = body.config {
+            let existing: TestConfig = serde_json::from_value(test.config.clone())
+                .map_err(UnexpectedError::TestDeserialization)?;
+            restore_secrets(&mut config, Some(&existing))?;
+            validate_config(&config)?;
             test.config = serde_json::to_value(config).unwrap();
         }
         if let Some(failure_threshold) = body.failure_threshold {
             test.failure_threshold = failure_threshold as i32;
         }
+        if let Some(interval) = body.interval_minutes {
+            test.run_interval = interval.map(i32::from);
+        }
+        if let Some(timeout) = body.timeout_seconds {
+            test.run_timeout = timeout.map(i32::from);
+        }
+        validate_run_timeout(&state.settings.runner, test.run_interval, test.run_timeout)?;
+        if let Some(tags) = body.tags {
+            test.tags = tags;
+        }
+        if let Some(severity) = body.severity {
+            test.severity = severity.as_str().to_string();
+        }
         test_repository.update(&test)?;
         TestSummary::try_from(test)
     })
@@ -130,9 +296,12 @@ async fn update(
 }
 
 async fn delete(
+    auth: Auth,
     test_name: Path<String>,
     state: Data<AppState>,
 ) -> Result<HttpResponse, CalpolApiError> {
+    auth.require_scope(Scope::TestsWrite)?;
+    auth.require_role(Role::Operator)?;
     web::block(move || -> Result<_, CalpolApiError> {
         let database = state.database();
         let test_repository = TestRepositoryImpl::new(&database);
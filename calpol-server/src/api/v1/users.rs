@@ -1,9 +1,12 @@
 use crate::api::auth::{authenticator, Auth};
 use crate::api::error::{CalpolApiError, MapDieselUniqueViolation, UnexpectedError};
 use crate::api::v1::password_reset::send_reset_email;
+use crate::api::v1::tests::retrieve_tests;
 use crate::api::{api_resource, api_scope, auth, JsonResponse};
 use crate::database::{
-    NewUser, SessionRepository, SessionRepositoryImpl, User, UserRepository, UserRepositoryImpl,
+    NewUser, NewUserSubscription, SessionRepository, SessionRepositoryImpl, TestRepositoryImpl,
+    User, UserRepository, UserRepositoryImpl, UserSubscriptionRepository,
+    UserSubscriptionRepositoryImpl,
 };
 use crate::state::AppState;
 use actix_web::http::StatusCode;
@@ -11,13 +14,16 @@ use actix_web::web::{Data, Path, ServiceConfig};
 use actix_web::{web, HttpResponse};
 use actix_web_httpauth::middleware::HttpAuthentication;
 use calpol_model::api_v1::{
-    CreateUserRequest, ListUsersRequest, ListUsersResponse, UpdateUserRequest, UserSummary,
+    CreateUserRequest, ListUsersRequest, ListUsersResponse, Role, Scope, UpdateUserRequest,
+    UserSubscriptions, UserSummary,
 };
-use chrono::Utc;
+use chrono::{NaiveTime, Utc};
+use chrono_tz::Tz;
 use diesel::Connection;
 use diesel_repository::CrudRepository;
 use http_api_problem::ApiError;
 use lettre::{AsyncTransport, Message};
+use std::str::FromStr;
 
 pub fn configure(v1: &mut ServiceConfig) {
     let auth = HttpAuthentication::with_fn(authenticator);
@@ -36,15 +42,21 @@ pub fn configure(v1: &mut ServiceConfig) {
             )
             .service(api_resource("{user_id}/test_email").route(web::post().to(test_email)))
             .service(api_resource("{user_id}/test_sms").route(web::post().to(test_sms)))
+            .service(
+                api_resource("{user_id}/subscriptions")
+                    .route(web::get().to(get_subscriptions))
+                    .route(web::put().to(update_subscriptions)),
+            )
             .wrap(auth),
     );
 }
 
 async fn list(
-    _auth: Auth,
+    auth: Auth,
     query: actix_web_validator::Query<ListUsersRequest>,
     state: Data<AppState>,
 ) -> Result<HttpResponse, CalpolApiError> {
+    auth.require_scope(Scope::UsersRead)?;
     web::block(move || -> Result<_, CalpolApiError> {
         let database = state.database();
         let user_repository = UserRepositoryImpl::new(&database);
@@ -65,11 +77,15 @@ async fn list(
 }
 
 pub async fn create(
-    _auth: Auth,
+    auth: Auth,
     json: actix_web_validator::Json<CreateUserRequest>,
     state: Data<AppState>,
 ) -> Result<HttpResponse, CalpolApiError> {
+    auth.require_scope(Scope::UsersWrite)?;
+    auth.require_role(Role::Admin)?;
     let database = state.database();
+    let token = auth::generate_token();
+    let token_hash = auth::hash_token(&state.settings, &token);
     let user = web::block(move || -> Result<_, CalpolApiError> {
         let user_repository = UserRepositoryImpl::new(&database);
         let user = user_repository
@@ -79,8 +95,9 @@ pub async fn create(
                 password_hash: None,
                 sms_notifications: false,
                 email_notifications: false,
-                password_reset_token: Some(auth::generate_token()),
+                password_reset_token_hash: Some(token_hash),
                 password_reset_token_creation: Some(Utc::now()),
+                role: json.role.as_str().to_string(),
             })
             .map_unique_violation(|_| {
                 ApiError::builder(StatusCode::CONFLICT)
@@ -92,13 +109,13 @@ pub async fn create(
         Ok(user)
     })
     .await??;
-    if let Err(e) = send_reset_email(&state.mailer, &user, &state.settings).await {
+    if let Err(e) = send_reset_email(&state.mailer, &user, &token, &state.settings).await {
         log::error!("Unable to send reset email on account creation: {}", e)
     }
     Ok(UserSummary::from(user).json_response())
 }
 
-fn retrieve_user<'u, U>(user_repository: &U, user_id: i32) -> Result<User, CalpolApiError>
+pub fn retrieve_user<'u, U>(user_repository: &U, user_id: i32) -> Result<User, CalpolApiError>
 where
     U: UserRepository + 'u,
 {
@@ -111,10 +128,11 @@ where
 }
 
 async fn get(
-    _auth: Auth,
+    auth: Auth,
     user_id: Path<i32>,
     state: Data<AppState>,
 ) -> Result<HttpResponse, CalpolApiError> {
+    auth.require_scope(Scope::UsersRead)?;
     web::block(move || -> Result<_, CalpolApiError> {
         let database = state.database();
         let user_repository = UserRepositoryImpl::new(&database);
@@ -126,11 +144,13 @@ async fn get(
 }
 
 async fn update(
-    _auth: Auth,
+    auth: Auth,
     user_id: Path<i32>,
     json: actix_web_validator::Json<UpdateUserRequest>,
     state: Data<AppState>,
 ) -> Result<HttpResponse, CalpolApiError> {
+    auth.require_scope(Scope::UsersWrite)?;
+    auth.require_self_or_admin(*user_id)?;
     web::block(move || -> Result<_, CalpolApiError> {
         let database = state.database();
         let user_repository = UserRepositoryImpl::new(&database);
@@ -141,6 +161,16 @@ async fn update(
         if let Some(name) = &json.name {
             user.name = name.clone();
         }
+        if let Some(role) = json.role {
+            auth.require_role(Role::Admin)?;
+            if auth.user.id == user.id && role != Role::Admin {
+                return Err(ApiError::builder(StatusCode::BAD_REQUEST)
+                    .message("Admins can't remove their own admin role")
+                    .finish()
+                    .into());
+            }
+            user.role = role.as_str().to_string();
+        }
         if let Some(sms_notifications) = json.sms_notifications {
             user.sms_notifications = sms_notifications;
         }
@@ -150,6 +180,32 @@ async fn update(
         if let Some(phone_number) = &json.phone_number {
             user.phone_number = Some(phone_number.clone());
         }
+        if let Some(severity) = json.email_min_severity {
+            user.email_min_severity = severity.as_str().to_string();
+        }
+        if let Some(severity) = json.sms_min_severity {
+            user.sms_min_severity = severity.as_str().to_string();
+        }
+        if let Some(timezone) = &json.timezone {
+            Tz::from_str(timezone).map_err(|_| {
+                ApiError::builder(StatusCode::BAD_REQUEST)
+                    .message(format!("Unknown timezone `{}`", timezone))
+                    .finish()
+            })?;
+            user.timezone = timezone.clone();
+        }
+        if let Some(quiet_hours) = &json.quiet_hours {
+            let start = parse_time(&quiet_hours.start)?;
+            let end = parse_time(&quiet_hours.end)?;
+            if start == end {
+                user.quiet_hours_start = None;
+                user.quiet_hours_end = None;
+            } else {
+                user.quiet_hours_start = Some(start);
+                user.quiet_hours_end = Some(end);
+            }
+            user.quiet_hours_sms = quiet_hours.sms.as_str().to_string();
+        }
         user_repository.update(&user).map_unique_violation(|_| {
             ApiError::builder(StatusCode::CONFLICT)
                 .title("Email Taken")
@@ -163,11 +219,21 @@ async fn update(
     .map(JsonResponse::json_response)
 }
 
+fn parse_time(time: &str) -> Result<NaiveTime, CalpolApiError> {
+    Ok(NaiveTime::parse_from_str(time, "%H:%M").map_err(|_| {
+        ApiError::builder(StatusCode::BAD_REQUEST)
+            .message(format!("Invalid time `{}`, expected HH:MM", time))
+            .finish()
+    })?)
+}
+
 async fn delete(
-    _auth: Auth,
+    auth: Auth,
     user_id: Path<i32>,
     state: Data<AppState>,
 ) -> Result<HttpResponse, CalpolApiError> {
+    auth.require_scope(Scope::UsersWrite)?;
+    auth.require_role(Role::Admin)?;
     web::block(move || -> Result<_, CalpolApiError> {
         let database = state.database();
         let user_repository = UserRepositoryImpl::new(&database);
@@ -184,10 +250,12 @@ async fn delete(
 }
 
 async fn test_email(
-    _auth: Auth,
+    auth: Auth,
     user_id: Path<i32>,
     state: Data<AppState>,
 ) -> Result<HttpResponse, CalpolApiError> {
+    auth.require_scope(Scope::UsersWrite)?;
+    auth.require_self_or_admin(*user_id)?;
     let database = state.database();
     let user = web::block(move || -> Result<_, CalpolApiError> {
         let user_repository = UserRepositoryImpl::new(&database);
@@ -208,10 +276,12 @@ async fn test_email(
 }
 
 async fn test_sms(
-    _auth: Auth,
+    auth: Auth,
     user_id: Path<i32>,
     state: Data<AppState>,
 ) -> Result<HttpResponse, CalpolApiError> {
+    auth.require_scope(Scope::UsersWrite)?;
+    auth.require_self_or_admin(*user_id)?;
     let database = state.database();
     let user = web::block(move || {
         let user_repository = UserRepositoryImpl::new(&database);
@@ -219,10 +289,9 @@ async fn test_sms(
     })
     .await??;
     if let Some(phone) = user.phone_number {
-        if let Some(message_bird) = &state.message_bird {
-            let body = "Calpol Test SMS";
-            let res = message_bird.send_message(body, vec![phone.clone()]).await?;
-            log::info!("Sent test SMS to {}: {:?}", phone, res);
+        if let Some(sms) = &state.sms {
+            sms.send_sms("Calpol Test SMS", vec![phone.clone()]).await?;
+            log::info!("Sent test SMS to {}", phone);
             Ok(().json_response())
         } else {
             Err(ApiError::builder(StatusCode::BAD_REQUEST)
@@ -237,3 +306,87 @@ async fn test_sms(
             .into())
     }
 }
+
+fn summarise_subscriptions(
+    subscription_repository: &UserSubscriptionRepositoryImpl,
+    user: &User,
+) -> Result<UserSubscriptions, CalpolApiError> {
+    let mut summary = UserSubscriptions {
+        all_tests: user.subscribe_all_tests,
+        tests: vec![],
+        tags: vec![],
+        excluded_tests: vec![],
+        excluded_tags: vec![],
+    };
+    for (subscription, test) in subscription_repository.find_belonging_to(user)? {
+        match (test, subscription.tag, subscription.excluded) {
+            (Some(test), _, false) => summary.tests.push(test.name),
+            (Some(test), _, true) => summary.excluded_tests.push(test.name),
+            (None, Some(tag), false) => summary.tags.push(tag),
+            (None, Some(tag), true) => summary.excluded_tags.push(tag),
+            (None, None, _) => {}
+        }
+    }
+    Ok(summary)
+}
+
+async fn get_subscriptions(
+    auth: Auth,
+    user_id: Path<i32>,
+    state: Data<AppState>,
+) -> Result<HttpResponse, CalpolApiError> {
+    auth.require_scope(Scope::UsersRead)?;
+    web::block(move || -> Result<_, CalpolApiError> {
+        let database = state.database();
+        let user = retrieve_user(&UserRepositoryImpl::new(&database), *user_id)?;
+        summarise_subscriptions(&UserSubscriptionRepositoryImpl::new(&database), &user)
+    })
+    .await?
+    .map(JsonResponse::json_response)
+}
+
+/// Replaces the tests and tags the user is subscribed to or excluded from.
+async fn update_subscriptions(
+    auth: Auth,
+    user_id: Path<i32>,
+    json: actix_web_validator::Json<UserSubscriptions>,
+    state: Data<AppState>,
+) -> Result<HttpResponse, CalpolApiError> {
+    auth.require_scope(Scope::UsersWrite)?;
+    auth.require_self_or_admin(*user_id)?;
+    web::block(move || -> Result<_, CalpolApiError> {
+        let database = state.database();
+        let user_repository = UserRepositoryImpl::new(&database);
+        let subscription_repository = UserSubscriptionRepositoryImpl::new(&database);
+        let test_repository = TestRepositoryImpl::new(&database);
+        let mut user = retrieve_user(&user_repository, *user_id)?;
+        let tests = retrieve_tests(&test_repository, &json.tests)?;
+        let excluded_tests = retrieve_tests(&test_repository, &json.excluded_tests)?;
+        let subscriptions = tests
+            .iter()
+            .map(|t| (Some(t.id), None, false))
+            .chain(excluded_tests.iter().map(|t| (Some(t.id), None, true)))
+            .chain(json.tags.iter().map(|t| (None, Some(t.clone()), false)))
+            .chain(
+                json.excluded_tags
+                    .iter()
+                    .map(|t| (None, Some(t.clone()), true)),
+            )
+            .map(|(test_id, tag, excluded)| NewUserSubscription {
+                user_id: user.id,
+                test_id,
+                tag,
+                excluded,
+            })
+            .collect::<Vec<_>>();
+        user.subscribe_all_tests = json.all_tests;
+        database.transaction(|| -> Result<_, CalpolApiError> {
+            user_repository.update(&user)?;
+            subscription_repository.set_subscriptions(&user, &subscriptions)?;
+            Ok(())
+        })?;
+        summarise_subscriptions(&subscription_repository, &user)
+    })
+    .await?
+    .map(JsonResponse::json_response)
+}
@@ -0,0 +1,69 @@
+use crate::database::users::User;
+use crate::database::Connection;
+use crate::schema::api_keys::dsl as ApiKeys;
+use crate::schema::users::dsl as Users;
+use crate::schema::*;
+use chrono::{DateTime, Utc};
+use diesel::prelude::*;
+use diesel::QueryResult;
+use diesel_repository::{implement_crud_repository, CrudRepository};
+
+/// A long lived token for automation, limited to a set of scopes.
+#[derive(Debug, Queryable, Identifiable, Associations, AsChangeset)]
+#[belongs_to(User)]
+#[changeset_options(treat_none_as_null = "true")]
+pub struct ApiKey {
+    pub id: i32,
+    pub user_id: i32,
+    pub name: String,
+    /// Keyed hash of the API key
+    pub token_hash: String,
+    pub scopes: Vec<String>,
+    pub created: DateTime<Utc>,
+    pub expires: Option<DateTime<Utc>>,
+    pub last_used: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Insertable)]
+#[table_name = "api_keys"]
+pub struct NewApiKey {
+    pub user_id: i32,
+    pub name: String,
+    pub token_hash: String,
+    pub scopes: Vec<String>,
+    pub expires: Option<DateTime<Utc>>,
+}
+
+implement_crud_repository!(ApiKeyRepositoryImpl, ApiKey, i32, Connection);
+
+pub trait ApiKeyRepository: CrudRepository<ApiKey, i32> {
+    fn find_by_token_hash(&self, token_hash: &str) -> QueryResult<Option<(ApiKey, User)>>;
+
+    fn find_all_belonging_to_user(&self, user: &User) -> QueryResult<Vec<ApiKey>>;
+
+    fn delete_by_id_and_user(&self, api_key_id: i32, user: &User) -> QueryResult<bool>;
+}
+
+impl ApiKeyRepository for ApiKeyRepositoryImpl<'_> {
+    fn find_by_token_hash(&self, token_hash: &str) -> QueryResult<Option<(ApiKey, User)>> {
+        ApiKeys::api_keys
+            .filter(ApiKeys::token_hash.eq(token_hash))
+            .inner_join(Users::users)
+            .first::<(ApiKey, User)>(self.connection())
+            .optional()
+    }
+
+    fn find_all_belonging_to_user(&self, user: &User) -> QueryResult<Vec<ApiKey>> {
+        ApiKey::belonging_to(user)
+            .order(ApiKeys::id)
+            .load(self.connection())
+    }
+
+    fn delete_by_id_and_user(&self, api_key_id: i32, user: &User) -> QueryResult<bool> {
+        diesel::delete(
+            ApiKeys::api_keys.filter(ApiKeys::user_id.eq(user.id).and(ApiKeys::id.eq(api_key_id))),
+        )
+        .execute(self.connection())
+        .map(|affected| affected > 0)
+    }
+}
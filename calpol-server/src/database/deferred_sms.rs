@@ -0,0 +1,43 @@
+use crate::database::{Connection, User};
+use crate::schema::deferred_sms::dsl as DeferredSmsDsl;
+use crate::schema::users::dsl as Users;
+use crate::schema::*;
+use chrono::{DateTime, Utc};
+use diesel::prelude::*;
+use diesel_repository::{implement_crud_repository, CrudRepository};
+
+/// An SMS notification that was held back during a user's quiet hours.
+#[derive(Queryable, Debug, Identifiable, Insertable, AsChangeset)]
+#[table_name = "deferred_sms"]
+pub struct DeferredSms {
+    pub id: i32,
+    pub user_id: i32,
+    pub created: DateTime<Utc>,
+    pub body: String,
+    /// Number of times sending the SMS has failed.
+    pub attempts: i32,
+}
+
+#[derive(Queryable, Debug, Insertable, AsChangeset)]
+#[table_name = "deferred_sms"]
+pub struct NewDeferredSms {
+    pub user_id: i32,
+    pub created: DateTime<Utc>,
+    pub body: String,
+}
+
+implement_crud_repository!(DeferredSmsRepositoryImpl, DeferredSms, i32, Connection);
+
+pub trait DeferredSmsRepository: CrudRepository<DeferredSms, i32> {
+    /// Finds all the deferred SMS along with their user, in the order they were deferred.
+    fn find_all_with_user(&self) -> QueryResult<Vec<(DeferredSms, User)>>;
+}
+
+impl DeferredSmsRepository for DeferredSmsRepositoryImpl<'_> {
+    fn find_all_with_user(&self) -> QueryResult<Vec<(DeferredSms, User)>> {
+        DeferredSmsDsl::deferred_sms
+            .inner_join(Users::users)
+            .order(DeferredSmsDsl::id)
+            .load(self.connection())
+    }
+}
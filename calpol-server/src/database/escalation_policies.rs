@@ -0,0 +1,142 @@
+use crate::database::{Connection, Test, User};
+use crate::schema::escalation_policies::dsl as EscalationPolicies;
+use crate::schema::escalation_policy_tests::dsl as EscalationPolicyTests;
+use crate::schema::escalation_policy_users::dsl as EscalationPolicyUsers;
+use crate::schema::tests::dsl as Tests;
+use crate::schema::users::dsl as Users;
+use crate::schema::*;
+use diesel::prelude::*;
+use diesel_repository::{implement_crud_repository, CrudRepository};
+
+#[derive(Queryable, Debug, Identifiable, Insertable, AsChangeset)]
+#[table_name = "escalation_policies"]
+#[changeset_options(treat_none_as_null = "true")]
+pub struct EscalationPolicy {
+    pub id: i32,
+    pub name: String,
+    /// Minutes between reminders while a test is still failing.
+    pub repeat_interval: Option<i32>,
+    /// Minutes a test can be failing before it is escalated.
+    pub escalate_after: Option<i32>,
+    /// Names of additional notification channels to escalate to.
+    pub escalate_channels: Vec<String>,
+}
+
+#[derive(Queryable, Debug, Insertable, AsChangeset)]
+#[table_name = "escalation_policies"]
+pub struct NewEscalationPolicy {
+    pub name: String,
+    pub repeat_interval: Option<i32>,
+    pub escalate_after: Option<i32>,
+    pub escalate_channels: Vec<String>,
+}
+
+#[derive(Queryable, Debug, Insertable)]
+#[table_name = "escalation_policy_tests"]
+pub struct EscalationPolicyTest {
+    pub escalation_policy_id: i32,
+    pub test_id: i32,
+}
+
+#[derive(Queryable, Debug, Insertable)]
+#[table_name = "escalation_policy_users"]
+pub struct EscalationPolicyUser {
+    pub escalation_policy_id: i32,
+    pub user_id: i32,
+}
+
+implement_crud_repository!(
+    EscalationPolicyRepositoryImpl,
+    EscalationPolicy,
+    i32,
+    Connection
+);
+
+pub trait EscalationPolicyRepository: CrudRepository<EscalationPolicy, i32> {
+    fn find_by_name(&self, name: &str) -> QueryResult<Option<EscalationPolicy>>;
+    fn find_by_test(&self, test: &Test) -> QueryResult<Option<EscalationPolicy>>;
+    fn find_tests(&self, policy: &EscalationPolicy) -> QueryResult<Vec<Test>>;
+    fn find_users(&self, policy: &EscalationPolicy) -> QueryResult<Vec<User>>;
+    /// Replaces the tests the policy applies to, removing them from any other policy.
+    fn set_tests(&self, policy: &EscalationPolicy, tests: &[Test]) -> QueryResult<()>;
+    /// Replaces the users the policy escalates to.
+    fn set_users(&self, policy: &EscalationPolicy, users: &[User]) -> QueryResult<()>;
+}
+
+impl EscalationPolicyRepository for EscalationPolicyRepositoryImpl<'_> {
+    fn find_by_name(&self, name: &str) -> QueryResult<Option<EscalationPolicy>> {
+        EscalationPolicies::escalation_policies
+            .filter(EscalationPolicies::name.eq(name))
+            .first(self.connection())
+            .optional()
+    }
+
+    fn find_by_test(&self, test: &Test) -> QueryResult<Option<EscalationPolicy>> {
+        EscalationPolicyTests::escalation_policy_tests
+            .inner_join(EscalationPolicies::escalation_policies)
+            .filter(EscalationPolicyTests::test_id.eq(test.id))
+            .select(escalation_policies::all_columns)
+            .first(self.connection())
+            .optional()
+    }
+
+    fn find_tests(&self, policy: &EscalationPolicy) -> QueryResult<Vec<Test>> {
+        EscalationPolicyTests::escalation_policy_tests
+            .inner_join(Tests::tests)
+            .filter(EscalationPolicyTests::escalation_policy_id.eq(policy.id))
+            .select(tests::all_columns)
+            .order(Tests::name)
+            .load(self.connection())
+    }
+
+    fn find_users(&self, policy: &EscalationPolicy) -> QueryResult<Vec<User>> {
+        EscalationPolicyUsers::escalation_policy_users
+            .inner_join(Users::users)
+            .filter(EscalationPolicyUsers::escalation_policy_id.eq(policy.id))
+            .select(users::all_columns)
+            .order(Users::id)
+            .load(self.connection())
+    }
+
+    fn set_tests(&self, policy: &EscalationPolicy, tests: &[Test]) -> QueryResult<()> {
+        let test_ids = tests.iter().map(|t| t.id).collect::<Vec<_>>();
+        diesel::delete(
+            EscalationPolicyTests::escalation_policy_tests.filter(
+                EscalationPolicyTests::escalation_policy_id
+                    .eq(policy.id)
+                    .or(EscalationPolicyTests::test_id.eq_any(&test_ids)),
+            ),
+        )
+        .execute(self.connection())?;
+        let links = test_ids
+            .into_iter()
+            .map(|test_id| EscalationPolicyTest {
+                escalation_policy_id: policy.id,
+                test_id,
+            })
+            .collect::<Vec<_>>();
+        diesel::insert_into(EscalationPolicyTests::escalation_policy_tests)
+            .values(&links)
+            .execute(self.connection())?;
+        Ok(())
+    }
+
+    fn set_users(&self, policy: &EscalationPolicy, users: &[User]) -> QueryResult<()> {
+        diesel::delete(
+            EscalationPolicyUsers::escalation_policy_users
+                .filter(EscalationPolicyUsers::escalation_policy_id.eq(policy.id)),
+        )
+        .execute(self.connection())?;
+        let links = users
+            .iter()
+            .map(|user| EscalationPolicyUser {
+                escalation_policy_id: policy.id,
+                user_id: user.id,
+            })
+            .collect::<Vec<_>>();
+        diesel::insert_into(EscalationPolicyUsers::escalation_policy_users)
+            .values(&links)
+            .execute(self.connection())?;
+        Ok(())
+    }
+}
@@ -0,0 +1,92 @@
+use crate::database::{Connection, Incident};
+use crate::schema::incident_events::dsl as IncidentEvents;
+use crate::schema::*;
+use chrono::{DateTime, Utc};
+use diesel::prelude::*;
+use diesel_repository::{implement_crud_repository, CrudRepository};
+
+/// An entry in the timeline of an incident.
+#[derive(Queryable, Debug, Identifiable, Insertable, AsChangeset)]
+#[changeset_options(treat_none_as_null = "true")]
+pub struct IncidentEvent {
+    pub id: i32,
+    pub incident_id: i32,
+    pub time: DateTime<Utc>,
+    pub kind: String,
+    /// The user that caused the event, if it wasn't caused by the runner.
+    pub user_id: Option<i32>,
+    pub message: Option<String>,
+}
+
+#[derive(Queryable, Debug, Insertable, AsChangeset)]
+#[table_name = "incident_events"]
+pub struct NewIncidentEvent {
+    pub incident_id: i32,
+    pub time: DateTime<Utc>,
+    pub kind: String,
+    pub user_id: Option<i32>,
+    pub message: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IncidentEventKind {
+    /// The test started failing.
+    Opened,
+    /// A notification was sent (or failed to send) via a notifier.
+    Notified,
+    /// The incident was escalated according to the test's escalation policy.
+    Escalated,
+    Acknowledged,
+    Note,
+    /// A user manually ended the incident.
+    Resolved,
+    /// The test is passing again.
+    Ended,
+}
+
+impl IncidentEventKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            IncidentEventKind::Opened => "opened",
+            IncidentEventKind::Notified => "notified",
+            IncidentEventKind::Escalated => "escalated",
+            IncidentEventKind::Acknowledged => "acknowledged",
+            IncidentEventKind::Note => "note",
+            IncidentEventKind::Resolved => "resolved",
+            IncidentEventKind::Ended => "ended",
+        }
+    }
+}
+
+impl NewIncidentEvent {
+    pub fn new(
+        incident: &Incident,
+        kind: IncidentEventKind,
+        user_id: Option<i32>,
+        message: Option<String>,
+    ) -> Self {
+        Self {
+            incident_id: incident.id,
+            time: Utc::now(),
+            kind: kind.as_str().to_string(),
+            user_id,
+            message,
+        }
+    }
+}
+
+implement_crud_repository!(IncidentEventRepositoryImpl, IncidentEvent, i32, Connection);
+
+pub trait IncidentEventRepository: CrudRepository<IncidentEvent, i32> {
+    /// Finds the events of an incident in the order they occurred.
+    fn find_belonging_to(&self, incident: &Incident) -> QueryResult<Vec<IncidentEvent>>;
+}
+
+impl IncidentEventRepository for IncidentEventRepositoryImpl<'_> {
+    fn find_belonging_to(&self, incident: &Incident) -> QueryResult<Vec<IncidentEvent>> {
+        IncidentEvents::incident_events
+            .filter(IncidentEvents::incident_id.eq(incident.id))
+            .order(IncidentEvents::id)
+            .load(self.connection())
+    }
+}
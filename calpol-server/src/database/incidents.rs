@@ -0,0 +1,129 @@
+use crate::database::{Connection, Test};
+use crate::schema::incidents::dsl as Incidents;
+use crate::schema::tests::dsl as Tests;
+use crate::schema::*;
+use chrono::{DateTime, Utc};
+use diesel::dsl::sql;
+use diesel::prelude::*;
+use diesel::sql_types::{Double, Nullable};
+use diesel_postgres::limit::{CountedLimitDsl, CountedLimitResult};
+use diesel_repository::{implement_crud_repository, CrudRepository};
+
+/// A period of time during which a test was failing.
+#[derive(Queryable, Debug, Identifiable, Insertable, AsChangeset)]
+#[changeset_options(treat_none_as_null = "true")]
+pub struct Incident {
+    pub id: i32,
+    pub test_id: i32,
+    pub started: DateTime<Utc>,
+    /// Set once the test is passing again.
+    pub ended: Option<DateTime<Utc>>,
+    /// When users were last notified about the incident (including reminders).
+    pub last_notified: DateTime<Utc>,
+    /// When the incident was escalated according to the test's escalation policy.
+    pub escalated: Option<DateTime<Utc>>,
+    /// Set once a user has acknowledged the incident, stopping reminders and escalation.
+    pub acknowledged_at: Option<DateTime<Utc>>,
+    pub acknowledged_by: Option<i32>,
+    /// Why the test was failing when the incident started.
+    pub failure_reason: Option<String>,
+}
+
+#[derive(Queryable, Debug, Insertable, AsChangeset)]
+#[table_name = "incidents"]
+pub struct NewIncident {
+    pub test_id: i32,
+    pub started: DateTime<Utc>,
+    pub last_notified: DateTime<Utc>,
+    pub failure_reason: Option<String>,
+}
+
+implement_crud_repository!(IncidentRepositoryImpl, Incident, i32, Connection);
+
+pub trait IncidentRepository: CrudRepository<Incident, i32> {
+    /// Finds all incidents that haven't yet ended, along with their test.
+    fn find_ongoing(&self) -> QueryResult<Vec<(Incident, Test)>>;
+    fn find_ongoing_by_test(&self, test: &Test) -> QueryResult<Option<Incident>>;
+    fn find_by_id_with_test(&self, id: i32) -> QueryResult<Option<(Incident, Test)>>;
+    /// Finds all incidents, most recent first.
+    fn find_all(
+        &self,
+        limit: u32,
+        offset: u32,
+    ) -> QueryResult<CountedLimitResult<(Incident, Test)>>;
+    /// Finds all incidents of a test, most recent first.
+    fn find_all_by_test(
+        &self,
+        test: &Test,
+        limit: u32,
+        offset: u32,
+    ) -> QueryResult<CountedLimitResult<(Incident, Test)>>;
+    /// Mean duration in seconds of incidents that have ended, optionally only for a single test.
+    fn mean_time_to_resolve(&self, test: Option<&Test>) -> QueryResult<Option<f64>>;
+}
+
+impl IncidentRepository for IncidentRepositoryImpl<'_> {
+    fn find_ongoing(&self) -> QueryResult<Vec<(Incident, Test)>> {
+        Incidents::incidents
+            .inner_join(Tests::tests)
+            .filter(Incidents::ended.is_null())
+            .order(Incidents::started)
+            .load(self.connection())
+    }
+
+    fn find_ongoing_by_test(&self, test: &Test) -> QueryResult<Option<Incident>> {
+        Incidents::incidents
+            .filter(Incidents::test_id.eq(test.id))
+            .filter(Incidents::ended.is_null())
+            .first(self.connection())
+            .optional()
+    }
+
+    fn find_by_id_with_test(&self, id: i32) -> QueryResult<Option<(Incident, Test)>> {
+        Incidents::incidents
+            .inner_join(Tests::tests)
+            .filter(Incidents::id.eq(id))
+            .first(self.connection())
+            .optional()
+    }
+
+    fn find_all(
+        &self,
+        limit: u32,
+        offset: u32,
+    ) -> QueryResult<CountedLimitResult<(Incident, Test)>> {
+        Incidents::incidents
+            .inner_join(Tests::tests)
+            .order(Incidents::id.desc())
+            .counted_limit(limit)
+            .offset(offset)
+            .load_with_total::<(Incident, Test)>(self.connection())
+    }
+
+    fn find_all_by_test(
+        &self,
+        test: &Test,
+        limit: u32,
+        offset: u32,
+    ) -> QueryResult<CountedLimitResult<(Incident, Test)>> {
+        Incidents::incidents
+            .inner_join(Tests::tests)
+            .filter(Incidents::test_id.eq(test.id))
+            .order(Incidents::id.desc())
+            .counted_limit(limit)
+            .offset(offset)
+            .load_with_total::<(Incident, Test)>(self.connection())
+    }
+
+    fn mean_time_to_resolve(&self, test: Option<&Test>) -> QueryResult<Option<f64>> {
+        let mean = sql::<Nullable<Double>>("EXTRACT(EPOCH FROM AVG(ended - started))::FLOAT8");
+        let ended = Incidents::incidents.filter(Incidents::ended.is_not_null());
+        match test {
+            Some(test) => ended
+                .filter(Incidents::test_id.eq(test.id))
+                .select(mean)
+                .first(self.connection()),
+            None => ended.select(mean).first(self.connection()),
+        }
+    }
+}
@@ -0,0 +1,85 @@
+use crate::database::{Connection, Test};
+use crate::schema::maintenance_window_tests::dsl as MaintenanceWindowTests;
+use crate::schema::maintenance_windows::dsl as MaintenanceWindows;
+use crate::schema::tests::dsl as Tests;
+use crate::schema::*;
+use chrono::{DateTime, Utc};
+use diesel::prelude::*;
+use diesel_repository::{implement_crud_repository, CrudRepository};
+
+#[derive(Queryable, Debug, Identifiable, Insertable, AsChangeset)]
+pub struct MaintenanceWindow {
+    pub id: i32,
+    pub name: String,
+    pub start_time: DateTime<Utc>,
+    pub end_time: DateTime<Utc>,
+    pub all_tests: bool,
+}
+
+#[derive(Queryable, Debug, Insertable, AsChangeset)]
+#[table_name = "maintenance_windows"]
+pub struct NewMaintenanceWindow {
+    pub name: String,
+    pub start_time: DateTime<Utc>,
+    pub end_time: DateTime<Utc>,
+    pub all_tests: bool,
+}
+
+#[derive(Queryable, Debug, Insertable)]
+#[table_name = "maintenance_window_tests"]
+pub struct MaintenanceWindowTest {
+    pub maintenance_window_id: i32,
+    pub test_id: i32,
+}
+
+implement_crud_repository!(
+    MaintenanceWindowRepositoryImpl,
+    MaintenanceWindow,
+    i32,
+    Connection
+);
+
+pub trait MaintenanceWindowRepository: CrudRepository<MaintenanceWindow, i32> {
+    fn find_active(&self, time: DateTime<Utc>) -> QueryResult<Vec<MaintenanceWindow>>;
+    fn find_tests(&self, window: &MaintenanceWindow) -> QueryResult<Vec<Test>>;
+    fn insert_tests(&self, window: &MaintenanceWindow, tests: &[Test]) -> QueryResult<usize>;
+    fn delete_all_ended_before(&self, age: DateTime<Utc>) -> QueryResult<usize>;
+}
+
+impl MaintenanceWindowRepository for MaintenanceWindowRepositoryImpl<'_> {
+    fn find_active(&self, time: DateTime<Utc>) -> QueryResult<Vec<MaintenanceWindow>> {
+        MaintenanceWindows::maintenance_windows
+            .filter(MaintenanceWindows::start_time.le(time))
+            .filter(MaintenanceWindows::end_time.gt(time))
+            .load(self.connection())
+    }
+
+    fn find_tests(&self, window: &MaintenanceWindow) -> QueryResult<Vec<Test>> {
+        MaintenanceWindowTests::maintenance_window_tests
+            .inner_join(Tests::tests)
+            .filter(MaintenanceWindowTests::maintenance_window_id.eq(window.id))
+            .select(tests::all_columns)
+            .order(Tests::name)
+            .load(self.connection())
+    }
+
+    fn insert_tests(&self, window: &MaintenanceWindow, tests: &[Test]) -> QueryResult<usize> {
+        let links = tests
+            .iter()
+            .map(|test| MaintenanceWindowTest {
+                maintenance_window_id: window.id,
+                test_id: test.id,
+            })
+            .collect::<Vec<_>>();
+        diesel::insert_into(MaintenanceWindowTests::maintenance_window_tests)
+            .values(&links)
+            .execute(self.connection())
+    }
+
+    fn delete_all_ended_before(&self, age: DateTime<Utc>) -> QueryResult<usize> {
+        diesel::delete(
+            MaintenanceWindows::maintenance_windows.filter(MaintenanceWindows::end_time.lt(age)),
+        )
+        .execute(self.connection())
+    }
+}
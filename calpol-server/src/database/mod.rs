@@ -1,16 +1,32 @@
 use diesel::r2d2::{ConnectionManager, PooledConnection};
 use diesel::PgConnection;
 
+mod api_keys;
+mod deferred_sms;
+mod escalation_policies;
+mod incident_events;
+mod incidents;
+mod maintenance_windows;
+mod plaintext_tokens;
 mod runner_logs;
 mod sessions;
 mod test_results;
 mod tests;
+mod user_subscriptions;
 mod users;
 
+pub use api_keys::*;
+pub use deferred_sms::*;
+pub use escalation_policies::*;
+pub use incident_events::*;
+pub use incidents::*;
+pub use maintenance_windows::*;
+pub use plaintext_tokens::*;
 pub use runner_logs::*;
 pub use sessions::*;
 pub use test_results::*;
 pub use tests::*;
+pub use user_subscriptions::*;
 pub use users::*;
 
 pub type Connection = PooledConnection<ConnectionManager<PgConnection>>;
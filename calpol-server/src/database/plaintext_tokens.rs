@@ -0,0 +1,68 @@
+use diesel::prelude::*;
+use diesel::sql_query;
+use diesel::sql_types::{Bool, Int4, Text};
+use diesel::PgConnection;
+
+/// Columns that stored tokens in plaintext, until they are renamed by the `hash_tokens` migration.
+const PLAINTEXT_TOKEN_COLUMNS: [(&str, &str); 3] = [
+    ("sessions", "token"),
+    ("api_keys", "token"),
+    ("users", "password_reset_token"),
+];
+
+#[derive(QueryableByName)]
+struct ColumnExists {
+    #[sql_type = "Bool"]
+    found: bool,
+}
+
+#[derive(QueryableByName)]
+struct PlaintextToken {
+    #[sql_type = "Int4"]
+    id: i32,
+    #[sql_type = "Text"]
+    token: String,
+}
+
+/// Replaces the tokens stored in plaintext with their keyed hash, so that existing sessions,
+/// API keys and password reset tokens keep working once the `hash_tokens` migration renames the
+/// columns. This does nothing once that migration has been run.
+///
+/// The hash can't be computed in SQL without the `token_key`, so this must be run before the
+/// migrations, and in the same transaction so that the tokens can't be hashed twice.
+/// Returns the number of tokens that were hashed.
+pub fn hash_plaintext_tokens(
+    connection: &PgConnection,
+    hash: impl Fn(&str) -> String,
+) -> QueryResult<usize> {
+    let mut hashed = 0;
+    for (table, column) in PLAINTEXT_TOKEN_COLUMNS {
+        let exists = sql_query(
+            "SELECT EXISTS(SELECT 1 FROM information_schema.columns \
+            WHERE table_schema = current_schema() AND table_name = $1 AND column_name = $2) AS found",
+        )
+        .bind::<Text, _>(table)
+        .bind::<Text, _>(column)
+        .get_result::<ColumnExists>(connection)?;
+        if !exists.found {
+            continue;
+        }
+        let tokens = sql_query(format!(
+            "SELECT id, {column} AS token FROM {table} WHERE {column} IS NOT NULL",
+            table = table,
+            column = column
+        ))
+        .load::<PlaintextToken>(connection)?;
+        for token in &tokens {
+            sql_query(format!(
+                "UPDATE {} SET {} = $1 WHERE id = $2",
+                table, column
+            ))
+            .bind::<Text, _>(hash(&token.token))
+            .bind::<Int4, _>(token.id)
+            .execute(connection)?;
+        }
+        hashed += tokens.len();
+    }
+    Ok(hashed)
+}
@@ -13,7 +13,8 @@ use diesel_repository::{implement_crud_repository, CrudRepository};
 pub struct Session {
     pub id: i32,
     pub user_id: i32,
-    pub token: String,
+    /// Keyed hash of the session token
+    pub token_hash: String,
     pub created: DateTime<Utc>,
     pub last_used: DateTime<Utc>,
     pub last_ip: Vec<u8>,
@@ -24,7 +25,7 @@ pub struct Session {
 #[table_name = "sessions"]
 pub struct NewSession {
     pub user_id: i32,
-    pub token: String,
+    pub token_hash: String,
     pub last_ip: Vec<u8>,
     pub user_agent: String,
 }
@@ -32,14 +33,7 @@ pub struct NewSession {
 implement_crud_repository!(SessionRepositoryImpl, Session, i32, Connection);
 
 pub trait SessionRepository: CrudRepository<Session, i32> {
-    fn find_belonging_to_user_by_ip_and_agent(
-        &self,
-        user: &User,
-        last_ip: &[u8],
-        user_agent: &str,
-    ) -> QueryResult<Option<Session>>;
-
-    fn find_by_token(&self, token: &str) -> QueryResult<Option<(Session, User)>>;
+    fn find_by_token_hash(&self, token_hash: &str) -> QueryResult<Option<(Session, User)>>;
 
     fn find_all_belonging_to_user(&self, user: &User) -> QueryResult<Vec<Session>>;
 
@@ -49,22 +43,9 @@ pub trait SessionRepository: CrudRepository<Session, i32> {
 }
 
 impl SessionRepository for SessionRepositoryImpl<'_> {
-    fn find_belonging_to_user_by_ip_and_agent(
-        &self,
-        user: &User,
-        last_ip: &[u8],
-        user_agent: &str,
-    ) -> QueryResult<Option<Session>> {
-        Session::belonging_to(user)
-            .filter(Sessions::last_ip.eq(last_ip))
-            .filter(Sessions::user_agent.eq(user_agent))
-            .first::<Session>(self.connection())
-            .optional()
-    }
-
-    fn find_by_token(&self, token: &str) -> QueryResult<Option<(Session, User)>> {
+    fn find_by_token_hash(&self, token_hash: &str) -> QueryResult<Option<(Session, User)>> {
         Sessions::sessions
-            .filter(Sessions::token.eq(token))
+            .filter(Sessions::token_hash.eq(token_hash))
             .inner_join(Users::users)
             .first::<(Session, User)>(self.connection())
             .optional()
@@ -13,6 +13,11 @@ pub struct TestResult {
     pub failure_reason: Option<String>,
     pub time_started: DateTime<Utc>,
     pub time_finished: DateTime<Utc>,
+    pub dns_ms: Option<i32>,
+    pub connect_ms: Option<i32>,
+    pub tls_ms: Option<i32>,
+    pub first_byte_ms: Option<i32>,
+    pub total_ms: Option<i32>,
 }
 
 #[derive(Queryable, Debug, Insertable, AsChangeset)]
@@ -23,6 +28,11 @@ pub struct NewTestResult {
     pub failure_reason: Option<String>,
     pub time_started: DateTime<Utc>,
     pub time_finished: DateTime<Utc>,
+    pub dns_ms: Option<i32>,
+    pub connect_ms: Option<i32>,
+    pub tls_ms: Option<i32>,
+    pub first_byte_ms: Option<i32>,
+    pub total_ms: Option<i32>,
 }
 
 implement_crud_repository!(TestResultRepositoryImpl, TestResult, i32, Connection);
@@ -4,7 +4,7 @@ use crate::schema::*;
 use diesel::prelude::*;
 use diesel_repository::{implement_crud_repository, CrudRepository};
 
-#[derive(Queryable, Debug, Identifiable, Insertable, AsChangeset)]
+#[derive(Queryable, Debug, Clone, Identifiable, Insertable, AsChangeset)]
 pub struct Test {
     pub id: i32,
     pub name: String,
@@ -12,6 +12,11 @@ pub struct Test {
     pub config: serde_json::Value,
     pub failing: bool,
     pub failure_threshold: i32,
+    pub run_interval: Option<i32>,
+    pub run_timeout: Option<i32>,
+    pub tags: Vec<String>,
+    /// One of `low`, `medium`, `high` or `critical`.
+    pub severity: String,
 }
 
 #[derive(Queryable, Debug, Insertable, AsChangeset)]
@@ -22,12 +27,17 @@ pub struct NewTest {
     pub config: serde_json::Value,
     pub failing: bool,
     pub failure_threshold: i32,
+    pub run_interval: Option<i32>,
+    pub run_timeout: Option<i32>,
+    pub tags: Vec<String>,
+    pub severity: String,
 }
 
 implement_crud_repository!(TestRepositoryImpl, Test, i32, Connection);
 
 pub trait TestRepository: CrudRepository<Test, i32> {
     fn find_by_name(&self, name: &str) -> QueryResult<Option<Test>>;
+    fn find_by_names(&self, names: &[String]) -> QueryResult<Vec<Test>>;
 }
 
 impl TestRepository for TestRepositoryImpl<'_> {
@@ -37,4 +47,10 @@ impl TestRepository for TestRepositoryImpl<'_> {
             .first::<_>(self.connection())
             .optional()
     }
+
+    fn find_by_names(&self, names: &[String]) -> QueryResult<Vec<Test>> {
+        Tests::tests
+            .filter(Tests::name.eq_any(names))
+            .load(self.connection())
+    }
 }
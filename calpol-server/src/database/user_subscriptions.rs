@@ -0,0 +1,79 @@
+use crate::database::{Connection, Test, User};
+use crate::schema::tests::dsl as Tests;
+use crate::schema::user_subscriptions::dsl as UserSubscriptions;
+use crate::schema::*;
+use diesel::prelude::*;
+use diesel_repository::{implement_crud_repository, CrudRepository};
+
+/// Subscribes a user to (or excludes them from) either a single test, or all tests with a tag.
+#[derive(Queryable, Debug, Identifiable, Insertable, AsChangeset)]
+#[changeset_options(treat_none_as_null = "true")]
+pub struct UserSubscription {
+    pub id: i32,
+    pub user_id: i32,
+    pub test_id: Option<i32>,
+    pub tag: Option<String>,
+    pub excluded: bool,
+}
+
+impl UserSubscription {
+    pub fn matches(&self, test: &Test) -> bool {
+        match (&self.test_id, &self.tag) {
+            (Some(test_id), _) => *test_id == test.id,
+            (None, Some(tag)) => test.tags.contains(tag),
+            (None, None) => false,
+        }
+    }
+}
+
+#[derive(Queryable, Debug, Insertable, AsChangeset)]
+#[table_name = "user_subscriptions"]
+pub struct NewUserSubscription {
+    pub user_id: i32,
+    pub test_id: Option<i32>,
+    pub tag: Option<String>,
+    pub excluded: bool,
+}
+
+implement_crud_repository!(
+    UserSubscriptionRepositoryImpl,
+    UserSubscription,
+    i32,
+    Connection
+);
+
+pub trait UserSubscriptionRepository: CrudRepository<UserSubscription, i32> {
+    /// Finds the subscriptions of a user, along with the test if the subscription is for a test.
+    fn find_belonging_to(&self, user: &User) -> QueryResult<Vec<(UserSubscription, Option<Test>)>>;
+    /// Replaces all the subscriptions of a user.
+    fn set_subscriptions(
+        &self,
+        user: &User,
+        subscriptions: &[NewUserSubscription],
+    ) -> QueryResult<()>;
+}
+
+impl UserSubscriptionRepository for UserSubscriptionRepositoryImpl<'_> {
+    fn find_belonging_to(&self, user: &User) -> QueryResult<Vec<(UserSubscription, Option<Test>)>> {
+        UserSubscriptions::user_subscriptions
+            .left_join(Tests::tests)
+            .filter(UserSubscriptions::user_id.eq(user.id))
+            .order(UserSubscriptions::id)
+            .load(self.connection())
+    }
+
+    fn set_subscriptions(
+        &self,
+        user: &User,
+        subscriptions: &[NewUserSubscription],
+    ) -> QueryResult<()> {
+        diesel::delete(
+            UserSubscriptions::user_subscriptions.filter(UserSubscriptions::user_id.eq(user.id)),
+        )
+        .execute(self.connection())?;
+        diesel::insert_into(UserSubscriptions::user_subscriptions)
+            .values(subscriptions)
+            .execute(self.connection())?;
+        Ok(())
+    }
+}
@@ -1,7 +1,7 @@
 use crate::database::Connection;
 use crate::schema::users;
 use crate::schema::users::dsl as Users;
-use chrono::{DateTime, Utc};
+use chrono::{DateTime, NaiveTime, Utc};
 use diesel::prelude::*;
 use diesel_postgres::functions::{lower, strpos};
 use diesel_postgres::limit::{CountedLimitDsl, CountedLimitResult};
@@ -16,11 +16,24 @@ pub struct User {
     pub name: String,
     pub email: String,
     pub password_hash: Option<String>,
-    pub password_reset_token: Option<String>,
+    /// Keyed hash of the password reset token
+    pub password_reset_token_hash: Option<String>,
     pub password_reset_token_creation: Option<DateTime<Utc>>,
     pub phone_number: Option<String>,
     pub sms_notifications: bool,
     pub email_notifications: bool,
+    /// Notify about all tests, other than those excluded by a subscription.
+    pub subscribe_all_tests: bool,
+    pub email_min_severity: String,
+    pub sms_min_severity: String,
+    /// IANA timezone that the quiet hours are in.
+    pub timezone: String,
+    pub quiet_hours_start: Option<NaiveTime>,
+    pub quiet_hours_end: Option<NaiveTime>,
+    /// Whether SMS during quiet hours are deferred, or sent by email instead.
+    pub quiet_hours_sms: String,
+    /// One of `viewer`, `operator` or `admin`.
+    pub role: String,
 }
 
 impl User {
@@ -37,8 +50,9 @@ pub struct NewUser {
     pub password_hash: Option<String>,
     pub sms_notifications: bool,
     pub email_notifications: bool,
-    pub password_reset_token: Option<String>,
+    pub password_reset_token_hash: Option<String>,
     pub password_reset_token_creation: Option<DateTime<Utc>>,
+    pub role: String,
 }
 
 implement_crud_repository!(UserRepositoryImpl, User, i32, Connection);
@@ -52,7 +66,7 @@ pub trait UserRepository: CrudRepository<User, i32> {
         offset: u32,
         search: &str,
     ) -> QueryResult<CountedLimitResult<User>>;
-    fn find_by_reset_token(&self, reset_token: &str) -> QueryResult<Option<User>>;
+    fn find_by_reset_token_hash(&self, reset_token_hash: &str) -> QueryResult<Option<User>>;
 }
 
 impl UserRepository for UserRepositoryImpl<'_> {
@@ -87,9 +101,9 @@ impl UserRepository for UserRepositoryImpl<'_> {
             .load_with_total::<User>(self.connection())
     }
 
-    fn find_by_reset_token(&self, reset_token: &str) -> QueryResult<Option<User>> {
+    fn find_by_reset_token_hash(&self, reset_token_hash: &str) -> QueryResult<Option<User>> {
         Users::users
-            .filter(Users::password_reset_token.eq(reset_token))
+            .filter(Users::password_reset_token_hash.eq(reset_token_hash))
             .first::<User>(self.connection())
             .optional()
     }
@@ -7,10 +7,13 @@ extern crate diesel;
 mod api;
 mod database;
 mod messagebird;
+mod notifier;
 mod schema;
 mod settings;
+mod sms;
 mod state;
 mod test_runner;
+mod twilio;
 
 use crate::database::{Connection, NewUser, UserRepositoryImpl};
 use crate::settings::Settings;
@@ -18,7 +21,7 @@ use actix_extensible_rate_limit::backend::memory::InMemoryBackend;
 use actix_web::web::Data;
 use actix_web::{middleware, App, HttpServer};
 use anyhow::Context;
-use calpol_model::api_v1::UserSummary;
+use calpol_model::api_v1::{Role, UserSummary};
 use clap::{Parser, Subcommand};
 use diesel::r2d2::ConnectionManager;
 use diesel::{r2d2, PgConnection};
@@ -51,6 +54,9 @@ struct CreateUser {
     email: String,
     #[clap(long)]
     password: String,
+    /// Role of the user (viewer, operator or admin)
+    #[clap(long, default_value = "admin")]
+    role: Role,
 }
 
 #[actix_web::main]
@@ -64,7 +70,7 @@ async fn main() -> anyhow::Result<()> {
 
     let manager = ConnectionManager::<PgConnection>::new(&settings.database_url);
     let pool = r2d2::Pool::builder().build(manager)?;
-    embedded_migrations::run_with_output(&pool.get().unwrap(), &mut std::io::stdout())?;
+    run_migrations(&pool.get().unwrap(), &settings)?;
 
     match opts.subcommand {
         SubCommand::Server => {
@@ -90,6 +96,22 @@ async fn main() -> anyhow::Result<()> {
     Ok(())
 }
 
+/// Runs the pending migrations, first hashing any tokens that are still stored in plaintext.
+fn run_migrations(connection: &Connection, settings: &Settings) -> anyhow::Result<()> {
+    connection
+        .build_transaction()
+        .run(|| -> anyhow::Result<()> {
+            let hashed = database::hash_plaintext_tokens(connection, |token| {
+                api::auth::hash_token(settings, token)
+            })?;
+            if hashed > 0 {
+                log::info!("Hashed {} existing tokens", hashed);
+            }
+            embedded_migrations::run_with_output(connection, &mut std::io::stdout())?;
+            Ok(())
+        })
+}
+
 fn create_user(connection: Connection, user: CreateUser) -> anyhow::Result<()> {
     let user_repository = UserRepositoryImpl::new(&connection);
     user_repository
@@ -99,8 +121,9 @@ fn create_user(connection: Connection, user: CreateUser) -> anyhow::Result<()> {
             password_hash: Some(bcrypt::hash(user.password, bcrypt::DEFAULT_COST)?),
             sms_notifications: false,
             email_notifications: false,
-            password_reset_token: None,
+            password_reset_token_hash: None,
             password_reset_token_creation: None,
+            role: user.role.as_str().to_string(),
         })
         .map_err(|e| e.into())
         .map(|u| {
@@ -0,0 +1,129 @@
+use crate::notifier::{Notification, NotificationTargets, Notifier, Templates, PASSING_TITLE};
+use crate::settings::ChatSetting;
+use anyhow::{bail, Context};
+use async_trait::async_trait;
+use reqwest::{Client, RequestBuilder};
+use serde_json::json;
+use std::sync::Arc;
+use std::time::Duration;
+
+const TIMEOUT_SEC: u64 = 5;
+const MAX_DISCORD_CHARS: usize = 2000;
+
+/// Posts messages to a chat channel.
+pub struct ChatNotifier {
+    inner: Client,
+    setting: ChatSetting,
+    templates: Arc<Templates>,
+}
+
+impl ChatNotifier {
+    pub fn new(setting: &ChatSetting, templates: Arc<Templates>) -> anyhow::Result<Self> {
+        Ok(Self {
+            inner: Client::builder()
+                .timeout(Duration::from_secs(TIMEOUT_SEC))
+                .build()?,
+            setting: setting.clone(),
+            templates,
+        })
+    }
+
+    fn build_request(&self, title: &str, body: &str, failing: bool) -> RequestBuilder {
+        let text = format!("{}\n\n{}", title, body.trim_end());
+        match &self.setting {
+            ChatSetting::Slack { webhook_url } => self
+                .inner
+                .post(webhook_url.clone())
+                .json(&json!({ "text": text })),
+            ChatSetting::Discord { webhook_url } => self
+                .inner
+                .post(webhook_url.clone())
+                .json(&json!({ "content": truncate(&text, MAX_DISCORD_CHARS) })),
+            ChatSetting::Matrix {
+                homeserver,
+                room_id,
+                access_token,
+            } => {
+                let mut url = homeserver.clone();
+                let transaction_id = format!("calpol-{}", rand::random::<u64>());
+                url.path_segments_mut()
+                    .unwrap()
+                    .pop_if_empty()
+                    .extend(&["_matrix", "client", "v3", "rooms", room_id])
+                    .extend(&["send", "m.room.message", &transaction_id]);
+                self.inner
+                    .put(url)
+                    .bearer_auth(access_token)
+                    .json(&json!({ "msgtype": "m.text", "body": text }))
+            }
+            // Teams renders the text as markdown, which requires blank lines between paragraphs
+            ChatSetting::Teams { webhook_url } => {
+                let paragraphs = body.lines().filter(|l| !l.is_empty()).collect::<Vec<_>>();
+                self.inner.post(webhook_url.clone()).json(&json!({
+                    "@type": "MessageCard",
+                    "@context": "http://schema.org/extensions",
+                    "summary": title,
+                    "title": title,
+                    "themeColor": if failing { "D32F2F" } else { "388E3C" },
+                    "text": paragraphs.join("\n\n"),
+                }))
+            }
+        }
+    }
+
+    async fn post(&self, title: &str, body: &str, failing: bool) -> anyhow::Result<()> {
+        let response = self
+            .build_request(title, body, failing)
+            .send()
+            .await
+            .with_context(|| format!("Failed to post {} message", self.name()))?;
+        if !response.status().is_success() {
+            bail!(
+                "{} returned unexpected response status: {}",
+                self.name(),
+                response.status().as_u16()
+            )
+        }
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl Notifier for ChatNotifier {
+    fn name(&self) -> &str {
+        match self.setting {
+            ChatSetting::Slack { .. } => "slack",
+            ChatSetting::Discord { .. } => "discord",
+            ChatSetting::Matrix { .. } => "matrix",
+            ChatSetting::Teams { .. } => "teams",
+        }
+    }
+
+    async fn notify(
+        &self,
+        notification: &Notification<'_>,
+        _: &NotificationTargets,
+    ) -> anyhow::Result<()> {
+        if !notification.now_failing.is_empty() {
+            let body = self.templates.render_failing(self.name(), notification)?;
+            self.post(notification.kind.failure_title(), &body, true)
+                .await?;
+        }
+        if !notification.now_passing.is_empty() {
+            let body = self.templates.render_passing(self.name(), notification)?;
+            self.post(PASSING_TITLE, &body, false).await?;
+        }
+        Ok(())
+    }
+}
+
+fn truncate(text: &str, max_chars: usize) -> String {
+    if text.chars().count() > max_chars {
+        format!(
+            "{}...",
+            text.chars().take(max_chars - 3).collect::<String>()
+        )
+    } else {
+        text.to_string()
+    }
+}
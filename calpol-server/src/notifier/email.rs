@@ -0,0 +1,92 @@
+use crate::notifier::{Notification, NotificationTargets, Notifier, Templates, PASSING_TITLE};
+use crate::settings::MailerSetting;
+use anyhow::bail;
+use async_trait::async_trait;
+use futures::future::join_all;
+use lettre::message::Mailbox;
+use lettre::{AsyncSmtpTransport, AsyncTransport, Message, Tokio1Executor};
+use std::sync::Arc;
+
+pub struct EmailNotifier {
+    mailer: AsyncSmtpTransport<Tokio1Executor>,
+    send_from: Mailbox,
+    reply_to: Mailbox,
+    templates: Arc<Templates>,
+}
+
+impl EmailNotifier {
+    pub fn new(
+        mailer: AsyncSmtpTransport<Tokio1Executor>,
+        setting: &MailerSetting,
+        templates: Arc<Templates>,
+    ) -> Self {
+        Self {
+            mailer,
+            send_from: setting.send_from.clone(),
+            reply_to: setting.reply_to().clone(),
+            templates,
+        }
+    }
+
+    /// Returns the number of emails that failed to send.
+    async fn send_emails(&self, emails: &[Mailbox], message: &str, subject: &str) -> usize {
+        let results = join_all(emails.iter().map(|email| {
+            let message = Message::builder()
+                .to(email.clone())
+                .from(self.send_from.clone())
+                .reply_to(self.reply_to.clone())
+                .subject(subject.to_string())
+                .body(message.to_string())
+                .unwrap();
+            async move { (self.mailer.send(message).await, email) }
+        }))
+        .await;
+        let mut failed = 0;
+        for (result, mailbox) in results {
+            if let Err(e) = result {
+                log::error!("Failed to send email to {} because {:#}", mailbox, e);
+                failed += 1;
+            } else {
+                log::info!("Sent email to {}", mailbox)
+            }
+        }
+        failed
+    }
+}
+
+#[async_trait]
+impl Notifier for EmailNotifier {
+    fn name(&self) -> &str {
+        "email"
+    }
+
+    async fn notify(
+        &self,
+        notification: &Notification<'_>,
+        targets: &NotificationTargets,
+    ) -> anyhow::Result<()> {
+        let mut failed = 0;
+        let mut sent = 0;
+        if !notification.now_failing.is_empty() {
+            let body = self.templates.render_failing(self.name(), notification)?;
+            let title = notification.kind.failure_title();
+            failed += self.send_emails(&targets.emails, &body, title).await;
+            sent += targets.emails.len();
+        }
+        if !notification.now_passing.is_empty() {
+            let body = self.templates.render_passing(self.name(), notification)?;
+            failed += self
+                .send_emails(&targets.emails, &body, PASSING_TITLE)
+                .await;
+            sent += targets.emails.len();
+        }
+        if failed > 0 {
+            bail!("Failed to send {} of {} emails", failed, sent)
+        }
+        Ok(())
+    }
+
+    fn notifies_users(&self) -> bool {
+        true
+    }
+}
@@ -0,0 +1,144 @@
+mod chat;
+mod email;
+mod opsgenie;
+mod pagerduty;
+mod sms;
+mod templates;
+mod webhook;
+
+use crate::database::Test;
+use crate::settings::Settings;
+use crate::sms::SmsProvider;
+use async_trait::async_trait;
+use chrono::Duration;
+use lettre::message::Mailbox;
+use lettre::{AsyncSmtpTransport, Tokio1Executor};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::Arc;
+
+pub use chat::ChatNotifier;
+pub use email::EmailNotifier;
+pub use opsgenie::OpsgenieNotifier;
+pub use pagerduty::PagerDutyNotifier;
+pub use sms::{create_sms_bodies, SmsNotifier};
+pub use templates::Templates;
+pub use webhook::WebhookNotifier;
+
+pub const FAILURE_TITLE: &str = "Calpol Test Failures";
+pub const PASSING_TITLE: &str = "Calpol Tests Passing";
+pub const REMINDER_TITLE: &str = "Calpol Tests Still Failing";
+pub const ESCALATION_TITLE: &str = "Calpol Test Failures Escalated";
+
+/// A channel that can be notified when tests change state.
+#[async_trait]
+pub trait Notifier: Send + Sync {
+    /// Name of the channel, used when reporting the result of the notification.
+    fn name(&self) -> &str;
+
+    /// Sends the notification, returns an error if it failed to be delivered to any recipient.
+    async fn notify(
+        &self,
+        notification: &Notification<'_>,
+        targets: &NotificationTargets,
+    ) -> anyhow::Result<()>;
+
+    /// Whether the channel delivers to the users in the targets, as opposed to a fixed
+    /// destination. These channels are used to notify the users of an escalation policy.
+    fn notifies_users(&self) -> bool {
+        false
+    }
+}
+
+/// Why a notification is being sent.
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum NotificationKind {
+    /// Tests have transitioned between passing and failing.
+    StateChange,
+    /// Tests are still failing after the repeat interval of their escalation policy.
+    Reminder,
+    /// Tests have been failing for longer than their escalation policy allows.
+    Escalation,
+}
+
+impl NotificationKind {
+    pub fn failure_title(self) -> &'static str {
+        match self {
+            NotificationKind::StateChange => FAILURE_TITLE,
+            NotificationKind::Reminder => REMINDER_TITLE,
+            NotificationKind::Escalation => ESCALATION_TITLE,
+        }
+    }
+
+    /// Describes the notification in incident timelines.
+    pub fn label(self) -> &'static str {
+        match self {
+            NotificationKind::StateChange => "notification",
+            NotificationKind::Reminder => "reminder",
+            NotificationKind::Escalation => "escalation",
+        }
+    }
+
+    /// Describes the failing tests, e.g. "3 tests failed".
+    pub fn failure_verb(self) -> &'static str {
+        match self {
+            NotificationKind::StateChange => "failed",
+            NotificationKind::Reminder | NotificationKind::Escalation => "still failing",
+        }
+    }
+}
+
+/// Tests that changed state in a test run, or that are still failing.
+pub struct Notification<'a> {
+    pub kind: NotificationKind,
+    /// Tests that were previously failing but have now transitioned to a passing state.
+    pub now_passing: &'a [Test],
+    /// Tests that were previously passing but have now transitioned into a failing state.
+    /// For reminders and escalations these are the tests that are still failing.
+    pub now_failing: &'a [(Test, anyhow::Error)],
+    /// How long each test has been failing for, by test ID, if known.
+    pub outages: &'a HashMap<i32, Duration>,
+}
+
+/// Users that have opted in to receiving notifications.
+#[derive(Default, Clone)]
+pub struct NotificationTargets {
+    pub emails: Vec<Mailbox>,
+    pub sms: Vec<String>,
+}
+
+/// Creates the notifiers that are enabled in the settings.
+pub fn build_notifiers(
+    settings: &Settings,
+    mailer: &AsyncSmtpTransport<Tokio1Executor>,
+    sms: Option<Arc<dyn SmsProvider>>,
+    templates: &Arc<Templates>,
+) -> anyhow::Result<Vec<Box<dyn Notifier>>> {
+    let mut notifiers: Vec<Box<dyn Notifier>> = vec![
+        Box::new(EmailNotifier::new(
+            mailer.clone(),
+            &settings.mailer,
+            templates.clone(),
+        )),
+        Box::new(SmsNotifier::new(sms, templates.clone())),
+    ];
+    for webhook in &settings.webhooks {
+        notifiers.push(Box::new(WebhookNotifier::new(webhook)?));
+    }
+    for chat in &settings.chats {
+        notifiers.push(Box::new(ChatNotifier::new(chat, templates.clone())?));
+    }
+    if let Some(pagerduty) = &settings.pagerduty {
+        notifiers.push(Box::new(PagerDutyNotifier::new(pagerduty)?));
+    }
+    if let Some(opsgenie) = &settings.opsgenie {
+        notifiers.push(Box::new(OpsgenieNotifier::new(opsgenie)?));
+    }
+    Ok(notifiers)
+}
+
+/// Identifies the alert for a test in paging systems, so that it can later be resolved.
+pub fn dedup_key(test: &Test) -> String {
+    format!("calpol-test-{}", test.id)
+}
@@ -0,0 +1,129 @@
+use crate::database::Test;
+use crate::notifier::{dedup_key, Notification, NotificationKind, NotificationTargets, Notifier};
+use crate::settings::OpsgenieSetting;
+use anyhow::{bail, Context};
+use async_trait::async_trait;
+use reqwest::header::AUTHORIZATION;
+use reqwest::{Client, RequestBuilder};
+use serde_json::json;
+use std::time::Duration;
+use url::Url;
+
+const TIMEOUT_SEC: u64 = 5;
+const MAX_MESSAGE_CHARS: usize = 130;
+
+/// Creates and closes alerts using the Opsgenie Alert API.
+pub struct OpsgenieNotifier {
+    inner: Client,
+    api_key: String,
+    url: Url,
+}
+
+impl OpsgenieNotifier {
+    pub fn new(setting: &OpsgenieSetting) -> anyhow::Result<Self> {
+        Ok(Self {
+            inner: Client::builder()
+                .timeout(Duration::from_secs(TIMEOUT_SEC))
+                .build()?,
+            api_key: setting.api_key.clone(),
+            url: setting.url.clone(),
+        })
+    }
+
+    fn alerts_url(&self, path: &[&str]) -> Url {
+        let mut url = self.url.clone();
+        url.path_segments_mut()
+            .unwrap()
+            .pop_if_empty()
+            .extend(&["v2", "alerts"])
+            .extend(path);
+        url
+    }
+
+    async fn send(&self, test: &Test, request: RequestBuilder) -> anyhow::Result<()> {
+        let response = request
+            .header(AUTHORIZATION, format!("GenieKey {}", self.api_key))
+            .send()
+            .await
+            .with_context(|| format!("Failed to send alert for test {}", test.name))?;
+        if !response.status().is_success() {
+            bail!(
+                "Alert for test {} returned unexpected response status: {}",
+                test.name,
+                response.status().as_u16()
+            )
+        }
+        Ok(())
+    }
+
+    async fn create_alert(
+        &self,
+        test: &Test,
+        error: &anyhow::Error,
+        kind: NotificationKind,
+    ) -> anyhow::Result<()> {
+        let message = format!("Calpol test {} {}", test.name, kind.failure_verb());
+        // Creating an alert with the same alias updates the open alert
+        let priority = match kind {
+            NotificationKind::Escalation => "P1",
+            _ => "P3",
+        };
+        let request = self.inner.post(self.alerts_url(&[])).json(&json!({
+            "message": message.chars().take(MAX_MESSAGE_CHARS).collect::<String>(),
+            "alias": dedup_key(test),
+            "description": format!("{:#}", error),
+            "entity": test.name,
+            "source": "calpol",
+            "priority": priority,
+        }));
+        self.send(test, request).await
+    }
+
+    async fn close_alert(&self, test: &Test) -> anyhow::Result<()> {
+        let alias = dedup_key(test);
+        let request = self
+            .inner
+            .post(self.alerts_url(&[&alias, "close"]))
+            .query(&[("identifierType", "alias")])
+            .json(&json!({
+                "source": "calpol",
+                "note": format!("Calpol test {} now passing", test.name),
+            }));
+        self.send(test, request).await
+    }
+}
+
+#[async_trait]
+impl Notifier for OpsgenieNotifier {
+    fn name(&self) -> &str {
+        "opsgenie"
+    }
+
+    async fn notify(
+        &self,
+        notification: &Notification<'_>,
+        _: &NotificationTargets,
+    ) -> anyhow::Result<()> {
+        // The alert remains open in Opsgenie, which handles its own reminders
+        if notification.kind == NotificationKind::Reminder {
+            return Ok(());
+        }
+        let mut failed = 0;
+        for (test, error) in notification.now_failing {
+            if let Err(e) = self.create_alert(test, error, notification.kind).await {
+                log::error!("{:#}", e);
+                failed += 1;
+            }
+        }
+        for test in notification.now_passing {
+            if let Err(e) = self.close_alert(test).await {
+                log::error!("{:#}", e);
+                failed += 1;
+            }
+        }
+        if failed > 0 {
+            bail!("Failed to send {} opsgenie alerts", failed)
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,131 @@
+use crate::database::Test;
+use crate::notifier::{dedup_key, Notification, NotificationKind, NotificationTargets, Notifier};
+use crate::settings::PagerDutySetting;
+use anyhow::{bail, Context};
+use async_trait::async_trait;
+use reqwest::Client;
+use serde::Serialize;
+use std::time::Duration;
+use url::Url;
+
+const TIMEOUT_SEC: u64 = 5;
+const MAX_SUMMARY_CHARS: usize = 1024;
+
+/// Triggers and resolves alerts using the PagerDuty Events API v2.
+pub struct PagerDutyNotifier {
+    inner: Client,
+    routing_key: String,
+    url: Url,
+}
+
+#[derive(Serialize)]
+struct Event<'a> {
+    routing_key: &'a str,
+    event_action: &'static str,
+    dedup_key: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    payload: Option<EventPayload<'a>>,
+}
+
+#[derive(Serialize)]
+struct EventPayload<'a> {
+    summary: String,
+    source: &'static str,
+    severity: &'static str,
+    component: &'a str,
+}
+
+impl PagerDutyNotifier {
+    pub fn new(setting: &PagerDutySetting) -> anyhow::Result<Self> {
+        Ok(Self {
+            inner: Client::builder()
+                .timeout(Duration::from_secs(TIMEOUT_SEC))
+                .build()?,
+            routing_key: setting.routing_key.clone(),
+            url: setting.url.clone(),
+        })
+    }
+
+    async fn send_event(&self, test: &Test, event: Event<'_>) -> anyhow::Result<()> {
+        let response = self
+            .inner
+            .post(self.url.clone())
+            .json(&event)
+            .send()
+            .await
+            .with_context(|| format!("Failed to send event for test {}", test.name))?;
+        if !response.status().is_success() {
+            bail!(
+                "Event for test {} returned unexpected response status: {}",
+                test.name,
+                response.status().as_u16()
+            )
+        }
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl Notifier for PagerDutyNotifier {
+    fn name(&self) -> &str {
+        "pagerduty"
+    }
+
+    async fn notify(
+        &self,
+        notification: &Notification<'_>,
+        _: &NotificationTargets,
+    ) -> anyhow::Result<()> {
+        // The alert remains open in PagerDuty, which handles its own reminders
+        if notification.kind == NotificationKind::Reminder {
+            return Ok(());
+        }
+        // Escalations re-trigger the existing alert at a higher severity
+        let severity = match notification.kind {
+            NotificationKind::Escalation => "critical",
+            _ => "error",
+        };
+        let mut failed = 0;
+        for (test, error) in notification.now_failing {
+            let event = Event {
+                routing_key: &self.routing_key,
+                event_action: "trigger",
+                dedup_key: dedup_key(test),
+                payload: Some(EventPayload {
+                    summary: format!(
+                        "Calpol test {} {}: {:#}",
+                        test.name,
+                        notification.kind.failure_verb(),
+                        error
+                    )
+                    .chars()
+                    .take(MAX_SUMMARY_CHARS)
+                    .collect(),
+                    source: "calpol",
+                    severity,
+                    component: &test.name,
+                }),
+            };
+            if let Err(e) = self.send_event(test, event).await {
+                log::error!("{:#}", e);
+                failed += 1;
+            }
+        }
+        for test in notification.now_passing {
+            let event = Event {
+                routing_key: &self.routing_key,
+                event_action: "resolve",
+                dedup_key: dedup_key(test),
+                payload: None,
+            };
+            if let Err(e) = self.send_event(test, event).await {
+                log::error!("{:#}", e);
+                failed += 1;
+            }
+        }
+        if failed > 0 {
+            bail!("Failed to send {} pagerduty events", failed)
+        }
+        Ok(())
+    }
+}
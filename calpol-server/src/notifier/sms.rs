@@ -0,0 +1,86 @@
+use crate::notifier::{Notification, NotificationTargets, Notifier, Templates};
+use crate::sms::SmsProvider;
+use anyhow::Context;
+use async_trait::async_trait;
+use std::sync::Arc;
+
+const MAX_SMS_CHARS: usize = 70;
+
+pub struct SmsNotifier {
+    provider: Option<Arc<dyn SmsProvider>>,
+    templates: Arc<Templates>,
+}
+
+impl SmsNotifier {
+    pub fn new(provider: Option<Arc<dyn SmsProvider>>, templates: Arc<Templates>) -> Self {
+        Self {
+            provider,
+            templates,
+        }
+    }
+
+    async fn send_sms(&self, phone_numbers: Vec<String>, message: String) -> anyhow::Result<()> {
+        match &self.provider {
+            Some(provider) => provider
+                .send_sms(&message, phone_numbers)
+                .await
+                .context("Failed sending SMS messages")?,
+            None => log::error!(
+                "Unable to send {} sms notifications because no SMS provider is configured",
+                phone_numbers.len()
+            ),
+        }
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl Notifier for SmsNotifier {
+    fn name(&self) -> &str {
+        "sms"
+    }
+
+    async fn notify(
+        &self,
+        notification: &Notification<'_>,
+        targets: &NotificationTargets,
+    ) -> anyhow::Result<()> {
+        if targets.sms.is_empty() {
+            return Ok(());
+        }
+        for body in create_sms_bodies(&self.templates, notification)? {
+            self.send_sms(targets.sms.clone(), body).await?;
+        }
+        Ok(())
+    }
+
+    fn notifies_users(&self) -> bool {
+        true
+    }
+}
+
+/// Creates the SMS messages to send for a notification, truncated to the maximum length.
+pub fn create_sms_bodies(
+    templates: &Templates,
+    notification: &Notification,
+) -> anyhow::Result<Vec<String>> {
+    let mut bodies = Vec::new();
+    if !notification.now_failing.is_empty() {
+        bodies.push(templates.render_failing("sms", notification)?);
+    }
+    if !notification.now_passing.is_empty() {
+        bodies.push(templates.render_passing("sms", notification)?);
+    }
+    Ok(bodies.into_iter().map(truncate).collect())
+}
+
+fn truncate(message: String) -> String {
+    if message.len() > MAX_SMS_CHARS {
+        format!(
+            "{}...",
+            message.chars().take(MAX_SMS_CHARS - 3).collect::<String>()
+        )
+    } else {
+        message
+    }
+}
@@ -0,0 +1,197 @@
+use crate::database::Test;
+use crate::notifier::{Notification, NotificationKind};
+use crate::settings::Settings;
+use anyhow::Context;
+use chrono::Duration;
+use handlebars::Handlebars;
+use serde::Serialize;
+use url::Url;
+
+/// Templates that apply to every channel, unless the channel has its own template.
+const DEFAULT_CHANNEL: &str = "default";
+const FAILING_EVENTS: [&str; 3] = ["failing", "reminder", "escalation"];
+const PASSING_EVENT: &str = "passing";
+
+const DEFAULT_FAILING: &str =
+    "Calpol: {{count}} tests {{verb}}\n\n{{#each tests}}{{name}}: {{failure_reason}}\n\n{{/each}}";
+const DEFAULT_PASSING: &str =
+    "Calpol: {{count}} tests now passing\n\n{{#each tests}}{{name}}\n{{/each}}";
+const SMS_FAILING: &str = "Calpol: {{#if (eq count 1)}}Test {{tests.0.name}} {{verb}}: \
+    {{tests.0.failure_reason}}{{else}}{{count}} tests {{verb}}, including: \
+    {{#each tests}}{{name}}{{#unless @last}}, {{/unless}}{{/each}}{{/if}}";
+const SMS_PASSING: &str = "Calpol: {{#if (eq count 1)}}Test {{tests.0.name}} now passing\
+    {{else}}{{count}} tests passing, including: \
+    {{#each tests}}{{name}}{{#unless @last}}, {{/unless}}{{/each}}{{/if}}";
+
+/// Renders the message bodies of notifications, for each channel and type of notification.
+pub struct Templates {
+    registry: Handlebars<'static>,
+    public_url: Option<Url>,
+}
+
+#[derive(Serialize)]
+struct TemplateData<'a> {
+    kind: NotificationKind,
+    /// Describes the tests, e.g. `failed`, `still failing` or `now passing`.
+    verb: &'a str,
+    count: usize,
+    tests: Vec<TestData<'a>>,
+    server_url: Option<&'a str>,
+}
+
+#[derive(Serialize)]
+struct TestData<'a> {
+    name: &'a str,
+    severity: &'a str,
+    tags: &'a [String],
+    failure_reason: Option<String>,
+    /// How long the test has been failing for, e.g. `1h 5m`.
+    outage: Option<String>,
+    outage_seconds: Option<i64>,
+    url: Option<String>,
+}
+
+impl Templates {
+    pub fn new(settings: &Settings) -> anyhow::Result<Self> {
+        let mut registry = Handlebars::new();
+        registry.register_escape_fn(handlebars::no_escape);
+        for event in FAILING_EVENTS {
+            registry.register_template_string(
+                &template_name(DEFAULT_CHANNEL, event),
+                DEFAULT_FAILING,
+            )?;
+            registry.register_template_string(&template_name("sms", event), SMS_FAILING)?;
+        }
+        registry.register_template_string(
+            &template_name(DEFAULT_CHANNEL, PASSING_EVENT),
+            DEFAULT_PASSING,
+        )?;
+        registry.register_template_string(&template_name("sms", PASSING_EVENT), SMS_PASSING)?;
+        for (channel, setting) in &settings.templates {
+            let templates = [
+                ("failing", &setting.failing),
+                ("reminder", &setting.reminder),
+                ("escalation", &setting.escalation),
+                (PASSING_EVENT, &setting.passing),
+            ];
+            for (event, template) in templates {
+                if let Some(template) = template {
+                    registry
+                        .register_template_string(&template_name(channel, event), template)
+                        .with_context(|| format!("Invalid {} template for {}", event, channel))?;
+                }
+            }
+        }
+        Ok(Self {
+            registry,
+            public_url: settings.public_url.clone(),
+        })
+    }
+
+    /// Renders the message about the failing tests of the notification, for the channel.
+    pub fn render_failing(
+        &self,
+        channel: &str,
+        notification: &Notification,
+    ) -> anyhow::Result<String> {
+        let tests = notification
+            .now_failing
+            .iter()
+            .map(|(test, error)| self.test_data(test, Some(format!("{:#}", error)), notification))
+            .collect();
+        let event = match notification.kind {
+            NotificationKind::StateChange => "failing",
+            NotificationKind::Reminder => "reminder",
+            NotificationKind::Escalation => "escalation",
+        };
+        self.render(
+            channel,
+            event,
+            notification,
+            notification.kind.failure_verb(),
+            tests,
+        )
+    }
+
+    /// Renders the message about the passing tests of the notification, for the channel.
+    pub fn render_passing(
+        &self,
+        channel: &str,
+        notification: &Notification,
+    ) -> anyhow::Result<String> {
+        let tests = notification
+            .now_passing
+            .iter()
+            .map(|test| self.test_data(test, None, notification))
+            .collect();
+        self.render(channel, PASSING_EVENT, notification, "now passing", tests)
+    }
+
+    fn render(
+        &self,
+        channel: &str,
+        event: &str,
+        notification: &Notification,
+        verb: &str,
+        tests: Vec<TestData>,
+    ) -> anyhow::Result<String> {
+        let mut name = template_name(channel, event);
+        if !self.registry.has_template(&name) {
+            name = template_name(DEFAULT_CHANNEL, event);
+        }
+        let data = TemplateData {
+            kind: notification.kind,
+            verb,
+            count: tests.len(),
+            tests,
+            server_url: self.public_url.as_ref().map(Url::as_str),
+        };
+        self.registry
+            .render(&name, &data)
+            .with_context(|| format!("Failed to render {} template", name))
+    }
+
+    fn test_data<'a>(
+        &self,
+        test: &'a Test,
+        failure_reason: Option<String>,
+        notification: &Notification,
+    ) -> TestData<'a> {
+        let outage = notification.outages.get(&test.id);
+        TestData {
+            name: &test.name,
+            severity: &test.severity,
+            tags: &test.tags,
+            failure_reason,
+            outage: outage.map(format_duration),
+            outage_seconds: outage.map(Duration::num_seconds),
+            url: self
+                .public_url
+                .as_ref()
+                .and_then(|url| url.join(&format!("api/v1/tests/{}", test.name)).ok())
+                .map(String::from),
+        }
+    }
+}
+
+fn template_name(channel: &str, event: &str) -> String {
+    format!("{}.{}", channel, event)
+}
+
+/// Formats a duration in days, hours and minutes, e.g. `1d 2h 5m`.
+fn format_duration(duration: &Duration) -> String {
+    if duration.num_minutes() < 1 {
+        return format!("{}s", duration.num_seconds().max(0));
+    }
+    let parts = [
+        (duration.num_days(), "d"),
+        (duration.num_hours() % 24, "h"),
+        (duration.num_minutes() % 60, "m"),
+    ];
+    parts
+        .iter()
+        .filter(|(value, _)| *value > 0)
+        .map(|(value, unit)| format!("{}{}", value, unit))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
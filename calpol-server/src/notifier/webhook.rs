@@ -0,0 +1,147 @@
+use crate::notifier::{Notification, NotificationKind, NotificationTargets, Notifier};
+use crate::settings::WebhookSetting;
+use anyhow::{bail, Context};
+use async_trait::async_trait;
+use chrono::Utc;
+use hmac::{Hmac, Mac};
+use reqwest::header::CONTENT_TYPE;
+use reqwest::Client;
+use serde::Serialize;
+use sha2::Sha256;
+use std::time::Duration;
+use url::Url;
+
+const TIMEOUT_SEC: u64 = 5;
+const SIGNATURE_HEADER: &str = "X-Calpol-Signature";
+const TIMESTAMP_HEADER: &str = "X-Calpol-Timestamp";
+
+pub struct WebhookNotifier {
+    inner: Client,
+    url: Url,
+    secret: Option<String>,
+}
+
+/// JSON body describing the tests that changed state in a test run, or are still failing.
+#[derive(Serialize, Debug)]
+struct WebhookPayload {
+    /// RFC3339 time the notification was created.
+    timestamp: String,
+    kind: NotificationKind,
+    now_failing: Vec<FailingTest>,
+    now_passing: Vec<PassingTest>,
+}
+
+#[derive(Serialize, Debug)]
+struct FailingTest {
+    name: String,
+    error: String,
+}
+
+#[derive(Serialize, Debug)]
+struct PassingTest {
+    name: String,
+}
+
+impl WebhookNotifier {
+    pub fn new(setting: &WebhookSetting) -> anyhow::Result<Self> {
+        Ok(Self {
+            inner: Client::builder()
+                .timeout(Duration::from_secs(TIMEOUT_SEC))
+                .user_agent(format!("calpol {}", env!("CARGO_PKG_VERSION")))
+                .build()?,
+            url: setting.url.clone(),
+            secret: setting.secret.clone(),
+        })
+    }
+}
+
+#[async_trait]
+impl Notifier for WebhookNotifier {
+    fn name(&self) -> &str {
+        "webhook"
+    }
+
+    /// POSTs the payload, signing the timestamp and body with the secret if there is one.
+    async fn notify(
+        &self,
+        notification: &Notification<'_>,
+        _: &NotificationTargets,
+    ) -> anyhow::Result<()> {
+        let body = serde_json::to_vec(&create_payload(notification)).unwrap();
+        let timestamp = Utc::now().timestamp();
+        let mut request = self
+            .inner
+            .post(self.url.clone())
+            .header(CONTENT_TYPE, "application/json")
+            .header(TIMESTAMP_HEADER, timestamp);
+        if let Some(secret) = &self.secret {
+            request = request.header(SIGNATURE_HEADER, sign(secret, timestamp, &body));
+        }
+        let response = request
+            .body(body)
+            .send()
+            .await
+            .with_context(|| format!("Failed to send webhook to {}", self.url))?;
+        if !response.status().is_success() {
+            bail!(
+                "Webhook {} returned unexpected response status: {}",
+                self.url,
+                response.status().as_u16()
+            )
+        }
+        log::info!("Sent webhook to {}", self.url);
+        Ok(())
+    }
+}
+
+fn create_payload(notification: &Notification) -> WebhookPayload {
+    WebhookPayload {
+        timestamp: Utc::now().to_rfc3339(),
+        kind: notification.kind,
+        now_failing: notification
+            .now_failing
+            .iter()
+            .map(|(t, e)| FailingTest {
+                name: t.name.clone(),
+                error: format!("{:#}", e),
+            })
+            .collect(),
+        now_passing: notification
+            .now_passing
+            .iter()
+            .map(|t| PassingTest {
+                name: t.name.clone(),
+            })
+            .collect(),
+    }
+}
+
+/// Hex encoded HMAC-SHA256 of `<timestamp>.<body>`, in the form `sha256=<digest>`.
+///
+/// Including the timestamp allows receivers to reject requests that are replayed later on.
+fn sign(secret: &str, timestamp: i64, body: &[u8]) -> String {
+    let mut mac =
+        Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC accepts any key length");
+    mac.update(timestamp.to_string().as_bytes());
+    mac.update(b".");
+    mac.update(body);
+    format!("sha256={}", hex::encode(mac.finalize().into_bytes()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::sign;
+
+    #[test]
+    fn signature_covers_timestamp_and_body() {
+        let body = br#"{"kind":"state_change"}"#;
+        assert_eq!(
+            sign("secret", 1650456000, body),
+            "sha256=01b15f5568ae57630ad98502b46bbcdcf88e08988869884b70bf41edd6e5ddad"
+        );
+        assert_ne!(
+            sign("secret", 1650456000, body),
+            sign("secret", 1650456001, body)
+        );
+    }
+}
@@ -1,3 +1,92 @@
+table! {
+    api_keys (id) {
+        id -> Int4,
+        user_id -> Int4,
+        name -> Varchar,
+        token_hash -> Varchar,
+        scopes -> Array<Text>,
+        created -> Timestamptz,
+        expires -> Nullable<Timestamptz>,
+        last_used -> Nullable<Timestamptz>,
+    }
+}
+
+table! {
+    deferred_sms (id) {
+        id -> Int4,
+        user_id -> Int4,
+        created -> Timestamptz,
+        body -> Text,
+        attempts -> Int4,
+    }
+}
+
+table! {
+    escalation_policies (id) {
+        id -> Int4,
+        name -> Varchar,
+        repeat_interval -> Nullable<Int4>,
+        escalate_after -> Nullable<Int4>,
+        escalate_channels -> Array<Text>,
+    }
+}
+
+table! {
+    escalation_policy_tests (escalation_policy_id, test_id) {
+        escalation_policy_id -> Int4,
+        test_id -> Int4,
+    }
+}
+
+table! {
+    escalation_policy_users (escalation_policy_id, user_id) {
+        escalation_policy_id -> Int4,
+        user_id -> Int4,
+    }
+}
+
+table! {
+    incident_events (id) {
+        id -> Int4,
+        incident_id -> Int4,
+        time -> Timestamptz,
+        kind -> Varchar,
+        user_id -> Nullable<Int4>,
+        message -> Nullable<Text>,
+    }
+}
+
+table! {
+    incidents (id) {
+        id -> Int4,
+        test_id -> Int4,
+        started -> Timestamptz,
+        ended -> Nullable<Timestamptz>,
+        last_notified -> Timestamptz,
+        escalated -> Nullable<Timestamptz>,
+        acknowledged_at -> Nullable<Timestamptz>,
+        acknowledged_by -> Nullable<Int4>,
+        failure_reason -> Nullable<Text>,
+    }
+}
+
+table! {
+    maintenance_window_tests (maintenance_window_id, test_id) {
+        maintenance_window_id -> Int4,
+        test_id -> Int4,
+    }
+}
+
+table! {
+    maintenance_windows (id) {
+        id -> Int4,
+        name -> Varchar,
+        start_time -> Timestamptz,
+        end_time -> Timestamptz,
+        all_tests -> Bool,
+    }
+}
+
 table! {
     runner_logs (id) {
         id -> Int4,
@@ -15,7 +104,7 @@ table! {
     sessions (id) {
         id -> Int4,
         user_id -> Int4,
-        token -> Varchar,
+        token_hash -> Varchar,
         created -> Timestamptz,
         last_used -> Timestamptz,
         last_ip -> Bytea,
@@ -31,6 +120,11 @@ table! {
         failure_reason -> Nullable<Text>,
         time_started -> Timestamptz,
         time_finished -> Timestamptz,
+        dns_ms -> Nullable<Int4>,
+        connect_ms -> Nullable<Int4>,
+        tls_ms -> Nullable<Int4>,
+        first_byte_ms -> Nullable<Int4>,
+        total_ms -> Nullable<Int4>,
     }
 }
 
@@ -42,6 +136,20 @@ table! {
         config -> Jsonb,
         failing -> Bool,
         failure_threshold -> Int4,
+        run_interval -> Nullable<Int4>,
+        run_timeout -> Nullable<Int4>,
+        tags -> Array<Text>,
+        severity -> Varchar,
+    }
+}
+
+table! {
+    user_subscriptions (id) {
+        id -> Int4,
+        user_id -> Int4,
+        test_id -> Nullable<Int4>,
+        tag -> Nullable<Varchar>,
+        excluded -> Bool,
     }
 }
 
@@ -51,15 +159,53 @@ table! {
         name -> Varchar,
         email -> Varchar,
         password_hash -> Nullable<Varchar>,
-        password_reset_token -> Nullable<Varchar>,
+        password_reset_token_hash -> Nullable<Varchar>,
         password_reset_token_creation -> Nullable<Timestamptz>,
         phone_number -> Nullable<Varchar>,
         sms_notifications -> Bool,
         email_notifications -> Bool,
+        subscribe_all_tests -> Bool,
+        email_min_severity -> Varchar,
+        sms_min_severity -> Varchar,
+        timezone -> Varchar,
+        quiet_hours_start -> Nullable<Time>,
+        quiet_hours_end -> Nullable<Time>,
+        quiet_hours_sms -> Varchar,
+        role -> Varchar,
     }
 }
 
+joinable!(api_keys -> users (user_id));
+joinable!(deferred_sms -> users (user_id));
+joinable!(escalation_policy_tests -> escalation_policies (escalation_policy_id));
+joinable!(escalation_policy_tests -> tests (test_id));
+joinable!(escalation_policy_users -> escalation_policies (escalation_policy_id));
+joinable!(escalation_policy_users -> users (user_id));
+joinable!(incident_events -> incidents (incident_id));
+joinable!(incident_events -> users (user_id));
+joinable!(incidents -> tests (test_id));
+joinable!(incidents -> users (acknowledged_by));
+joinable!(maintenance_window_tests -> maintenance_windows (maintenance_window_id));
+joinable!(maintenance_window_tests -> tests (test_id));
 joinable!(sessions -> users (user_id));
 joinable!(test_results -> users (test_id));
+joinable!(user_subscriptions -> tests (test_id));
+joinable!(user_subscriptions -> users (user_id));
 
-allow_tables_to_appear_in_same_query!(runner_logs, sessions, test_results, tests, users,);
+allow_tables_to_appear_in_same_query!(
+    api_keys,
+    deferred_sms,
+    escalation_policies,
+    escalation_policy_tests,
+    escalation_policy_users,
+    incident_events,
+    incidents,
+    maintenance_window_tests,
+    maintenance_windows,
+    runner_logs,
+    sessions,
+    test_results,
+    tests,
+    user_subscriptions,
+    users,
+);
@@ -1,9 +1,11 @@
-use anyhow::Context;
+use anyhow::{bail, Context};
 use config::{Config, Environment, File, FileFormat};
 use lettre::message::Mailbox;
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use std::net::SocketAddr;
 use std::time::Duration;
+use url::Url;
 use validator::{Validate, ValidationError};
 
 #[derive(Debug, Deserialize, Validate)]
@@ -13,6 +15,10 @@ pub struct Settings {
     pub api_socket: SocketAddr,
     /// Postgres connection URL
     pub database_url: String,
+    /// Secret key used to hash session, API key and password reset tokens before they are stored
+    #[serde(default)]
+    #[validate(length(min = 32, message = "token_key must be at least 32 characters"))]
+    pub token_key: String,
     #[validate]
     pub mailer: MailerSetting,
     #[validate]
@@ -20,6 +26,25 @@ pub struct Settings {
     #[validate]
     #[serde(rename = "messagebird")]
     pub message_bird: Option<MessageBirdSetting>,
+    #[validate]
+    pub twilio: Option<TwilioSetting>,
+    /// Which SMS provider to use, required if more than one is configured
+    pub sms_provider: Option<SmsProviderKind>,
+    /// Webhooks to notify when tests change state
+    #[serde(default)]
+    pub webhooks: Vec<WebhookSetting>,
+    /// Chat channels to post to when tests change state
+    #[serde(default)]
+    pub chats: Vec<ChatSetting>,
+    /// PagerDuty service to trigger and resolve incidents on
+    pub pagerduty: Option<PagerDutySetting>,
+    /// Opsgenie account to create and close alerts on
+    pub opsgenie: Option<OpsgenieSetting>,
+    /// Public URL of the Calpol server, used to link to it from notifications
+    pub public_url: Option<Url>,
+    /// Overrides the notification templates, by channel name (or `default` for every channel)
+    #[serde(default)]
+    pub templates: HashMap<String, TemplateSetting>,
 }
 
 #[derive(Debug, Deserialize, Validate)]
@@ -43,10 +68,10 @@ impl MailerSetting {
 #[derive(Debug, Serialize, Deserialize, Validate)]
 #[validate(schema(function = "validate_runner_setting", skip_on_field_errors = false))]
 pub struct RunnerSetting {
-    /// How frequently to run the test suite in minutes
+    /// How frequently to run tests in minutes (unless the test specifies its own interval)
     #[serde(default = "default_runner_interval")]
     pub interval: u8,
-    /// Maximum time the test suite is allowed to run in minutes
+    /// Maximum time a run of the test suite is allowed to take in minutes
     #[serde(default = "default_runner_timeout")]
     #[validate(range(min = 1))]
     pub timeout: u8,
@@ -77,6 +102,79 @@ pub struct MessageBirdSetting {
     pub access_key: String,
 }
 
+#[derive(Debug, Deserialize, Validate)]
+pub struct TwilioSetting {
+    pub account_sid: String,
+    pub auth_token: String,
+    /// Phone number (or alphanumeric sender ID) SMS messages will be sent from
+    pub from: String,
+    /// Allows using a different API server, e.g. for testing
+    #[serde(default = "default_twilio_base_url")]
+    pub base_url: Url,
+}
+
+#[derive(Debug, Deserialize, Copy, Clone)]
+#[serde(rename_all = "lowercase")]
+pub enum SmsProviderKind {
+    MessageBird,
+    Twilio,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct WebhookSetting {
+    pub url: Url,
+    /// Key used to sign requests with HMAC-SHA256, in the `X-Calpol-Signature` header
+    pub secret: Option<String>,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+#[serde(rename_all = "lowercase", tag = "type")]
+pub enum ChatSetting {
+    /// Slack incoming webhook
+    Slack { webhook_url: Url },
+    /// Discord channel webhook
+    Discord { webhook_url: Url },
+    /// Matrix room, using the client-server API
+    Matrix {
+        homeserver: Url,
+        room_id: String,
+        access_token: String,
+    },
+    /// Microsoft Teams incoming webhook connector
+    Teams { webhook_url: Url },
+}
+
+#[derive(Debug, Deserialize)]
+pub struct PagerDutySetting {
+    /// Integration key of the Events API v2 integration
+    pub routing_key: String,
+    /// Allows using a different API server, e.g. for testing
+    #[serde(default = "default_pagerduty_url")]
+    pub url: Url,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct OpsgenieSetting {
+    /// Key of an API integration
+    pub api_key: String,
+    /// Allows using a different API server, e.g. for the EU instance or for testing
+    #[serde(default = "default_opsgenie_url")]
+    pub url: Url,
+}
+
+/// Handlebars templates for the message body of each type of notification.
+#[derive(Debug, Deserialize)]
+pub struct TemplateSetting {
+    /// Tests that have started failing
+    pub failing: Option<String>,
+    /// Tests that are still failing, sent according to their escalation policy
+    pub reminder: Option<String>,
+    /// Tests that have been failing long enough to be escalated
+    pub escalation: Option<String>,
+    /// Tests that are passing again
+    pub passing: Option<String>,
+}
+
 impl Settings {
     pub fn new(file: Option<&String>) -> anyhow::Result<Self> {
         let mut s = Config::new();
@@ -90,6 +188,11 @@ impl Settings {
         s.merge(Environment::new())
             .context("Failed loading config from environment")?;
         let r: Settings = s.try_into().context("Failed to load config")?;
+        if r.token_key.is_empty() {
+            bail!(
+                "The token_key setting is required, generate a key using e.g. `openssl rand -base64 32`"
+            )
+        }
         r.validate().context("Failed to validate config")?;
         Ok(r)
     }
@@ -99,6 +202,18 @@ fn default_api_socket() -> SocketAddr {
     "0.0.0.0:80".parse().unwrap()
 }
 
+fn default_twilio_base_url() -> Url {
+    Url::parse(crate::twilio::DEFAULT_BASE_URL).unwrap()
+}
+
+fn default_pagerduty_url() -> Url {
+    Url::parse("https://events.pagerduty.com/v2/enqueue").unwrap()
+}
+
+fn default_opsgenie_url() -> Url {
+    Url::parse("https://api.opsgenie.com").unwrap()
+}
+
 fn default_runner_interval() -> u8 {
     15
 }
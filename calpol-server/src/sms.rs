@@ -0,0 +1,99 @@
+use crate::messagebird::MessageBirdClient;
+use crate::settings::{Settings, SmsProviderKind};
+use crate::twilio::TwilioClient;
+use anyhow::{bail, Context};
+use async_trait::async_trait;
+use std::sync::Arc;
+use thiserror::Error;
+
+/// A service that can send SMS messages.
+#[async_trait]
+pub trait SmsProvider: Send + Sync {
+    /// Sends the message to each recipient.
+    async fn send_sms(&self, body: &str, recipients: Vec<String>) -> Result<(), Error>;
+}
+
+#[derive(Error, Debug)]
+pub enum Error {
+    #[error("MessageBird: {0}")]
+    MessageBird(
+        #[from]
+        #[source]
+        crate::messagebird::Error,
+    ),
+    #[error("Twilio: {0}")]
+    Twilio(
+        #[from]
+        #[source]
+        crate::twilio::Error,
+    ),
+}
+
+#[async_trait]
+impl SmsProvider for MessageBirdClient {
+    async fn send_sms(&self, body: &str, recipients: Vec<String>) -> Result<(), Error> {
+        let count = recipients.len();
+        let result = self.send_message(body, recipients).await?;
+        log::info!("Sent {} sms messages via messagebird: {:?}", count, result);
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl SmsProvider for TwilioClient {
+    async fn send_sms(&self, body: &str, recipients: Vec<String>) -> Result<(), Error> {
+        let mut error = None;
+        for recipient in &recipients {
+            match self.send_message(body, recipient).await {
+                Ok(result) => log::info!("Sent sms to {} via twilio: {:?}", recipient, result),
+                Err(e) => {
+                    log::error!("Failed to send sms to {} because {:#}", recipient, e);
+                    error.get_or_insert(e);
+                }
+            }
+        }
+        match error {
+            None => Ok(()),
+            Some(e) => Err(e.into()),
+        }
+    }
+}
+
+/// Creates the configured SMS provider, if there is one.
+/// If both providers are configured then `sms_provider` must be set to choose between them.
+pub fn sms_provider(settings: &Settings) -> anyhow::Result<Option<Arc<dyn SmsProvider>>> {
+    let kind = match (
+        settings.sms_provider,
+        &settings.message_bird,
+        &settings.twilio,
+    ) {
+        (Some(kind), _, _) => kind,
+        (None, Some(_), Some(_)) => {
+            bail!("Both messagebird and twilio are configured, sms_provider must be set")
+        }
+        (None, Some(_), None) => SmsProviderKind::MessageBird,
+        (None, None, Some(_)) => SmsProviderKind::Twilio,
+        (None, None, None) => return Ok(None),
+    };
+    Ok(Some(match kind {
+        SmsProviderKind::MessageBird => {
+            let setting = settings
+                .message_bird
+                .as_ref()
+                .context("sms_provider is messagebird, but messagebird is not configured")?;
+            Arc::new(MessageBirdClient::new(&setting.access_key)?)
+        }
+        SmsProviderKind::Twilio => {
+            let setting = settings
+                .twilio
+                .as_ref()
+                .context("sms_provider is twilio, but twilio is not configured")?;
+            Arc::new(TwilioClient::new(
+                &setting.account_sid,
+                &setting.auth_token,
+                &setting.from,
+                &setting.base_url,
+            )?)
+        }
+    }))
+}
@@ -1,5 +1,6 @@
-use crate::messagebird::MessageBirdClient;
-use crate::settings::{MailerSetting, MessageBirdSetting, Settings};
+use crate::notifier::{build_notifiers, Notifier, Templates};
+use crate::settings::{MailerSetting, Settings};
+use crate::sms::{sms_provider, SmsProvider};
 use diesel::r2d2::ConnectionManager;
 use diesel::{r2d2, PgConnection};
 use lettre::transport::smtp::authentication::Credentials;
@@ -12,7 +13,9 @@ use tokio::sync::mpsc;
 pub struct AppState {
     database: r2d2::Pool<ConnectionManager<PgConnection>>,
     pub mailer: AsyncSmtpTransport<Tokio1Executor>,
-    pub message_bird: Option<MessageBirdClient>,
+    pub sms: Option<Arc<dyn SmsProvider>>,
+    pub notifiers: Arc<Vec<Box<dyn Notifier>>>,
+    pub templates: Arc<Templates>,
     pub settings: Arc<Settings>,
     test_runner: mpsc::Sender<()>,
 }
@@ -23,10 +26,16 @@ impl AppState {
         database: r2d2::Pool<ConnectionManager<PgConnection>>,
         test_runner: mpsc::Sender<()>,
     ) -> anyhow::Result<Self> {
+        let mailer = lettre_client(&settings.mailer)?;
+        let sms = sms_provider(&settings)?;
+        let templates = Arc::new(Templates::new(&settings)?);
+        let notifiers = build_notifiers(&settings, &mailer, sms.clone(), &templates)?;
         Ok(Self {
             database,
-            mailer: lettre_client(&settings.mailer)?,
-            message_bird: message_bird_client(settings.message_bird.as_ref())?,
+            mailer,
+            sms,
+            notifiers: Arc::new(notifiers),
+            templates,
             settings,
             test_runner,
         })
@@ -41,16 +50,6 @@ impl AppState {
     }
 }
 
-fn message_bird_client(
-    setting: Option<&MessageBirdSetting>,
-) -> anyhow::Result<Option<MessageBirdClient>> {
-    Ok(if let Some(setting) = setting {
-        Some(MessageBirdClient::new(&setting.access_key)?)
-    } else {
-        None
-    })
-}
-
 fn lettre_client(setting: &MailerSetting) -> anyhow::Result<AsyncSmtpTransport<Tokio1Executor>> {
     Ok(
         AsyncSmtpTransport::<Tokio1Executor>::starttls_relay(setting.host.as_str())?
@@ -1,14 +1,22 @@
 use crate::database::{
-    Connection, NewRunnerLog, NewTestResult, RunnerLogRepository, RunnerLogRepositoryImpl, Test,
-    TestRepositoryImpl, TestResultRepository, TestResultRepositoryImpl, UserRepositoryImpl,
+    Connection, DeferredSms, DeferredSmsRepository, DeferredSmsRepositoryImpl,
+    EscalationPolicyRepository, EscalationPolicyRepositoryImpl, Incident, IncidentEventKind,
+    IncidentEventRepositoryImpl, IncidentRepository, IncidentRepositoryImpl,
+    MaintenanceWindowRepository, MaintenanceWindowRepositoryImpl, NewDeferredSms, NewIncident,
+    NewIncidentEvent, NewRunnerLog, NewTestResult, RunnerLogRepository, RunnerLogRepositoryImpl,
+    Test, TestRepositoryImpl, TestResultRepository, TestResultRepositoryImpl, User,
+    UserRepositoryImpl, UserSubscriptionRepositoryImpl,
 };
+use crate::notifier::NotificationTargets;
 use crate::settings::{RunnerSetting, Settings};
+use crate::test_runner::notify::NotificationResults;
+use crate::test_runner::routing::{NotificationRouting, Recipient};
 use crate::test_runner::{RunResults, TestRunResult};
-use anyhow::Context;
+use anyhow::{anyhow, Context};
 use chrono::{DateTime, Duration, Utc};
 use diesel::QueryResult;
 use diesel_repository::CrudRepository;
-use lettre::message::Mailbox;
+use std::collections::{HashMap, HashSet};
 use std::sync::Arc;
 use tokio::task::spawn_blocking;
 
@@ -74,18 +82,57 @@ pub async fn retrieve_tests(database: Connection) -> anyhow::Result<Vec<Test>> {
     .await?
 }
 
+#[derive(Default, Clone)]
+pub struct ActiveMaintenance {
+    all_tests: bool,
+    test_ids: HashSet<i32>,
+}
+
+impl ActiveMaintenance {
+    pub fn includes(&self, test: &Test) -> bool {
+        self.all_tests || self.test_ids.contains(&test.id)
+    }
+}
+
+/// Fetches the tests that are currently in a maintenance window.
+pub async fn fetch_active_maintenance(database: Connection) -> anyhow::Result<ActiveMaintenance> {
+    spawn_blocking(move || -> anyhow::Result<_> {
+        let window_repository = MaintenanceWindowRepositoryImpl::new(&database);
+        let mut maintenance = ActiveMaintenance::default();
+        for window in window_repository
+            .find_active(Utc::now())
+            .context("Failed to load maintenance windows")?
+        {
+            if window.all_tests {
+                maintenance.all_tests = true;
+            } else {
+                let tests = window_repository
+                    .find_tests(&window)
+                    .context("Failed to load maintenance window tests")?;
+                maintenance.test_ids.extend(tests.iter().map(|t| t.id));
+            }
+        }
+        Ok(maintenance)
+    })
+    .await?
+}
+
 pub struct ProcessedTests {
     /// Tests that were previously failing but have now transitioned to a passing state.
     pub now_passing: Vec<Test>,
     /// Tests that were previously passing but have now transitioned into a failing state.
     /// Includes the latest error indicating why they are failing.
     pub now_failing: Vec<(Test, anyhow::Error)>,
+    /// How long each of the tests had been failing for, by test ID.
+    pub outages: HashMap<i32, Duration>,
 }
 
-/// Inserts test results into the database, and retrieves the new status of the tests
+/// Inserts test results into the database, and retrieves the new status of the tests.
+/// State changes of tests that are in maintenance are deferred until the maintenance has ended.
 pub async fn insert_test_results(
     database: Connection,
     results: Vec<(Test, TestRunResult)>,
+    maintenance: ActiveMaintenance,
 ) -> anyhow::Result<ProcessedTests> {
     spawn_blocking(move || -> anyhow::Result<_> {
         let test_result_repository = TestResultRepositoryImpl::new(&database);
@@ -98,6 +145,11 @@ pub async fn insert_test_results(
                 failure_reason: result.result.as_ref().err().map(|e| format!("{:#}", e)),
                 time_started: result.started,
                 time_finished: result.finished,
+                dns_ms: result.timings.dns.map(duration_ms),
+                connect_ms: result.timings.connect.map(duration_ms),
+                tls_ms: result.timings.tls.map(duration_ms),
+                first_byte_ms: result.timings.first_byte.map(duration_ms),
+                total_ms: result.timings.total.map(duration_ms),
             };
             test_result_repository.insert(test_result).allow_foreign_key_violation(|info| {
                 log::warn!(
@@ -109,15 +161,31 @@ pub async fn insert_test_results(
         }
 
         // Check the most recent test results to determine if the test has reached the failure threshold
+        let now = Utc::now();
+        let mut outages = HashMap::new();
         let results = results.into_iter().map(|(test, result)| {
             let latest = test_result_repository
                 .find_latest_belonging_to(&test, test.failure_threshold as u32)
                 .context("Loading test results")?;
             let failing = (latest.len() == test.failure_threshold as usize)
                 && latest.iter().all(|r| !r.success);
+            if failing && !test.failing {
+                if let Some(first_failure) = latest.iter().map(|r| r.time_started).min() {
+                    outages.insert(test.id, now - first_failure);
+                }
+            }
             Ok((test, result, failing))
         }).collect::<anyhow::Result<Vec<_>>>()?;
 
+        // Tests in maintenance keep their previous state
+        let results = results.into_iter().filter(|(test, _, failing_now)| {
+            let deferred = test.failing != *failing_now && maintenance.includes(test);
+            if deferred {
+                log::info!("Deferring state change of test {} due to maintenance", test.name);
+            }
+            !deferred
+        }).collect::<Vec<_>>();
+
         // Filter tests that have now transitioned to a passing state
         let (now_passing, remaining): (Vec<_>, Vec<_>) = results.into_iter()
             .partition(|(test, _, failing_now)| {
@@ -132,33 +200,231 @@ pub async fn insert_test_results(
                 !test.failing && *failing_now
             }).map(|(test, result, _)| (test, result.result.err().unwrap())).collect();
 
+        // The outage of a test that is passing again is the length of its incident
+        let incident_repository = IncidentRepositoryImpl::new(&database);
+        let now_passing = now_passing.into_iter().map(|x| x.0).collect::<Vec<_>>();
+        for test in &now_passing {
+            if let Some(incident) = incident_repository.find_ongoing_by_test(test)? {
+                outages.insert(test.id, now - incident.started);
+            }
+        }
+
         Ok(ProcessedTests {
-            now_passing: now_passing.into_iter().map(|x| x.0).collect(),
-            now_failing
+            now_passing,
+            now_failing,
+            outages,
         })
     })
     .await?
 }
 
-/// Mark tests as passing / failing in the database.
-/// This should be done only once notifications have been successfully sent.
+fn duration_ms(duration: std::time::Duration) -> i32 {
+    duration.as_millis().try_into().unwrap_or(i32::MAX)
+}
+
+/// Mark tests as passing / failing in the database, opening and closing their incidents.
+/// The result of each notification, including any that failed to send, is recorded in the
+/// timeline of the incident.
 pub async fn update_test_status(
     database: Connection,
     processed: ProcessedTests,
+    notified: NotificationResults,
 ) -> anyhow::Result<()> {
     spawn_blocking(move || -> anyhow::Result<()> {
         let test_repository = TestRepositoryImpl::new(&database);
-        for (mut test, _) in processed.now_failing {
+        let incident_repository = IncidentRepositoryImpl::new(&database);
+        let now = Utc::now();
+        for (mut test, error) in processed.now_failing {
             test.failing = true;
             test_repository
                 .update(&test)
                 .context("Updating test state to failing")?;
+            if incident_repository.find_ongoing_by_test(&test)?.is_none() {
+                let failure_reason = format!("{:#}", error);
+                let incident = incident_repository
+                    .insert(NewIncident {
+                        test_id: test.id,
+                        started: now,
+                        last_notified: now,
+                        failure_reason: Some(failure_reason.clone()),
+                    })
+                    .context("Opening incident")?;
+                let opened = (IncidentEventKind::Opened, Some(failure_reason));
+                let notified = notified.get(&test.id).map(Vec::as_slice);
+                record_events(
+                    &database,
+                    &incident,
+                    Some(opened),
+                    notified.unwrap_or_default(),
+                )?;
+            }
         }
         for mut test in processed.now_passing {
             test.failing = false;
             test_repository
                 .update(&test)
                 .context("Updating test state to passing")?;
+            if let Some(mut incident) = incident_repository.find_ongoing_by_test(&test)? {
+                incident.ended = Some(now);
+                incident_repository
+                    .update(&incident)
+                    .context("Closing incident")?;
+                let ended = (IncidentEventKind::Ended, None);
+                let notified = notified.get(&test.id).map(Vec::as_slice);
+                record_events(
+                    &database,
+                    &incident,
+                    Some(ended),
+                    notified.unwrap_or_default(),
+                )?;
+            }
+        }
+        Ok(())
+    })
+    .await?
+}
+
+/// Adds an event to the timeline of the incident, followed by the result of each notification.
+fn record_events(
+    database: &Connection,
+    incident: &Incident,
+    event: Option<(IncidentEventKind, Option<String>)>,
+    notified: &[String],
+) -> anyhow::Result<()> {
+    let event_repository = IncidentEventRepositoryImpl::new(database);
+    if let Some((kind, message)) = event {
+        event_repository
+            .insert(NewIncidentEvent::new(incident, kind, None, message))
+            .context("Recording incident event")?;
+    }
+    for result in notified {
+        event_repository
+            .insert(NewIncidentEvent::new(
+                incident,
+                IncidentEventKind::Notified,
+                None,
+                Some(result.clone()),
+            ))
+            .context("Recording incident notification")?;
+    }
+    Ok(())
+}
+
+/// Users and channels to notify when an incident is escalated.
+pub struct Escalation {
+    pub targets: NotificationTargets,
+    /// Names of the additional notifiers to escalate to.
+    pub channels: Vec<String>,
+}
+
+/// An ongoing incident that is due a reminder or an escalation.
+pub struct DueIncident {
+    pub incident: Incident,
+    pub test: Test,
+    /// The reason the test most recently failed.
+    pub error: anyhow::Error,
+    /// Set if the incident is due to be escalated.
+    pub escalation: Option<Escalation>,
+}
+
+/// Finds the ongoing incidents that are due a reminder or escalation according to the
+/// escalation policy of their test. Incidents that have been acknowledged, or of tests that are
+/// in maintenance, are skipped.
+pub async fn fetch_due_incidents(
+    database: Connection,
+    maintenance: ActiveMaintenance,
+    now: DateTime<Utc>,
+) -> anyhow::Result<Vec<DueIncident>> {
+    spawn_blocking(move || -> anyhow::Result<_> {
+        let incident_repository = IncidentRepositoryImpl::new(&database);
+        let policy_repository = EscalationPolicyRepositoryImpl::new(&database);
+        let test_result_repository = TestResultRepositoryImpl::new(&database);
+        let mut due = Vec::new();
+        for (incident, test) in incident_repository
+            .find_ongoing()
+            .context("Failed to load incidents")?
+        {
+            if incident.acknowledged_at.is_some() || maintenance.includes(&test) {
+                continue;
+            }
+            let policy = match policy_repository
+                .find_by_test(&test)
+                .context("Failed to load escalation policy")?
+            {
+                Some(policy) => policy,
+                None => continue,
+            };
+            let escalate = incident.escalated.is_none()
+                && policy.escalate_after.map_or(false, |m| {
+                    now - incident.started >= Duration::minutes(m as i64)
+                });
+            let remind = policy.repeat_interval.map_or(false, |m| {
+                now - incident.last_notified >= Duration::minutes(m as i64)
+            });
+            if !escalate && !remind {
+                continue;
+            }
+            let escalation = if escalate {
+                let mut targets = NotificationTargets::default();
+                for user in policy_repository
+                    .find_users(&policy)
+                    .context("Failed to load escalation users")?
+                {
+                    match user.get_mailbox() {
+                        Ok(m) => targets.emails.push(m),
+                        Err(e) => log::error!("Failed to get mailbox for user {}: {}", user.id, e),
+                    }
+                    targets.sms.extend(user.phone_number);
+                }
+                Some(Escalation {
+                    targets,
+                    channels: policy.escalate_channels,
+                })
+            } else {
+                None
+            };
+            let reason = test_result_repository
+                .find_latest_belonging_to(&test, 1)
+                .context("Loading test results")?
+                .into_iter()
+                .next()
+                .and_then(|r| r.failure_reason)
+                .unwrap_or_else(|| String::from("Unknown failure"));
+            due.push(DueIncident {
+                incident,
+                test,
+                error: anyhow!(reason),
+                escalation,
+            });
+        }
+        Ok(due)
+    })
+    .await?
+}
+
+/// An incident that has been reminded or escalated, with the result of each notification.
+pub struct NotifiedIncident {
+    pub incident: Incident,
+    pub escalated: bool,
+    pub notified: Vec<String>,
+}
+
+/// Saves the notification times of incidents that have been reminded or escalated, and records
+/// the notifications in their timelines.
+pub async fn update_incidents(
+    database: Connection,
+    incidents: Vec<NotifiedIncident>,
+) -> anyhow::Result<()> {
+    spawn_blocking(move || -> anyhow::Result<()> {
+        let incident_repository = IncidentRepositoryImpl::new(&database);
+        for notified in incidents {
+            incident_repository
+                .update(&notified.incident)
+                .context("Updating incident")?;
+            let escalated = notified
+                .escalated
+                .then(|| (IncidentEventKind::Escalated, None));
+            record_events(&database, &notified.incident, escalated, &notified.notified)?;
         }
         Ok(())
     })
@@ -186,38 +452,93 @@ where
     }
 }
 
-#[derive(Default)]
-pub struct NotificationTargets {
-    pub emails: Vec<Mailbox>,
-    pub sms: Vec<String>,
+/// Fetches the users that need to be notified of test results, along with their subscriptions
+pub async fn fetch_notification_routing(
+    database: Connection,
+) -> anyhow::Result<NotificationRouting> {
+    spawn_blocking(move || -> anyhow::Result<_> {
+        let user_repository = UserRepositoryImpl::new(&database);
+        let subscription_repository = UserSubscriptionRepositoryImpl::new(&database);
+        let mut subscriptions = HashMap::<_, Vec<_>>::new();
+        for subscription in subscription_repository
+            .find_all()
+            .context("Failed to load subscriptions")?
+        {
+            subscriptions
+                .entry(subscription.user_id)
+                .or_default()
+                .push(subscription);
+        }
+        let recipients = user_repository
+            .find_all()
+            .context("Failed to load users")?
+            .into_iter()
+            .filter_map(|user| {
+                let subscriptions = subscriptions.remove(&user.id).unwrap_or_default();
+                Recipient::new(user, subscriptions)
+            })
+            .collect();
+        Ok(NotificationRouting::new(recipients))
+    })
+    .await?
 }
 
-/// Fetches a list of emails and phone numbers that need to be notified of test results
-pub async fn fetch_notification_targets(
+/// Saves SMS that have been deferred until the end of users' quiet hours
+pub async fn insert_deferred_sms(
     database: Connection,
-) -> anyhow::Result<NotificationTargets> {
+    deferred: Vec<NewDeferredSms>,
+) -> anyhow::Result<()> {
+    spawn_blocking(move || -> anyhow::Result<()> {
+        let repository = DeferredSmsRepositoryImpl::new(&database);
+        for sms in deferred {
+            repository.insert(sms).context("Inserting deferred sms")?;
+        }
+        Ok(())
+    })
+    .await?
+}
+
+/// Fetches all the deferred SMS, along with the users they are for
+pub async fn fetch_deferred_sms(database: Connection) -> anyhow::Result<Vec<(DeferredSms, User)>> {
     spawn_blocking(move || -> anyhow::Result<_> {
-        let mut targets = NotificationTargets::default();
-        let user_repository = UserRepositoryImpl::new(&database);
-        for user in user_repository.find_all().context("Failed to load users")? {
-            if user.email_notifications {
-                match user.get_mailbox() {
-                    Ok(m) => targets.emails.push(m),
-                    Err(e) => log::error!("Failed to get mailbox for user {}: {}", user.id, e),
-                }
-            }
-            if let Some(number) = user.phone_number {
-                if user.sms_notifications {
-                    targets.sms.push(number);
-                }
-            }
+        DeferredSmsRepositoryImpl::new(&database)
+            .find_all_with_user()
+            .context("Failed to load deferred sms")
+    })
+    .await?
+}
+
+/// Saves the number of attempts of deferred SMS that failed to send, to be retried later
+pub async fn update_deferred_sms(
+    database: Connection,
+    deferred: Vec<DeferredSms>,
+) -> anyhow::Result<()> {
+    spawn_blocking(move || -> anyhow::Result<()> {
+        let repository = DeferredSmsRepositoryImpl::new(&database);
+        for sms in deferred {
+            repository.update(&sms).context("Updating deferred sms")?;
         }
-        Ok(targets)
+        Ok(())
     })
     .await?
 }
 
-/// Cleans up test results and runner logs that are older than the minimum log age.
+/// Removes deferred SMS once they have been sent
+pub async fn delete_deferred_sms(
+    database: Connection,
+    deferred: Vec<DeferredSms>,
+) -> anyhow::Result<()> {
+    spawn_blocking(move || -> anyhow::Result<()> {
+        let repository = DeferredSmsRepositoryImpl::new(&database);
+        for sms in deferred {
+            repository.delete(sms).context("Deleting deferred sms")?;
+        }
+        Ok(())
+    })
+    .await?
+}
+
+/// Cleans up test results, runner logs and maintenance windows older than the minimum log age.
 pub async fn delete_expired_records(
     database: Connection,
     settings: Arc<Settings>,
@@ -228,6 +549,12 @@ pub async fn delete_expired_records(
             .delete_all_older_than(settings.runner.minimum_log_age())
             .context("Failed to delete old test results")?;
 
+        let window_repository = MaintenanceWindowRepositoryImpl::new(&database);
+        if let Err(e) = window_repository.delete_all_ended_before(settings.runner.minimum_log_age())
+        {
+            log::error!("Failed to clean old maintenance windows: {:#}", e);
+        }
+
         let runner_log_repository = RunnerLogRepositoryImpl::new(&database);
         if let Err(e) =
             runner_log_repository.delete_all_older_than(settings.runner.minimum_log_age())
@@ -0,0 +1,75 @@
+use crate::notifier::{Notification, NotificationKind};
+use crate::test_runner::database::NotifiedIncident;
+use crate::test_runner::{database, notify};
+use crate::AppState;
+use chrono::Utc;
+use std::collections::HashMap;
+
+/// Sends reminders and escalations for incidents that are still ongoing, according to the
+/// escalation policies of their tests.
+pub async fn process_incidents(state: &AppState) -> anyhow::Result<()> {
+    let now = Utc::now();
+    let maintenance = database::fetch_active_maintenance(state.database()).await?;
+    let due = database::fetch_due_incidents(state.database(), maintenance, now).await?;
+    if due.is_empty() {
+        return Ok(());
+    }
+
+    let outages = due
+        .iter()
+        .map(|due| (due.test.id, now - due.incident.started))
+        .collect::<HashMap<_, _>>();
+    let mut notified = Vec::new();
+    let mut reminders = Vec::new();
+    let mut reminded = Vec::new();
+    for mut due in due {
+        let escalation = match due.escalation {
+            Some(escalation) => escalation,
+            None => {
+                reminders.push((due.test, due.error));
+                due.incident.last_notified = now;
+                reminded.push(due.incident);
+                continue;
+            }
+        };
+        log::info!("Escalating failure of test {}", due.test.name);
+        let failing = [(due.test, due.error)];
+        let notification = Notification {
+            kind: NotificationKind::Escalation,
+            now_passing: &[],
+            now_failing: &failing,
+            outages: &outages,
+        };
+        let notifiers = state.notifiers.iter().enumerate().filter(|(_, notifier)| {
+            notifier.notifies_users() || escalation.channels.iter().any(|c| c == notifier.name())
+        });
+        let results = notify::dispatch(&notification, notifiers, &escalation.targets).await;
+        due.incident.last_notified = now;
+        due.incident.escalated = Some(now);
+        notified.push(NotifiedIncident {
+            incident: due.incident,
+            escalated: true,
+            notified: results.into_iter().map(|d| d.description).collect(),
+        });
+    }
+
+    if !reminders.is_empty() {
+        log::info!("Sending reminders for {} failing tests", reminders.len());
+        let routing = database::fetch_notification_routing(state.database()).await?;
+        let notification = Notification {
+            kind: NotificationKind::Reminder,
+            now_passing: &[],
+            now_failing: &reminders,
+            outages: &outages,
+        };
+        // Failed reminders aren't retried, since they will be sent again after the repeat interval
+        let (mut results, _) = notify::route(&notification, &routing, state).await;
+        notified.extend(reminded.into_iter().map(|incident| NotifiedIncident {
+            notified: results.remove(&incident.test_id).unwrap_or_default(),
+            incident,
+            escalated: false,
+        }));
+    }
+
+    database::update_incidents(state.database(), notified).await
+}
@@ -1,36 +1,71 @@
 mod database;
+mod escalation;
 mod notify;
+mod routing;
 mod runnable;
+mod schedule;
+
+pub use runnable::{payload_bytes, ResponseMatcher};
+pub use schedule::ParsedSchedule;
 
 use crate::database::Test;
 use crate::state::AppState;
-use crate::test_runner::runnable::Runnable;
+use crate::test_runner::notify::Retries;
+use crate::test_runner::runnable::{Runnable, Timings};
+use crate::test_runner::schedule::{Scheduler, Status};
 use anyhow::Context;
 use calpol_model::tests::TestConfig;
 use chrono::{DateTime, Utc};
 use futures::{stream, StreamExt};
+use std::convert::TryFrom;
+use std::time::Duration;
 use tokio::sync::mpsc;
 use tokio::time::{sleep_until, timeout_at, Instant};
 
+/// How often the scheduler wakes up to check which tests are due to be run.
+const SCHEDULER_TICK: Duration = Duration::from_secs(60);
+
 pub fn make_channel() -> (mpsc::Sender<()>, mpsc::Receiver<()>) {
     mpsc::channel(1)
 }
 
 pub async fn start(state: AppState, mut rx: mpsc::Receiver<()>) -> anyhow::Result<()> {
+    let mut scheduler = Scheduler::default();
+    let mut retries = Retries::default();
     loop {
-        log::info!("Beginning test run");
         let start_instant = Instant::now();
         let start_time = Utc::now();
         let max_run_time = start_instant + state.settings.runner.timeout_duration();
-        let result = run_tests(&state, max_run_time).await;
-        if let Err(e) = database::insert_runner_log(state.database(), result, start_time).await {
-            log::error!("Failed to write runner log: {}", e);
-        };
-        let next_run = start_instant + state.settings.runner.interval_duration();
+        notify::retry_failed(&mut retries, &state).await;
+        let result = run_tests(
+            &state,
+            &mut scheduler,
+            &mut retries,
+            start_time,
+            max_run_time,
+        )
+        .await;
+        // Only log runs where at least one test was due
+        if let Some(result) = result.transpose() {
+            if let Err(e) = database::insert_runner_log(state.database(), result, start_time).await
+            {
+                log::error!("Failed to write runner log: {}", e);
+            };
+        }
+        if let Err(e) = escalation::process_incidents(&state).await {
+            log::error!("Failed to process ongoing incidents: {:#}", e);
+        }
+        if let Err(e) = notify::send_deferred_sms(&state).await {
+            log::error!("Failed to send deferred sms: {:#}", e);
+        }
+        let next_tick = start_instant + SCHEDULER_TICK;
         tokio::select! {
-            _ = sleep_until(next_run) => {},
+            _ = sleep_until(next_tick) => {},
             // Allows waking up early to immediately re-run tests
-            message = rx.recv() => {message.unwrap()}
+            message = rx.recv() => {
+                message.unwrap();
+                scheduler.reset();
+            }
         }
     }
 }
@@ -45,9 +80,17 @@ pub struct TestRunResult {
     started: DateTime<Utc>,
     finished: DateTime<Utc>,
     result: anyhow::Result<()>,
+    timings: Timings,
 }
 
-async fn run_tests(state: &AppState, timeout: Instant) -> anyhow::Result<RunResults> {
+/// Runs the tests that are due, returns `None` if no tests were due to be run or skipped.
+async fn run_tests(
+    state: &AppState,
+    scheduler: &mut Scheduler,
+    retries: &mut Retries,
+    now: DateTime<Utc>,
+    timeout: Instant,
+) -> anyhow::Result<Option<RunResults>> {
     // Retrieve disabled and enabled tests from the database
     let (disabled, enabled): (Vec<_>, Vec<_>) = database::retrieve_tests(state.database())
         .await?
@@ -65,26 +108,74 @@ async fn run_tests(state: &AppState, timeout: Instant) -> anyhow::Result<RunResu
             )
         });
 
+    // Select the tests that are due according to their schedule
+    let mut due = Vec::new();
+    let mut inactive = 0;
+    for (test, config) in deserialized {
+        let schedule = config
+            .as_ref()
+            .ok()
+            .and_then(|c| c.schedule.as_ref())
+            .and_then(|s| match ParsedSchedule::try_from(s) {
+                Ok(s) => Some(s),
+                Err(e) => {
+                    log::error!("Invalid schedule for test {}: {:#}", test.name, e);
+                    None
+                }
+            });
+        match scheduler.status(&test, schedule.as_ref(), &state.settings.runner, now) {
+            Status::NotDue => {}
+            Status::Due => due.push((test, config)),
+            Status::Inactive => {
+                // The test stays due, so it is checked again on the next tick
+                if scheduler.mark_skipped(&test) {
+                    inactive += 1;
+                }
+            }
+        }
+    }
+    let skipped = disabled.len() + inactive;
+    if due.is_empty() {
+        // Still log a run if tests were skipped for being outside their active hours
+        return Ok((inactive > 0).then(|| RunResults {
+            passed: 0,
+            failed: 0,
+            skipped,
+        }));
+    }
+    log::info!("Beginning test run of {} tests", due.len());
+    for (test, _) in &due {
+        scheduler.mark_run(test, now);
+    }
+
     // Run the tests
-    let results = stream::iter(deserialized)
+    let results = stream::iter(due)
         .map(|(test, config)| async move {
             let run_result = match config {
                 Ok(c) => {
                     let started = Utc::now();
-                    let result = timeout_at(timeout, c.run(&test.name))
+                    let test_deadline = test.timeout_duration().map(|t| Instant::now() + t);
+                    let (deadline, reason) = match test_deadline {
+                        Some(d) if d < timeout => (d, "Cancelled due to test timeout"),
+                        _ => (timeout, "Cancelled due to global test timeout"),
+                    };
+                    let mut timings = Timings::default();
+                    let result = timeout_at(deadline, c.run(&test.name, &mut timings))
                         .await
-                        .context("Cancelled due to global test timeout")
+                        .context(reason)
                         .and_then(std::convert::identity);
                     TestRunResult {
                         started,
                         finished: Utc::now(),
                         result,
+                        timings,
                     }
                 }
                 Err(e) => TestRunResult {
                     started: Utc::now(),
                     finished: Utc::now(),
                     result: Err(e),
+                    timings: Timings::default(),
                 },
             };
             (test, run_result)
@@ -97,18 +188,21 @@ async fn run_tests(state: &AppState, timeout: Instant) -> anyhow::Result<RunResu
     let run_results = RunResults {
         passed: results.iter().filter(|(_, r)| r.result.is_ok()).count(),
         failed: results.iter().filter(|(_, r)| r.result.is_err()).count(),
-        skipped: disabled.len(),
+        skipped,
     };
 
-    let processed = database::insert_test_results(state.database(), results).await?;
+    let maintenance = database::fetch_active_maintenance(state.database()).await?;
+
+    let processed = database::insert_test_results(state.database(), results, maintenance).await?;
 
-    let notification_targets = database::fetch_notification_targets(state.database()).await?;
+    let notification_routing = database::fetch_notification_routing(state.database()).await?;
 
-    notify::send_notifications(&processed, notification_targets, state).await?;
+    let notified =
+        notify::send_notifications(&processed, &notification_routing, state, retries).await;
 
-    database::update_test_status(state.database(), processed).await?;
+    database::update_test_status(state.database(), processed, notified).await?;
 
     database::delete_expired_records(state.database(), state.settings.clone()).await?;
 
-    Ok(run_results)
+    Ok(Some(run_results))
 }
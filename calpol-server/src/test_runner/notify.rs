@@ -1,141 +1,348 @@
-use crate::database::Test;
-use crate::test_runner::database::{NotificationTargets, ProcessedTests};
+use crate::database::{NewDeferredSms, Test};
+use crate::notifier::{
+    create_sms_bodies, Notification, NotificationKind, NotificationTargets, Notifier,
+};
+use crate::test_runner::database;
+use crate::test_runner::database::ProcessedTests;
+use crate::test_runner::routing::{NotificationRouting, QuietHours};
 use crate::AppState;
-use anyhow::Context;
+use anyhow::anyhow;
+use chrono::{Duration, Utc};
 use futures::future::join_all;
-use lettre::message::Mailbox;
-use lettre::{AsyncTransport, Message};
+use std::collections::{HashMap, HashSet};
 
-const MAX_SMS_CHARS: usize = 70;
+/// Number of times to try sending a deferred SMS before it is discarded.
+const MAX_DEFERRED_SMS_ATTEMPTS: i32 = 5;
 
+/// Number of times to try sending a state change notification via a notifier before giving up.
+const MAX_NOTIFICATION_ATTEMPTS: u32 = 5;
+
+/// Description of each notification result, by the ID of the test it was about.
+pub type NotificationResults = HashMap<i32, Vec<String>>;
+
+/// The result of sending a notification via one of the notifiers.
+pub struct Delivery {
+    /// Index of the notifier in the application state.
+    pub notifier: usize,
+    /// Description of the result, to be recorded in the incident timelines.
+    pub description: String,
+    pub sent: bool,
+}
+
+/// A state change notification that failed to send via one of the notifiers.
+pub struct FailedNotification {
+    /// Index of the notifier in the application state.
+    notifier: usize,
+    kind: NotificationKind,
+    now_passing: Vec<Test>,
+    now_failing: Vec<(Test, String)>,
+    outages: HashMap<i32, Duration>,
+    targets: NotificationTargets,
+    attempts: u32,
+}
+
+/// State change notifications that failed to send, which are retried on each tick using only the
+/// notifier that failed, until they are sent or the maximum attempts is reached.
+#[derive(Default)]
+pub struct Retries {
+    pending: Vec<FailedNotification>,
+}
+
+impl Retries {
+    /// Stops retrying notifications about tests that have since changed state again.
+    fn supersede(&mut self, notification: &Notification) {
+        let ids = notification
+            .now_passing
+            .iter()
+            .chain(notification.now_failing.iter().map(|(test, _)| test))
+            .map(|test| test.id)
+            .collect::<HashSet<_>>();
+        for failed in &mut self.pending {
+            failed.now_passing.retain(|test| !ids.contains(&test.id));
+            failed
+                .now_failing
+                .retain(|(test, _)| !ids.contains(&test.id));
+        }
+        self.pending
+            .retain(|failed| !failed.now_passing.is_empty() || !failed.now_failing.is_empty());
+    }
+}
+
+/// Sends the state changes to every notifier, logging the result of each.
+/// Notifications that fail to send are added to the retries.
 pub async fn send_notifications(
     processed: &ProcessedTests,
-    targets: NotificationTargets,
+    routing: &NotificationRouting,
     state: &AppState,
-) -> anyhow::Result<()> {
-    if !processed.now_failing.is_empty() {
-        let body = create_sms_failure_body(&processed.now_failing);
-        send_sms_notifications(state, targets.sms.clone(), body).await?;
-        let body = create_email_failure_body(&processed.now_failing);
-        send_email_notifications(state, &targets.emails, &body, "Calpol Test Failures").await?;
-    }
-    if !processed.now_passing.is_empty() {
-        let body = create_sms_passing_body(&processed.now_passing);
-        send_sms_notifications(state, targets.sms, body).await?;
-        let body = create_email_passing_body(&processed.now_passing);
-        send_email_notifications(state, &targets.emails, &body, "Calpol Tests Passing").await?;
+    retries: &mut Retries,
+) -> NotificationResults {
+    if processed.now_failing.is_empty() && processed.now_passing.is_empty() {
+        return NotificationResults::new();
     }
-    Ok(())
+    let notification = Notification {
+        kind: NotificationKind::StateChange,
+        now_passing: &processed.now_passing,
+        now_failing: &processed.now_failing,
+        outages: &processed.outages,
+    };
+    retries.supersede(&notification);
+    let (results, failed) = route(&notification, routing, state).await;
+    retries.pending.extend(failed);
+    results
 }
 
-async fn send_email_notifications(
+/// Sends the whole notification to the notifiers with fixed destinations, and to each user only
+/// the tests they are subscribed to.
+/// Returns a description of each result, to be recorded in the incident timelines, and the
+/// notifications that failed to send.
+pub async fn route(
+    notification: &Notification<'_>,
+    routing: &NotificationRouting,
     state: &AppState,
-    emails: &[Mailbox],
-    message: &str,
-    subject: &str,
-) -> anyhow::Result<()> {
-    let results = join_all(emails.iter().map(|email| {
-        let message = Message::builder()
-            .to(email.clone())
-            .from(state.settings.mailer.send_from.clone())
-            .reply_to(state.settings.mailer.reply_to().clone())
-            .subject(subject.to_string())
-            .body(message.to_string())
-            .unwrap();
-        async move { (state.mailer.send(message).await, email) }
-    }))
-    .await;
-    for (result, mailbox) in results {
-        if let Err(e) = result {
-            log::error!("Failed to send email to {} because {:#}", mailbox, e);
-        } else {
-            log::info!("Sent email to {}", mailbox)
+) -> (NotificationResults, Vec<FailedNotification>) {
+    let notifiers = &state.notifiers;
+    let mut results = NotificationResults::new();
+    let mut failed = Vec::new();
+    let channels = notifiers
+        .iter()
+        .enumerate()
+        .filter(|(_, n)| !n.notifies_users());
+    let targets = NotificationTargets::default();
+    let sent = dispatch(notification, channels, &targets).await;
+    failed.extend(failed_deliveries(notification, &sent, &targets));
+    let sent = sent.into_iter().map(|d| d.description).collect::<Vec<_>>();
+    record_results(&mut results, notification, &sent);
+    for routed in routing.split(notification) {
+        let notification = Notification {
+            kind: notification.kind,
+            now_passing: &routed.now_passing,
+            now_failing: &routed.now_failing,
+            outages: notification.outages,
+        };
+        let users = notifiers
+            .iter()
+            .enumerate()
+            .filter(|(_, n)| n.notifies_users());
+        let sent = dispatch(&notification, users, &routed.targets).await;
+        failed.extend(failed_deliveries(&notification, &sent, &routed.targets));
+        let mut sent = sent.into_iter().map(|d| d.description).collect::<Vec<_>>();
+        if !routed.deferred_sms.is_empty() {
+            sent.extend(defer_sms(&notification, routed.deferred_sms, state).await);
         }
+        record_results(&mut results, &notification, &sent);
     }
-    Ok(())
+    (results, failed)
 }
 
-async fn send_sms_notifications(
-    state: &AppState,
-    phone_numbers: Vec<String>,
-    message: String,
-) -> anyhow::Result<()> {
-    let message = if message.len() > MAX_SMS_CHARS {
-        format!(
-            "{}...",
-            message.chars().take(MAX_SMS_CHARS - 3).collect::<String>()
-        )
-    } else {
-        message
-    };
-    if let Some(message_bird) = &state.message_bird {
-        let count = phone_numbers.len();
-        let result = message_bird
-            .send_message(&message, phone_numbers)
-            .await
-            .context("Failed sending SMS messages")?;
-        log::info!("Sent {} sms messages: {:?}", count, result);
-    } else {
-        log::error!(
-            "Unable to send {} sms notifications because messagebird is not configured",
-            phone_numbers.len()
-        );
-    }
-    Ok(())
+fn failed_deliveries(
+    notification: &Notification,
+    sent: &[Delivery],
+    targets: &NotificationTargets,
+) -> Vec<FailedNotification> {
+    sent.iter()
+        .filter(|delivery| !delivery.sent)
+        .map(|delivery| FailedNotification {
+            notifier: delivery.notifier,
+            kind: notification.kind,
+            now_passing: notification.now_passing.to_vec(),
+            now_failing: notification
+                .now_failing
+                .iter()
+                .map(|(test, error)| (test.clone(), format!("{:#}", error)))
+                .collect(),
+            outages: notification.outages.clone(),
+            targets: targets.clone(),
+            attempts: 1,
+        })
+        .collect()
 }
 
-fn create_sms_failure_body(tests: &[(Test, anyhow::Error)]) -> String {
-    let mut message = String::from("Calpol: ");
-    if tests.len() == 1 {
-        let (test, e) = tests.first().unwrap();
-        message.push_str(&format!("Test {} failed: {:#}", test.name, e));
-    } else {
-        let names = tests
+/// Retries the notifications that previously failed to send, using only the notifier that failed.
+pub async fn retry_failed(retries: &mut Retries, state: &AppState) {
+    for mut failed in std::mem::take(&mut retries.pending) {
+        let notifier = &state.notifiers[failed.notifier];
+        let now_failing = failed
+            .now_failing
             .iter()
-            .map(|(t, _)| t.name.to_string())
-            .collect::<Vec<_>>()
-            .join(", ");
-        message.push_str(&format!(
-            "{} tests failed, including: {}",
-            tests.len(),
-            names
-        ));
+            .map(|(test, error)| (test.clone(), anyhow!(error.clone())))
+            .collect::<Vec<_>>();
+        let notification = Notification {
+            kind: failed.kind,
+            now_passing: &failed.now_passing,
+            now_failing: &now_failing,
+            outages: &failed.outages,
+        };
+        match notifier.notify(&notification, &failed.targets).await {
+            Ok(()) => log::info!(
+                "Sent {} notifications after {} failed attempts",
+                notifier.name(),
+                failed.attempts
+            ),
+            Err(e) => {
+                failed.attempts += 1;
+                if failed.attempts < MAX_NOTIFICATION_ATTEMPTS {
+                    log::error!(
+                        "Failed to send {} notifications, will retry: {:#}",
+                        notifier.name(),
+                        e
+                    );
+                    retries.pending.push(failed);
+                } else {
+                    log::error!(
+                        "Giving up sending {} notifications after {} attempts: {:#}",
+                        notifier.name(),
+                        failed.attempts,
+                        e
+                    );
+                }
+            }
+        }
     }
-    message
 }
 
-fn create_sms_passing_body(tests: &[Test]) -> String {
-    let mut message = String::from("Calpol: ");
-    if tests.len() == 1 {
-        let test = tests.first().unwrap();
-        message.push_str(&format!("Test {} now passing", test.name));
-    } else {
-        let names = tests
-            .iter()
-            .map(|t| t.name.to_string())
-            .collect::<Vec<_>>()
-            .join(", ");
-        message.push_str(&format!(
-            "{} tests passing, including: {}",
-            tests.len(),
-            names
-        ));
+/// Saves the SMS for users that are in their quiet hours, to be sent once the quiet hours end.
+/// Reminders aren't deferred, since the test may well have recovered by the time they are sent.
+async fn defer_sms(
+    notification: &Notification<'_>,
+    user_ids: Vec<i32>,
+    state: &AppState,
+) -> Option<String> {
+    if notification.kind != NotificationKind::StateChange {
+        return None;
     }
-    message
+    let now = Utc::now();
+    let count = user_ids.len();
+    let label = notification.kind.label();
+    let bodies = match create_sms_bodies(&state.templates, notification) {
+        Ok(bodies) => bodies,
+        Err(e) => {
+            log::error!("Failed to defer sms notifications: {:#}", e);
+            return Some(format!("Failed to defer {} via sms: {:#}", label, e));
+        }
+    };
+    let deferred = user_ids
+        .into_iter()
+        .flat_map(|user_id| {
+            bodies.iter().map(move |body| NewDeferredSms {
+                user_id,
+                created: now,
+                body: body.clone(),
+            })
+        })
+        .collect();
+    Some(
+        match database::insert_deferred_sms(state.database(), deferred).await {
+            Ok(()) => format!(
+                "Deferred {} via sms to {} users in their quiet hours",
+                label, count
+            ),
+            Err(e) => {
+                log::error!("Failed to defer sms notifications: {:#}", e);
+                format!("Failed to defer {} via sms: {:#}", label, e)
+            }
+        },
+    )
 }
 
-fn create_email_failure_body(tests: &[(Test, anyhow::Error)]) -> String {
-    let mut message = format!("Calpol: {} tests failed\n\n", tests.len());
-    for (t, e) in tests {
-        message.push_str(&format!("{}: {:#}\n\n", t.name, e));
+/// Sends the deferred SMS of users whose quiet hours have ended.
+/// SMS that fail to send are kept to be retried on the next tick, up to the maximum attempts.
+pub async fn send_deferred_sms(state: &AppState) -> anyhow::Result<()> {
+    let now = Utc::now();
+    let mut done = Vec::new();
+    let mut failed = Vec::new();
+    for (mut sms, user) in database::fetch_deferred_sms(state.database()).await? {
+        if QuietHours::from_user(&user).map_or(false, |q| q.is_quiet(now)) {
+            continue;
+        }
+        match (
+            user.phone_number.filter(|_| user.sms_notifications),
+            &state.sms,
+        ) {
+            (Some(number), Some(provider)) => {
+                if let Err(e) = provider.send_sms(&sms.body, vec![number]).await {
+                    sms.attempts += 1;
+                    if sms.attempts < MAX_DEFERRED_SMS_ATTEMPTS {
+                        log::error!(
+                            "Failed to send deferred sms to user {}, will retry: {:#}",
+                            user.id,
+                            e
+                        );
+                        failed.push(sms);
+                        continue;
+                    }
+                    log::error!(
+                        "Discarding deferred sms to user {} after {} failed attempts: {:#}",
+                        user.id,
+                        sms.attempts,
+                        e
+                    );
+                }
+            }
+            (Some(_), None) => {
+                log::error!("Unable to send deferred sms because no SMS provider is configured")
+            }
+            (None, _) => log::info!(
+                "Discarding deferred sms for user {} who has disabled sms notifications",
+                user.id
+            ),
+        }
+        done.push(sms);
     }
-    message
+    database::update_deferred_sms(state.database(), failed).await?;
+    database::delete_deferred_sms(state.database(), done).await
 }
 
-fn create_email_passing_body(tests: &[Test]) -> String {
-    let mut message = format!("Calpol: {} tests now passing\n\n", tests.len());
-    for t in tests {
-        message.push_str(&format!("{}\n", t.name));
+fn record_results(results: &mut NotificationResults, notification: &Notification, sent: &[String]) {
+    let tests = notification
+        .now_passing
+        .iter()
+        .chain(notification.now_failing.iter().map(|(test, _)| test));
+    for test in tests {
+        results
+            .entry(test.id)
+            .or_default()
+            .extend(sent.iter().cloned());
     }
-    message
+}
+
+/// Sends the notification to each of the notifiers (with their index in the application state),
+/// logging the result of each.
+pub async fn dispatch<'n>(
+    notification: &Notification<'_>,
+    notifiers: impl Iterator<Item = (usize, &'n Box<dyn Notifier>)>,
+    targets: &NotificationTargets,
+) -> Vec<Delivery> {
+    let results = join_all(notifiers.map(|(index, notifier)| async move {
+        (
+            index,
+            notifier,
+            notifier.notify(notification, targets).await,
+        )
+    }))
+    .await;
+    let label = notification.kind.label();
+    results
+        .into_iter()
+        .map(|(index, notifier, result)| {
+            let name = notifier.name();
+            match result {
+                Ok(()) => {
+                    log::info!("Sent {} notifications", name);
+                    Delivery {
+                        notifier: index,
+                        description: format!("Sent {} via {}", label, name),
+                        sent: true,
+                    }
+                }
+                Err(e) => {
+                    log::error!("Failed to send {} notifications: {:#}", name, e);
+                    Delivery {
+                        notifier: index,
+                        description: format!("Failed to send {} via {}: {:#}", label, name, e),
+                        sent: false,
+                    }
+                }
+            }
+        })
+        .collect()
 }
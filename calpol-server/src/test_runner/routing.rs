@@ -0,0 +1,228 @@
+use crate::database::{Test, User, UserSubscription};
+use crate::notifier::{Notification, NotificationTargets};
+use anyhow::anyhow;
+use calpol_model::api_v1::{QuietHoursSms, Severity};
+use chrono::{DateTime, NaiveTime, Utc};
+use chrono_tz::Tz;
+use lettre::message::Mailbox;
+use std::str::FromStr;
+
+/// A daily window during which a user is only sent SMS about critical tests.
+pub struct QuietHours {
+    timezone: Tz,
+    start: NaiveTime,
+    end: NaiveTime,
+    sms: QuietHoursSms,
+}
+
+impl QuietHours {
+    /// Returns `None` if the user doesn't have quiet hours.
+    pub fn from_user(user: &User) -> Option<Self> {
+        let (start, end) = user.quiet_hours_start.zip(user.quiet_hours_end)?;
+        let timezone = Tz::from_str(&user.timezone).unwrap_or_else(|e| {
+            log::error!("Invalid timezone for user {}: {}", user.id, e);
+            Tz::UTC
+        });
+        Some(Self {
+            timezone,
+            start,
+            end,
+            sms: user.quiet_hours_sms.parse().unwrap_or_default(),
+        })
+    }
+
+    pub fn is_quiet(&self, now: DateTime<Utc>) -> bool {
+        let time = now.with_timezone(&self.timezone).time();
+        if self.start <= self.end {
+            self.start <= time && time < self.end
+        } else {
+            // The quiet hours run overnight
+            self.start <= time || time < self.end
+        }
+    }
+}
+
+/// A user that has opted in to receiving notifications.
+pub struct Recipient {
+    user_id: i32,
+    mailbox: Option<Mailbox>,
+    email_notifications: bool,
+    phone_number: Option<String>,
+    subscribe_all_tests: bool,
+    email_min_severity: Severity,
+    sms_min_severity: Severity,
+    quiet_hours: Option<QuietHours>,
+    subscriptions: Vec<UserSubscription>,
+}
+
+impl Recipient {
+    /// Returns `None` if the user hasn't enabled any notifications.
+    pub fn new(user: User, subscriptions: Vec<UserSubscription>) -> Option<Self> {
+        let mailbox = match user.get_mailbox() {
+            Ok(m) => Some(m),
+            Err(e) => {
+                log::error!("Failed to get mailbox for user {}: {}", user.id, e);
+                None
+            }
+        };
+        let email_notifications = user.email_notifications && mailbox.is_some();
+        let phone_number = user.phone_number.clone().filter(|_| user.sms_notifications);
+        if !email_notifications && phone_number.is_none() {
+            return None;
+        }
+        Some(Self {
+            user_id: user.id,
+            mailbox,
+            email_notifications,
+            phone_number,
+            subscribe_all_tests: user.subscribe_all_tests,
+            email_min_severity: user.email_min_severity.parse().unwrap_or_default(),
+            sms_min_severity: user.sms_min_severity.parse().unwrap_or_default(),
+            quiet_hours: QuietHours::from_user(&user),
+            subscriptions,
+        })
+    }
+
+    /// Exclusions take priority over subscriptions.
+    fn is_subscribed(&self, test: &Test) -> bool {
+        let matching = self
+            .subscriptions
+            .iter()
+            .filter(|s| s.matches(test))
+            .collect::<Vec<_>>();
+        if matching.iter().any(|s| s.excluded) {
+            return false;
+        }
+        self.subscribe_all_tests || !matching.is_empty()
+    }
+}
+
+/// Decides which users are notified about each test.
+pub struct NotificationRouting {
+    recipients: Vec<Recipient>,
+    now: DateTime<Utc>,
+}
+
+/// Part of a notification, for the tests that are sent to the same users.
+pub struct RoutedNotification {
+    pub targets: NotificationTargets,
+    /// IDs of users whose SMS are deferred until the end of their quiet hours.
+    pub deferred_sms: Vec<i32>,
+    pub now_passing: Vec<Test>,
+    pub now_failing: Vec<(Test, anyhow::Error)>,
+}
+
+/// Indexes of the recipients to notify about a test.
+#[derive(Default, PartialEq)]
+struct Route {
+    emails: Vec<usize>,
+    sms: Vec<usize>,
+    deferred_sms: Vec<usize>,
+}
+
+impl Route {
+    fn is_empty(&self) -> bool {
+        self.emails.is_empty() && self.sms.is_empty() && self.deferred_sms.is_empty()
+    }
+}
+
+impl NotificationRouting {
+    pub fn new(recipients: Vec<Recipient>) -> Self {
+        Self {
+            recipients,
+            now: Utc::now(),
+        }
+    }
+
+    fn route(&self, test: &Test) -> Route {
+        let severity: Severity = test.severity.parse().unwrap_or_default();
+        let mut route = Route::default();
+        for (i, recipient) in self.recipients.iter().enumerate() {
+            if !recipient.is_subscribed(test) {
+                continue;
+            }
+            let mut email =
+                recipient.email_notifications && severity >= recipient.email_min_severity;
+            let sms = recipient.phone_number.is_some() && severity >= recipient.sms_min_severity;
+            let quiet = recipient
+                .quiet_hours
+                .as_ref()
+                .filter(|q| severity < Severity::Critical && q.is_quiet(self.now));
+            match (sms, quiet) {
+                (false, _) => {}
+                (true, None) => route.sms.push(i),
+                (true, Some(quiet)) => match quiet.sms {
+                    QuietHoursSms::Defer => route.deferred_sms.push(i),
+                    QuietHoursSms::Email => email |= recipient.mailbox.is_some(),
+                },
+            }
+            if email {
+                route.emails.push(i);
+            }
+        }
+        route
+    }
+
+    fn targets(&self, route: &Route) -> NotificationTargets {
+        NotificationTargets {
+            emails: route
+                .emails
+                .iter()
+                .filter_map(|i| self.recipients[*i].mailbox.clone())
+                .collect(),
+            sms: route
+                .sms
+                .iter()
+                .filter_map(|i| self.recipients[*i].phone_number.clone())
+                .collect(),
+        }
+    }
+
+    /// Splits the tests of the notification into groups that are sent to the same users.
+    /// Tests that no users are subscribed to are left out.
+    pub fn split(&self, notification: &Notification) -> Vec<RoutedNotification> {
+        let mut groups: Vec<(Route, RoutedNotification)> = Vec::new();
+        for test in notification.now_passing {
+            if let Some(group) = self.group_for(&mut groups, test) {
+                group.now_passing.push(test.clone());
+            }
+        }
+        for (test, error) in notification.now_failing {
+            if let Some(group) = self.group_for(&mut groups, test) {
+                group
+                    .now_failing
+                    .push((test.clone(), anyhow!("{:#}", error)));
+            }
+        }
+        groups.into_iter().map(|(_, routed)| routed).collect()
+    }
+
+    fn group_for<'a>(
+        &self,
+        groups: &'a mut Vec<(Route, RoutedNotification)>,
+        test: &Test,
+    ) -> Option<&'a mut RoutedNotification> {
+        let route = self.route(test);
+        if route.is_empty() {
+            return None;
+        }
+        let index = match groups.iter().position(|(r, _)| *r == route) {
+            Some(index) => index,
+            None => {
+                let routed = RoutedNotification {
+                    targets: self.targets(&route),
+                    deferred_sms: route
+                        .deferred_sms
+                        .iter()
+                        .map(|i| self.recipients[*i].user_id)
+                        .collect(),
+                    now_passing: Vec::new(),
+                    now_failing: Vec::new(),
+                };
+                groups.push((route, routed));
+                groups.len() - 1
+            }
+        };
+        Some(&mut groups[index].1)
+    }
+}
@@ -0,0 +1,158 @@
+use crate::test_runner::runnable::{Domain, Timings};
+use anyhow::{bail, Context};
+use calpol_model::tests::{Dns, DnsRecordType};
+use std::net::{IpAddr, SocketAddr};
+use std::time::{Duration, Instant};
+use trust_dns_resolver::config::{NameServerConfigGroup, ResolverConfig, ResolverOpts};
+use trust_dns_resolver::proto::rr::{RData, RecordType};
+use trust_dns_resolver::proto::xfer::DnsRequestOptions;
+use trust_dns_resolver::AsyncResolver;
+
+const DNS_TIMEOUT: Duration = Duration::from_secs(5);
+const DNS_PORT: u16 = 53;
+
+pub async fn test_dns(
+    dns: &Dns,
+    domain: Domain,
+    test_name: &str,
+    timings: &mut Timings,
+) -> anyhow::Result<()> {
+    let resolver = AsyncResolver::tokio(
+        resolver_config(dns, domain)?,
+        ResolverOpts {
+            timeout: DNS_TIMEOUT,
+            cache_size: 0,
+            ..Default::default()
+        },
+    )
+    .context("Failed to get resolver")?;
+    let record_type = to_record_type(dns.record_type);
+    let started = Instant::now();
+    let lookup = resolver
+        .lookup(dns.name.as_str(), record_type, DnsRequestOptions::default())
+        .await
+        .with_context(|| format!("Failed to lookup {} record", record_type))?;
+    timings.dns = Some(started.elapsed());
+    let records = lookup
+        .record_iter()
+        .filter(|r| r.record_type() == record_type)
+        .collect::<Vec<_>>();
+    log::info!(
+        "{}: Found {} {} records for {}",
+        test_name,
+        records.len(),
+        record_type,
+        dns.name
+    );
+
+    if records.len() < dns.minimum_records as usize {
+        bail!(
+            "Expected at least {} {} records, found {}",
+            dns.minimum_records,
+            record_type,
+            records.len()
+        )
+    }
+
+    for record in &records {
+        if let Some(min) = dns.minimum_ttl {
+            if record.ttl() < min {
+                bail!("Record TTL {} is less than {}", record.ttl(), min)
+            }
+        }
+        if let Some(max) = dns.maximum_ttl {
+            if record.ttl() > max {
+                bail!("Record TTL {} is greater than {}", record.ttl(), max)
+            }
+        }
+    }
+
+    let values = records
+        .iter()
+        .map(|r| normalise(&format_rdata(r.rdata())))
+        .collect::<Vec<_>>();
+    for expected in &dns.expected_values {
+        if !values.contains(&normalise(expected)) {
+            bail!(
+                "Expected value `{}` not found in {} records: {}",
+                expected,
+                record_type,
+                values.join(", ")
+            )
+        }
+    }
+
+    Ok(())
+}
+
+/// Only use nameservers that can be reached over the domain's IP version.
+fn resolver_config(dns: &Dns, domain: Domain) -> anyhow::Result<ResolverConfig> {
+    let nameservers = match &dns.nameserver {
+        None => ResolverConfig::google().name_servers().to_vec(),
+        Some(nameserver) => {
+            let addr = nameserver
+                .parse::<SocketAddr>()
+                .or_else(|_| {
+                    nameserver
+                        .parse::<IpAddr>()
+                        .map(|ip| SocketAddr::new(ip, DNS_PORT))
+                })
+                .context("Invalid nameserver address")?;
+            NameServerConfigGroup::from_ips_clear(&[addr.ip()], addr.port(), true).to_vec()
+        }
+    };
+    let mut config = ResolverConfig::new();
+    for nameserver in nameservers {
+        let matches = match domain {
+            Domain::IpV4 => nameserver.socket_addr.is_ipv4(),
+            Domain::IpV6 => nameserver.socket_addr.is_ipv6(),
+        };
+        if matches {
+            config.add_name_server(nameserver);
+        }
+    }
+    if config.name_servers().is_empty() {
+        bail!("No {} nameserver available", domain)
+    }
+    Ok(config)
+}
+
+fn to_record_type(record_type: DnsRecordType) -> RecordType {
+    match record_type {
+        DnsRecordType::A => RecordType::A,
+        DnsRecordType::AAAA => RecordType::AAAA,
+        DnsRecordType::CNAME => RecordType::CNAME,
+        DnsRecordType::MX => RecordType::MX,
+        DnsRecordType::TXT => RecordType::TXT,
+        DnsRecordType::NS => RecordType::NS,
+        DnsRecordType::SRV => RecordType::SRV,
+        DnsRecordType::CAA => RecordType::CAA,
+    }
+}
+
+fn format_rdata(rdata: &RData) -> String {
+    match rdata {
+        RData::A(ip) => ip.to_string(),
+        RData::AAAA(ip) => ip.to_string(),
+        RData::CNAME(name) | RData::NS(name) => name.to_utf8(),
+        RData::MX(mx) => format!("{} {}", mx.preference(), mx.exchange().to_utf8()),
+        RData::SRV(srv) => format!(
+            "{} {} {} {}",
+            srv.priority(),
+            srv.weight(),
+            srv.port(),
+            srv.target().to_utf8()
+        ),
+        RData::TXT(txt) => txt
+            .txt_data()
+            .iter()
+            .map(|data| String::from_utf8_lossy(data))
+            .collect(),
+        other => other.to_string(),
+    }
+}
+
+/// Compare values ignoring case and the trailing dot of fully qualified names.
+fn normalise(value: &str) -> String {
+    value.trim().trim_end_matches('.').to_ascii_lowercase()
+}
@@ -1,33 +1,37 @@
-use crate::test_runner::runnable::{verify_certificate_expiry, Domain};
+use crate::test_runner::runnable::{verify_certificate_expiry, Domain, Timings};
 use anyhow::bail;
 use anyhow::Context;
-use calpol_model::tests::Http;
+use calpol_model::tests::{BodyAssertion, Http, HttpAuthentication, HttpRequestBody};
+use http::header::{
+    HeaderMap, HeaderName, HeaderValue, ACCEPT, AUTHORIZATION, CONTENT_TYPE, HOST, LOCATION,
+    USER_AGENT,
+};
 use http::method::Method;
-use reqwest::redirect;
+use http::{Request, StatusCode};
+use hyper::body::HttpBody;
+use hyper::client::conn::{handshake, SendRequest};
+use hyper::{Body, Response};
+use regex::Regex;
 use std::str::FromStr;
-use std::time::Duration;
-use tokio::time::timeout;
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio::time::timeout_at;
 use tokio_native_tls::TlsConnector;
-use url::Url;
+use url::{Position, Url};
 
 const HTTP_TIMEOUT: Duration = Duration::from_secs(5);
-const TLS_TIMEOUT: Duration = Duration::from_secs(5);
+const MAX_REDIRECTS: usize = 10;
 
-pub async fn test_http(http: &Http, net_domain: Domain) -> anyhow::Result<()> {
-    let client = reqwest::ClientBuilder::default()
-        .danger_accept_invalid_certs(!http.verify_ssl)
-        .local_address(net_domain.local_address())
-        .timeout(HTTP_TIMEOUT)
-        .user_agent(format!("calpol-test-server {}", env!("CARGO_PKG_VERSION")))
-        .redirect(if http.follow_redirects {
-            redirect::Policy::default()
-        } else {
-            redirect::Policy::none()
-        })
-        .build()
-        .context("Failed to build http client")?;
-    let method = Method::from_str(http.method.as_str()).context("Invalid http method")?;
-    let response = client.request(method, http.url.clone()).send().await?;
+pub async fn test_http(
+    http: &Http,
+    net_domain: Domain,
+    timings: &mut Timings,
+) -> anyhow::Result<()> {
+    let started = Instant::now();
+    let deadline = tokio::time::Instant::now() + HTTP_TIMEOUT;
+    let (response, url) = timeout_at(deadline, send_request(http, net_domain, timings))
+        .await
+        .context("Request timed out")??;
     if !http
         .expected_code
         .map(|expected| expected == response.status().as_u16())
@@ -40,62 +44,302 @@ pub async fn test_http(http: &Http, net_domain: Domain) -> anyhow::Result<()> {
     }
     if http.follow_redirects {
         if let Some(expected) = &http.expected_redirect_destination {
-            if expected != response.url() {
+            if expected != &url {
                 bail!(
                     "Redirects did not match. Expected: {}, Found {}",
                     expected,
-                    response.url()
+                    url
                 )
             }
         }
     }
-    if http.url.scheme() == "https" && http.minimum_certificate_expiry_hours > 0 {
-        do_certificate_test(
-            http.verify_ssl,
-            http.url.clone(),
-            net_domain,
-            http.minimum_certificate_expiry_hours,
-        )
-        .await?;
+    if !http.body_assertions.is_empty() {
+        let (body, truncated) =
+            timeout_at(deadline, read_body(response, http.max_body_size as usize))
+                .await
+                .context("Reading response body timed out")??;
+        timings.total = Some(started.elapsed());
+        for assertion in &http.body_assertions {
+            check_body_assertion(assertion, &body, truncated)?;
+        }
+    } else {
+        timings.total = Some(started.elapsed());
     }
     Ok(())
 }
 
-async fn do_certificate_test(
-    verify: bool,
-    url: Url,
+/// Sends the request, following redirects if enabled, returns the final response and its URL.
+///
+/// Each request is made on a new connection so that the DNS lookup, TCP connect, and TLS
+/// handshake can be timed. When redirected, the time taken by each phase is summed.
+async fn send_request(
+    http: &Http,
     domain: Domain,
-    minimum_certificate_expiry_hours: u16,
-) -> anyhow::Result<()> {
-    let socket = domain.tcp_socket()?;
-    let connector = TlsConnector::from(
-        tokio_native_tls::native_tls::TlsConnector::builder()
-            .danger_accept_invalid_certs(!verify)
-            .build()
-            .context("Failed to build TlsConnector")?,
-    );
+    timings: &mut Timings,
+) -> anyhow::Result<(Response<Body>, Url)> {
+    let mut method = Method::from_str(http.method.as_str()).context("Invalid http method")?;
+    let mut url = http.url.clone();
+    let mut send_body = true;
+    let mut redirects = 0;
+    loop {
+        let mut sender = connect(http, domain, &url, url == http.url, timings).await?;
+        // Credentials are only sent to the original host, as reqwest / browsers do
+        let authenticate = url.host_str() == http.url.host_str();
+        let request = build_request(http, &url, method.clone(), send_body, authenticate)?;
+        let started = Instant::now();
+        let response = sender
+            .send_request(request)
+            .await
+            .context("Failed to send http request")?;
+        add_time(&mut timings.first_byte, started.elapsed());
 
-    let addr = domain.socket_addr_for_url(&url)?;
-    let host = url.host_str().context("URL missing host")?;
+        if !http.follow_redirects || !response.status().is_redirection() {
+            return Ok((response, url));
+        }
+        let location = match response.headers().get(LOCATION) {
+            None => return Ok((response, url)),
+            Some(location) => location
+                .to_str()
+                .context("Invalid redirect location header")?,
+        };
+        redirects += 1;
+        if redirects > MAX_REDIRECTS {
+            bail!("Too many redirects")
+        }
+        url = url.join(location).context("Invalid redirect location")?;
+        if matches!(
+            response.status(),
+            StatusCode::MOVED_PERMANENTLY | StatusCode::FOUND | StatusCode::SEE_OTHER
+        ) && method != Method::HEAD
+        {
+            method = Method::GET;
+            send_body = false;
+        }
+    }
+}
 
-    let stream = timeout(HTTP_TIMEOUT, socket.connect(addr))
+/// Opens a new connection to the URL, recording the time taken by each phase.
+async fn connect(
+    http: &Http,
+    domain: Domain,
+    url: &Url,
+    check_certificate: bool,
+    timings: &mut Timings,
+) -> anyhow::Result<SendRequest<Body>> {
+    let started = Instant::now();
+    let addr = domain.socket_addr_for_url(url).await?;
+    add_time(&mut timings.dns, started.elapsed());
+
+    let socket = domain.tcp_socket()?;
+    let started = Instant::now();
+    let stream = socket
+        .connect(addr)
         .await
-        .context("Socket timed out")?
         .context("Failed to establish TCP stream")?;
+    add_time(&mut timings.connect, started.elapsed());
 
-    let stream = timeout(TLS_TIMEOUT, connector.connect(host, stream))
+    match url.scheme() {
+        "http" => http_handshake(stream).await,
+        "https" => {
+            let connector = TlsConnector::from(
+                tokio_native_tls::native_tls::TlsConnector::builder()
+                    .danger_accept_invalid_certs(!http.verify_ssl)
+                    .build()
+                    .context("Failed to build TlsConnector")?,
+            );
+            let host = url.host_str().context("URL missing host")?;
+
+            let started = Instant::now();
+            let stream = connector
+                .connect(host, stream)
+                .await
+                .context("Failed to establish TLS stream")?;
+            add_time(&mut timings.tls, started.elapsed());
+
+            if check_certificate && http.minimum_certificate_expiry_hours > 0 {
+                let der = stream
+                    .get_ref()
+                    .peer_certificate()
+                    .context("Failed to get peer certificate")?
+                    .context("Server did not provide a certificate")?
+                    .to_der()
+                    .context("Failed to encode peer certificate")?;
+                verify_certificate_expiry(der, http.minimum_certificate_expiry_hours)?;
+            }
+            http_handshake(stream).await
+        }
+        scheme => bail!("Unsupported URL scheme `{}`", scheme),
+    }
+}
+
+async fn http_handshake<S>(stream: S) -> anyhow::Result<SendRequest<Body>>
+where
+    S: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+{
+    let (sender, connection) = handshake(stream)
         .await
-        .context("TLS timed out")?
-        .context("Failed to establish TLS stream")?;
+        .context("Failed to establish HTTP connection")?;
+    tokio::spawn(async move {
+        if let Err(e) = connection.await {
+            log::debug!("HTTP connection error: {}", e);
+        }
+    });
+    Ok(sender)
+}
 
-    let der = stream
-        .get_ref()
-        .peer_certificate()
-        .context("Failed to get peer certificate")?
-        .unwrap()
-        .to_der()
-        .unwrap();
-    verify_certificate_expiry(der, minimum_certificate_expiry_hours)?;
+fn build_request(
+    http: &Http,
+    url: &Url,
+    method: Method,
+    send_body: bool,
+    authenticate: bool,
+) -> anyhow::Result<Request<Body>> {
+    let mut headers = build_headers(http)?;
+    let host = &url[Position::BeforeHost..Position::AfterPort];
+    headers.insert(
+        HOST,
+        HeaderValue::from_str(host).context("Invalid URL host")?,
+    );
+    let user_agent = http
+        .user_agent
+        .clone()
+        .unwrap_or_else(|| format!("calpol-test-server {}", env!("CARGO_PKG_VERSION")));
+    headers.insert(
+        USER_AGENT,
+        HeaderValue::from_str(&user_agent).context("Invalid user agent")?,
+    );
+    headers
+        .entry(ACCEPT)
+        .or_insert_with(|| HeaderValue::from_static("*/*"));
+    if authenticate {
+        let authorization = match &http.authentication {
+            None => None,
+            Some(HttpAuthentication::Basic { username, password }) => Some(format!(
+                "Basic {}",
+                base64::encode(format!(
+                    "{}:{}",
+                    username,
+                    password.as_deref().unwrap_or_default()
+                ))
+            )),
+            Some(HttpAuthentication::Bearer { token }) => Some(format!("Bearer {}", token)),
+        };
+        if let Some(authorization) = authorization {
+            headers.insert(
+                AUTHORIZATION,
+                HeaderValue::from_str(&authorization).context("Invalid authentication")?,
+            );
+        }
+    }
+    let body = match &http.body {
+        Some(HttpRequestBody::Raw {
+            content,
+            content_type,
+        }) if send_body => {
+            if let Some(content_type) = content_type {
+                headers.insert(
+                    CONTENT_TYPE,
+                    HeaderValue::from_str(content_type).context("Invalid content type")?,
+                );
+            }
+            Body::from(content.clone())
+        }
+        Some(HttpRequestBody::Json { content }) if send_body => {
+            headers.insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
+            Body::from(serde_json::to_vec(content).context("Failed to serialize JSON body")?)
+        }
+        _ => Body::empty(),
+    };
+    let mut request = Request::builder()
+        .method(method)
+        .uri(&url[Position::BeforePath..Position::AfterQuery])
+        .body(body)
+        .context("Failed to build http request")?;
+    *request.headers_mut() = headers;
+    Ok(request)
+}
+
+fn add_time(phase: &mut Option<Duration>, elapsed: Duration) {
+    *phase = Some(phase.unwrap_or_default() + elapsed);
+}
+
+fn build_headers(http: &Http) -> anyhow::Result<HeaderMap> {
+    let mut headers = HeaderMap::new();
+    for (name, value) in &http.headers {
+        headers.insert(
+            HeaderName::from_str(name)
+                .with_context(|| format!("Invalid header name `{}`", name))?,
+            HeaderValue::from_str(value)
+                .with_context(|| format!("Invalid value for header `{}`", name))?,
+        );
+    }
+    Ok(headers)
+}
 
+/// Reads the response body up to the maximum size, returns whether the body was truncated.
+async fn read_body(response: Response<Body>, max_size: usize) -> anyhow::Result<(String, bool)> {
+    let mut response_body = response.into_body();
+    let mut body = Vec::new();
+    let mut truncated = false;
+    while let Some(chunk) = response_body.data().await {
+        let chunk = chunk.context("Failed to read response body")?;
+        if body.len() + chunk.len() > max_size {
+            body.extend_from_slice(&chunk[..max_size - body.len()]);
+            truncated = true;
+            break;
+        }
+        body.extend_from_slice(&chunk);
+    }
+    Ok((String::from_utf8_lossy(&body).into_owned(), truncated))
+}
+
+fn check_body_assertion(
+    assertion: &BodyAssertion,
+    body: &str,
+    truncated: bool,
+) -> anyhow::Result<()> {
+    match assertion {
+        BodyAssertion::Contains { value } => {
+            if !body.contains(value.as_str()) {
+                bail!("Response body does not contain `{}`", value)
+            }
+        }
+        BodyAssertion::NotContains { value } => {
+            if body.contains(value.as_str()) {
+                bail!("Response body contains `{}`", value)
+            }
+            // The value could be in the part of the body that wasn't read
+            if truncated {
+                bail!(
+                    "Response body exceeded the maximum size, unable to check it doesn't contain `{}`",
+                    value
+                )
+            }
+        }
+        BodyAssertion::Regex { pattern } => {
+            let regex = Regex::new(pattern).context("Invalid body regex")?;
+            if !regex.is_match(body) {
+                bail!("Response body does not match regex `{}`", pattern)
+            }
+        }
+        BodyAssertion::JsonPointer { pointer, value } => {
+            if truncated {
+                bail!("Response body exceeded the maximum size, unable to parse JSON")
+            }
+            let json = serde_json::from_str::<serde_json::Value>(body)
+                .context("Response body is not valid JSON")?;
+            let found = json
+                .pointer(pointer)
+                .with_context(|| format!("JSON pointer `{}` not found in response", pointer))?;
+            if found != value {
+                bail!(
+                    "JSON pointer `{}` did not match. Expected: {}, Found: {}",
+                    pointer,
+                    value,
+                    found
+                )
+            }
+        }
+    }
     Ok(())
 }
@@ -0,0 +1,228 @@
+use crate::test_runner::runnable::{Domain, Timings};
+use anyhow::{bail, Context};
+use calpol_model::tests::Icmp;
+use socket2::{Domain as SocketDomain, Protocol, Socket, Type};
+use std::net::SocketAddr;
+use std::time::{Duration, Instant};
+use tokio::net::UdpSocket;
+use tokio::time::{sleep_until, timeout_at};
+
+const ECHO_TIMEOUT: Duration = Duration::from_secs(2);
+const ECHO_INTERVAL: Duration = Duration::from_secs(1);
+const ECHO_PAYLOAD: &[u8] = b"calpol-icmp-echo";
+
+const ICMPV4_ECHO_REQUEST: u8 = 8;
+const ICMPV4_ECHO_REPLY: u8 = 0;
+const ICMPV6_ECHO_REQUEST: u8 = 128;
+const ICMPV6_ECHO_REPLY: u8 = 129;
+
+pub async fn test_icmp(
+    icmp: &Icmp,
+    domain: Domain,
+    test_name: &str,
+    timings: &mut Timings,
+) -> anyhow::Result<()> {
+    let started = Instant::now();
+    let addr = domain.resolve(&icmp.host, 0).await?;
+    timings.dns = Some(started.elapsed());
+
+    let socket = IcmpSocket::open(domain)?;
+    let identifier = rand::random::<u16>();
+    let mut round_trips = Vec::new();
+    for sequence in 0..icmp.count as u16 {
+        let sent = Instant::now();
+        socket
+            .send_echo(addr, identifier, sequence)
+            .await
+            .context("Failed to send echo request")?;
+        match socket
+            .receive_echo(addr, identifier, sequence, sent + ECHO_TIMEOUT)
+            .await
+        {
+            Ok(()) => round_trips.push(sent.elapsed()),
+            Err(e) => log::info!("{}: No reply to echo {}: {:#}", test_name, sequence, e),
+        }
+        sleep_until((sent + ECHO_INTERVAL).into()).await;
+    }
+
+    let lost = icmp.count as usize - round_trips.len();
+    let loss_percent = lost * 100 / icmp.count as usize;
+    log::info!(
+        "{}: Received {}/{} echo replies from {}",
+        test_name,
+        round_trips.len(),
+        icmp.count,
+        addr.ip()
+    );
+    if round_trips.is_empty() {
+        bail!("No echo replies received from {}", addr.ip())
+    }
+    if loss_percent > icmp.max_packet_loss_percent as usize {
+        bail!(
+            "Packet loss of {}% exceeded the maximum of {}%",
+            loss_percent,
+            icmp.max_packet_loss_percent
+        )
+    }
+    let average = round_trips.iter().sum::<Duration>() / round_trips.len() as u32;
+    if let Some(max) = icmp.max_average_rtt_ms {
+        if average > Duration::from_millis(max as u64) {
+            bail!(
+                "Average round trip time of {}ms exceeded the maximum of {}ms",
+                average.as_millis(),
+                max
+            )
+        }
+    }
+    Ok(())
+}
+
+struct IcmpSocket {
+    socket: UdpSocket,
+    domain: Domain,
+    /// Raw sockets receive every ICMP packet, so replies need to be matched by identifier.
+    raw: bool,
+}
+
+impl IcmpSocket {
+    /// Prefers unprivileged datagram sockets, falling back to raw sockets if they are permitted.
+    fn open(domain: Domain) -> anyhow::Result<Self> {
+        let (socket_domain, protocol) = match domain {
+            Domain::IpV4 => (SocketDomain::IPV4, Protocol::ICMPV4),
+            Domain::IpV6 => (SocketDomain::IPV6, Protocol::ICMPV6),
+        };
+        let (socket, raw) = match Socket::new(socket_domain, Type::DGRAM, Some(protocol)) {
+            Ok(socket) => (socket, false),
+            Err(dgram_err) => {
+                let socket =
+                    Socket::new(socket_domain, Type::RAW, Some(protocol)).with_context(|| {
+                        format!(
+                            "Failed to open ICMP socket, unprivileged ICMP is not available ({})",
+                            dgram_err
+                        )
+                    })?;
+                (socket, true)
+            }
+        };
+        socket.set_nonblocking(true)?;
+        let socket = UdpSocket::from_std(std::net::UdpSocket::from(socket))?;
+        Ok(Self {
+            socket,
+            domain,
+            raw,
+        })
+    }
+
+    async fn send_echo(
+        &self,
+        addr: SocketAddr,
+        identifier: u16,
+        sequence: u16,
+    ) -> std::io::Result<()> {
+        let mut packet = vec![0; 8];
+        packet[0] = match self.domain {
+            Domain::IpV4 => ICMPV4_ECHO_REQUEST,
+            Domain::IpV6 => ICMPV6_ECHO_REQUEST,
+        };
+        packet[4..6].copy_from_slice(&identifier.to_be_bytes());
+        packet[6..8].copy_from_slice(&sequence.to_be_bytes());
+        packet.extend_from_slice(ECHO_PAYLOAD);
+        // The ICMPv6 checksum covers the IP pseudo header, so it is filled in by the kernel
+        if let Domain::IpV4 = self.domain {
+            let checksum = internet_checksum(&packet);
+            packet[2..4].copy_from_slice(&checksum.to_be_bytes());
+        }
+        self.socket.send_to(&packet, addr).await?;
+        Ok(())
+    }
+
+    async fn receive_echo(
+        &self,
+        addr: SocketAddr,
+        identifier: u16,
+        sequence: u16,
+        deadline: Instant,
+    ) -> anyhow::Result<()> {
+        let mut buf = [0; 1024];
+        loop {
+            let (len, from) = timeout_at(deadline.into(), self.socket.recv_from(&mut buf))
+                .await
+                .context("Timed out")??;
+            if from.ip() != addr.ip() {
+                continue;
+            }
+            let mut packet = &buf[..len];
+            // IPv4 raw sockets (and datagram sockets on some platforms) include the IP header
+            if let Domain::IpV4 = self.domain {
+                if !packet.is_empty() && packet[0] >> 4 == 4 {
+                    let header_len = (packet[0] & 0x0f) as usize * 4;
+                    packet = packet.get(header_len..).unwrap_or_default();
+                }
+            }
+            if packet.len() < 8 {
+                continue;
+            }
+            let reply_type = match self.domain {
+                Domain::IpV4 => ICMPV4_ECHO_REPLY,
+                Domain::IpV6 => ICMPV6_ECHO_REPLY,
+            };
+            // Datagram sockets have their identifier replaced by the kernel
+            let identifier_matches = !self.raw || packet[4..6] == identifier.to_be_bytes();
+            if packet[0] == reply_type
+                && identifier_matches
+                && packet[6..8] == sequence.to_be_bytes()
+            {
+                return Ok(());
+            }
+        }
+    }
+}
+
+/// RFC 1071 checksum.
+fn internet_checksum(data: &[u8]) -> u16 {
+    let mut sum = data
+        .chunks(2)
+        .map(|chunk| match chunk {
+            [a, b] => u16::from_be_bytes([*a, *b]) as u32,
+            [a] => u16::from_be_bytes([*a, 0]) as u32,
+            _ => 0,
+        })
+        .sum::<u32>();
+    while sum >> 16 != 0 {
+        sum = (sum & 0xffff) + (sum >> 16);
+    }
+    !(sum as u16)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::internet_checksum;
+
+    #[test]
+    fn checksum_matches_rfc_1071_example() {
+        let data = [0x00, 0x01, 0xf2, 0x03, 0xf4, 0xf5, 0xf6, 0xf7];
+        assert_eq!(internet_checksum(&data), !0xddf2);
+    }
+
+    #[test]
+    fn odd_length_input_is_padded_with_zero() {
+        assert_eq!(internet_checksum(&[0x01]), !0x0100);
+        assert_eq!(
+            internet_checksum(&[0x12, 0x34, 0x56]),
+            internet_checksum(&[0x12, 0x34, 0x56, 0x00])
+        );
+    }
+
+    #[test]
+    fn packet_including_its_checksum_verifies() {
+        let mut packet = vec![8, 0, 0, 0, 0x12, 0x34, 0x00, 0x01, 0xab, 0xcd, 0xef];
+        let checksum = internet_checksum(&packet);
+        packet[2..4].copy_from_slice(&checksum.to_be_bytes());
+        assert_eq!(internet_checksum(&packet), 0);
+    }
+
+    #[test]
+    fn empty_input() {
+        assert_eq!(internet_checksum(&[]), 0xffff);
+    }
+}
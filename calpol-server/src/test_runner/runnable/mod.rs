@@ -1,33 +1,62 @@
+mod dns;
 mod http;
+mod icmp;
+mod payload;
 mod smtp;
 mod tcp;
+mod tls;
+mod udp;
 
+pub use payload::{payload_bytes, ResponseMatcher};
+
+use crate::test_runner::runnable::dns::test_dns;
 use crate::test_runner::runnable::http::test_http;
+use crate::test_runner::runnable::icmp::test_icmp;
 use crate::test_runner::runnable::smtp::test_smtp;
 use crate::test_runner::runnable::tcp::test_tcp;
+use crate::test_runner::runnable::tls::test_tls;
+use crate::test_runner::runnable::udp::test_udp;
 use anyhow::{bail, Context};
 use async_trait::async_trait;
 use calpol_model::tests::{IpVersion, TestConfig, TestVariant};
 use chrono::Duration;
 use std::fmt::{Display, Formatter};
 use std::net::{IpAddr, SocketAddr};
-use tokio::net::TcpSocket;
+use std::time::{Duration as StdDuration, Instant};
+use tokio::net::{lookup_host, TcpSocket};
 use url::Url;
 use x509_parser::certificate::X509Certificate;
 use x509_parser::traits::FromDer;
 
 #[async_trait]
 pub trait Runnable {
-    async fn run(&self, test_name: &str) -> anyhow::Result<()>;
+    /// Runs the test, recording the time taken by each phase (even if the test fails).
+    async fn run(&self, test_name: &str, timings: &mut Timings) -> anyhow::Result<()>;
 }
 
 #[async_trait]
 impl Runnable for TestConfig {
-    async fn run(&self, test_name: &str) -> anyhow::Result<()> {
+    async fn run(&self, test_name: &str, timings: &mut Timings) -> anyhow::Result<()> {
         for net_domain in Domain::from_model(self.ip_version) {
-            run_variant(&self.variant, net_domain, test_name)
-                .await
-                .context(format!("({})", net_domain))?
+            let mut domain_timings = Timings::default();
+            let started = Instant::now();
+            let result =
+                run_variant(&self.variant, net_domain, test_name, &mut domain_timings).await;
+            let total = *domain_timings
+                .total
+                .get_or_insert_with(|| started.elapsed());
+            timings.merge(domain_timings);
+            result.context(format!("({})", net_domain))?;
+            if let Some(max) = self.max_response_time_ms {
+                if total > StdDuration::from_millis(max as u64) {
+                    bail!(
+                        "({}) Response time of {}ms exceeded the maximum of {}ms",
+                        net_domain,
+                        total.as_millis(),
+                        max
+                    )
+                }
+            }
         }
         Ok(())
     }
@@ -37,15 +66,41 @@ async fn run_variant(
     variant: &TestVariant,
     net_domain: Domain,
     test_name: &str,
+    timings: &mut Timings,
 ) -> anyhow::Result<()> {
     match &variant {
-        TestVariant::Http(http) => test_http(http, net_domain).await?,
-        TestVariant::Smtp(smtp) => test_smtp(smtp, net_domain, test_name).await?,
-        TestVariant::Tcp(tcp) => test_tcp(tcp, net_domain).await?,
+        TestVariant::Http(http) => test_http(http, net_domain, timings).await?,
+        TestVariant::Smtp(smtp) => test_smtp(smtp, net_domain, test_name, timings).await?,
+        TestVariant::Tcp(tcp) => test_tcp(tcp, net_domain, test_name, timings).await?,
+        TestVariant::Dns(dns) => test_dns(dns, net_domain, test_name, timings).await?,
+        TestVariant::Icmp(icmp) => test_icmp(icmp, net_domain, test_name, timings).await?,
+        TestVariant::Tls(tls) => test_tls(tls, net_domain, test_name, timings).await?,
+        TestVariant::Udp(udp) => test_udp(udp, net_domain, test_name, timings).await?,
     }
     Ok(())
 }
 
+/// Time taken by each phase of a test, phases that don't apply to the test type are left empty.
+#[derive(Default, Debug, Clone, Copy)]
+pub struct Timings {
+    pub dns: Option<StdDuration>,
+    pub connect: Option<StdDuration>,
+    pub tls: Option<StdDuration>,
+    pub first_byte: Option<StdDuration>,
+    pub total: Option<StdDuration>,
+}
+
+impl Timings {
+    /// Keeps the slowest time of each phase, when a test is run over both IPv4 and IPv6.
+    fn merge(&mut self, other: Timings) {
+        self.dns = self.dns.max(other.dns);
+        self.connect = self.connect.max(other.connect);
+        self.tls = self.tls.max(other.tls);
+        self.first_byte = self.first_byte.max(other.first_byte);
+        self.total = self.total.max(other.total);
+    }
+}
+
 #[derive(Clone, Copy, Debug)]
 pub enum Domain {
     IpV4,
@@ -69,15 +124,24 @@ impl Domain {
         }
     }
 
-    fn socket_addr_for_url(self, url: &Url) -> anyhow::Result<SocketAddr> {
-        url.socket_addrs(|| None)
-            .context("Failed to resolve socket address")?
-            .into_iter()
+    /// Resolves the host to an address of this domain, without blocking the runtime.
+    async fn resolve(self, host: &str, port: u16) -> anyhow::Result<SocketAddr> {
+        lookup_host((host, port))
+            .await
+            .context("Failed to resolve host")?
             .find(|addr| match self {
                 Domain::IpV4 => addr.is_ipv4(),
                 Domain::IpV6 => addr.is_ipv6(),
             })
-            .context("Failed to resolve socket address")
+            .context("Failed to resolve host")
+    }
+
+    async fn socket_addr_for_url(self, url: &Url) -> anyhow::Result<SocketAddr> {
+        let host = url.host_str().context("URL missing host")?;
+        // IPv6 addresses are enclosed in brackets in URLs
+        let host = host.trim_start_matches('[').trim_end_matches(']');
+        let port = url.port_or_known_default().context("URL missing port")?;
+        self.resolve(host, port).await
     }
 
     fn tcp_socket(self) -> anyhow::Result<TcpSocket> {
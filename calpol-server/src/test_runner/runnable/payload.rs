@@ -0,0 +1,61 @@
+use anyhow::Context;
+use calpol_model::tests::{Payload, ResponseMatch};
+use regex::bytes::Regex;
+use std::convert::TryFrom;
+use std::fmt::{Display, Formatter};
+
+pub fn payload_bytes(payload: &Payload) -> anyhow::Result<Vec<u8>> {
+    Ok(match payload {
+        Payload::Text(text) => text.as_bytes().to_vec(),
+        Payload::Hex(hex) => decode_hex(hex).context("Invalid hex payload")?,
+    })
+}
+
+/// Strips whitespace so that long values can be grouped, e.g. `ff ff ff ff`.
+fn decode_hex(value: &str) -> Result<Vec<u8>, hex::FromHexError> {
+    hex::decode(value.split_whitespace().collect::<String>())
+}
+
+/// A [ResponseMatch] with its regex compiled / hex decoded.
+pub enum ResponseMatcher {
+    Contains(Vec<u8>),
+    Regex(Regex),
+}
+
+impl TryFrom<&ResponseMatch> for ResponseMatcher {
+    type Error = anyhow::Error;
+
+    fn try_from(value: &ResponseMatch) -> Result<Self, Self::Error> {
+        Ok(match value {
+            ResponseMatch::Contains { value } => Self::Contains(value.as_bytes().to_vec()),
+            ResponseMatch::Regex { pattern } => {
+                Self::Regex(Regex::new(pattern).context("Invalid response regex")?)
+            }
+            ResponseMatch::Hex { value } => {
+                Self::Contains(decode_hex(value).context("Invalid response hex")?)
+            }
+        })
+    }
+}
+
+impl ResponseMatcher {
+    pub fn is_match(&self, response: &[u8]) -> bool {
+        match self {
+            ResponseMatcher::Contains(needle) => {
+                needle.is_empty() || response.windows(needle.len()).any(|w| w == needle)
+            }
+            ResponseMatcher::Regex(regex) => regex.is_match(response),
+        }
+    }
+}
+
+impl Display for ResponseMatcher {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ResponseMatcher::Contains(needle) => {
+                write!(f, "`{}`", String::from_utf8_lossy(needle).escape_debug())
+            }
+            ResponseMatcher::Regex(regex) => write!(f, "regex `{}`", regex),
+        }
+    }
+}
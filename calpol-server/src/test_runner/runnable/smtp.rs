@@ -1,17 +1,26 @@
-use crate::test_runner::runnable::{verify_certificate_expiry, Domain};
+use crate::test_runner::runnable::{verify_certificate_expiry, Domain, Timings};
 use anyhow::{bail, Context};
 use calpol_model::tests::{Smtp, SmtpEncryption, SmtpServerType};
 use lettre::transport::smtp::client::{AsyncSmtpConnection, TlsParameters};
 use lettre::transport::smtp::extension::ClientId;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 use trust_dns_resolver::config::{ResolverConfig, ResolverOpts};
 use trust_dns_resolver::AsyncResolver;
 
 const SMTP_CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
 const DNS_TIMEOUT: Duration = Duration::from_secs(5);
 
-pub async fn test_smtp(smtp: &Smtp, domain: Domain, test_name: &str) -> anyhow::Result<()> {
+pub async fn test_smtp(
+    smtp: &Smtp,
+    domain: Domain,
+    test_name: &str,
+    timings: &mut Timings,
+) -> anyhow::Result<()> {
+    let started = Instant::now();
     let host = get_host(smtp).await?;
+    if let SmtpServerType::MailTransferAgent = smtp.r#type {
+        timings.dns = Some(started.elapsed());
+    }
     let port = get_port(smtp);
     log::info!("{}: Connecting to {}:{}", test_name, host, port);
     let client_id = ClientId::default();
@@ -20,6 +29,8 @@ pub async fn test_smtp(smtp: &Smtp, domain: Domain, test_name: &str) -> anyhow::
     } else {
         None
     };
+    // Includes the TLS handshake when using SMTPS
+    let started = Instant::now();
     let mut connection = AsyncSmtpConnection::connect_tokio1(
         (host.clone(), port),
         Some(SMTP_CONNECT_TIMEOUT),
@@ -29,12 +40,14 @@ pub async fn test_smtp(smtp: &Smtp, domain: Domain, test_name: &str) -> anyhow::
     )
     .await
     .context("Failed to connect to the smtp server")?;
+    timings.connect = Some(started.elapsed());
     log::info!(
         "{}: Server banner {}",
         test_name,
         connection.server_info().name()
     );
     if let SmtpEncryption::STARTTLS = smtp.encryption {
+        let started = Instant::now();
         connection
             .starttls(
                 TlsParameters::new(host.clone()).context("Failed to build tls parameters")?,
@@ -42,6 +55,7 @@ pub async fn test_smtp(smtp: &Smtp, domain: Domain, test_name: &str) -> anyhow::
             )
             .await
             .context("Failed to starttls")?;
+        timings.tls = Some(started.elapsed());
     }
 
     if !connection.test_connected().await {
@@ -1,21 +1,69 @@
-use crate::test_runner::runnable::Domain;
-use anyhow::Context;
+use crate::test_runner::runnable::payload::{payload_bytes, ResponseMatcher};
+use crate::test_runner::runnable::{Domain, Timings};
+use anyhow::{bail, Context};
 use calpol_model::tests::Tcp;
-use std::time::Duration;
-use tokio::time::timeout;
-use url::Url;
+use std::convert::TryFrom;
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::time::{timeout, timeout_at};
 
 const TCP_TIMEOUT: Duration = Duration::from_secs(5);
+const MAX_RESPONSE_SIZE: usize = 64 * 1024;
 
-pub async fn test_tcp(tcp: &Tcp, domain: Domain) -> anyhow::Result<()> {
-    let url = Url::parse(&format!("tcp://{}:{}", tcp.host, tcp.port)).context("Invalid host")?;
-    let addr = domain.socket_addr_for_url(&url)?;
+pub async fn test_tcp(
+    tcp: &Tcp,
+    domain: Domain,
+    test_name: &str,
+    timings: &mut Timings,
+) -> anyhow::Result<()> {
+    let started = Instant::now();
+    let addr = domain.resolve(&tcp.host, tcp.port).await?;
+    timings.dns = Some(started.elapsed());
 
     let socket = domain.tcp_socket()?;
-    timeout(TCP_TIMEOUT, socket.connect(addr))
+    let started = Instant::now();
+    let mut stream = timeout(TCP_TIMEOUT, socket.connect(addr))
         .await
         .context("Socket timed out")?
         .context("Failed to establish TCP stream")?;
+    timings.connect = Some(started.elapsed());
 
-    Ok(())
+    let started = Instant::now();
+    if let Some(payload) = &tcp.send {
+        let payload = payload_bytes(payload)?;
+        timeout(TCP_TIMEOUT, stream.write_all(&payload))
+            .await
+            .context("Send timed out")?
+            .context("Failed to send payload")?;
+    }
+
+    let expect = match &tcp.expect {
+        None => return Ok(()),
+        Some(expect) => ResponseMatcher::try_from(expect)?,
+    };
+    let deadline = started + Duration::from_millis(tcp.response_timeout_ms as u64);
+    let mut response = Vec::new();
+    let mut buf = [0; 4096];
+    loop {
+        let read = timeout_at(deadline.into(), stream.read(&mut buf))
+            .await
+            .with_context(|| format!("Timed out waiting for response matching {}", expect))?
+            .context("Failed to read response")?;
+        if read == 0 {
+            bail!("Connection closed before response matched {}", expect)
+        }
+        timings.first_byte.get_or_insert_with(|| started.elapsed());
+        response.extend_from_slice(&buf[..read]);
+        if expect.is_match(&response) {
+            log::info!("{}: Response matched {}", test_name, expect);
+            return Ok(());
+        }
+        if response.len() >= MAX_RESPONSE_SIZE {
+            bail!(
+                "Response exceeded {} bytes without matching {}",
+                MAX_RESPONSE_SIZE,
+                expect
+            )
+        }
+    }
 }
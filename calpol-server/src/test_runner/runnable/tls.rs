@@ -0,0 +1,145 @@
+use crate::test_runner::runnable::{verify_certificate_expiry, Domain, Timings};
+use anyhow::{bail, Context};
+use calpol_model::tests::{Tls, TlsVersion};
+use std::net::IpAddr;
+use std::time::{Duration, Instant};
+use tokio::time::timeout;
+use tokio_native_tls::native_tls::{Protocol, TlsConnector as NativeTlsConnector};
+use tokio_native_tls::TlsConnector;
+use x509_parser::certificate::X509Certificate;
+use x509_parser::extensions::GeneralName;
+use x509_parser::traits::FromDer;
+
+const TCP_TIMEOUT: Duration = Duration::from_secs(5);
+const TLS_TIMEOUT: Duration = Duration::from_secs(5);
+
+pub async fn test_tls(
+    tls: &Tls,
+    domain: Domain,
+    test_name: &str,
+    timings: &mut Timings,
+) -> anyhow::Result<()> {
+    let started = Instant::now();
+    let addr = domain.resolve(&tls.host, tls.port).await?;
+    timings.dns = Some(started.elapsed());
+
+    let socket = domain.tcp_socket()?;
+    let started = Instant::now();
+    let stream = timeout(TCP_TIMEOUT, socket.connect(addr))
+        .await
+        .context("Socket timed out")?
+        .context("Failed to establish TCP stream")?;
+    timings.connect = Some(started.elapsed());
+
+    // Hostname verification is done separately, so that it can be disabled independently
+    let connector = TlsConnector::from(
+        NativeTlsConnector::builder()
+            .danger_accept_invalid_certs(!tls.verify_chain)
+            .danger_accept_invalid_hostnames(true)
+            .min_protocol_version(tls.minimum_protocol_version.map(to_protocol))
+            .build()
+            .context("Failed to build TlsConnector")?,
+    );
+    let server_name = tls.server_name.as_deref().unwrap_or(&tls.host);
+    let started = Instant::now();
+    let stream = timeout(TLS_TIMEOUT, connector.connect(server_name, stream))
+        .await
+        .context("TLS timed out")?
+        .context("Failed to establish TLS stream")?;
+    timings.tls = Some(started.elapsed());
+
+    let der = stream
+        .get_ref()
+        .peer_certificate()
+        .context("Failed to get peer certificate")?
+        .context("Server did not present a certificate")?
+        .to_der()
+        .context("Failed to encode peer certificate")?;
+    if tls.verify_hostname {
+        verify_certificate_hostname(&der, server_name)?;
+    }
+    if tls.minimum_certificate_expiry_hours > 0 {
+        verify_certificate_expiry(der, tls.minimum_certificate_expiry_hours)?;
+    }
+    log::info!(
+        "{}: Established TLS connection to {} ({})",
+        test_name,
+        addr,
+        server_name
+    );
+    Ok(())
+}
+
+fn to_protocol(version: TlsVersion) -> Protocol {
+    match version {
+        TlsVersion::Tls10 => Protocol::Tlsv10,
+        TlsVersion::Tls11 => Protocol::Tlsv11,
+        TlsVersion::Tls12 => Protocol::Tlsv12,
+    }
+}
+
+fn verify_certificate_hostname(der: &[u8], server_name: &str) -> anyhow::Result<()> {
+    let cert = X509Certificate::from_der(der)
+        .context("Failed to parse certificate")?
+        .1;
+    let (_, san) = cert
+        .tbs_certificate
+        .subject_alternative_name()
+        .context("Certificate has no subject alternative names")?;
+    let ip = server_name.parse::<IpAddr>().ok();
+    let matches = san.general_names.iter().any(|name| match (name, ip) {
+        (GeneralName::DNSName(dns_name), None) => dns_name_matches(dns_name, server_name),
+        (GeneralName::IPAddress(bytes), Some(IpAddr::V4(ip))) => *bytes == ip.octets(),
+        (GeneralName::IPAddress(bytes), Some(IpAddr::V6(ip))) => *bytes == ip.octets(),
+        _ => false,
+    });
+    if !matches {
+        bail!("Certificate is not valid for {}", server_name)
+    }
+    Ok(())
+}
+
+/// Wildcards are only permitted as the entire left-most label (RFC 6125).
+fn dns_name_matches(pattern: &str, name: &str) -> bool {
+    let pattern = pattern.trim_end_matches('.').to_ascii_lowercase();
+    let name = name.trim_end_matches('.').to_ascii_lowercase();
+    match pattern.strip_prefix("*.") {
+        None => pattern == name,
+        Some(suffix) => match name.split_once('.') {
+            Some((label, rest)) => !label.is_empty() && rest == suffix,
+            None => false,
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::dns_name_matches;
+
+    #[test]
+    fn exact_names_match_ignoring_case_and_trailing_dot() {
+        assert!(dns_name_matches("example.com", "example.com"));
+        assert!(dns_name_matches("Example.COM", "example.com"));
+        assert!(dns_name_matches("example.com.", "example.com"));
+        assert!(dns_name_matches("example.com", "example.com."));
+        assert!(!dns_name_matches("example.com", "www.example.com"));
+        assert!(!dns_name_matches("example.com", "example.org"));
+    }
+
+    #[test]
+    fn wildcard_matches_a_single_label() {
+        assert!(dns_name_matches("*.example.com", "www.example.com"));
+        assert!(dns_name_matches("*.example.com", "WWW.Example.com."));
+        assert!(!dns_name_matches("*.example.com", "a.b.example.com"));
+        assert!(!dns_name_matches("*.example.com", "example.com"));
+        assert!(!dns_name_matches("*.example.com", ".example.com"));
+        assert!(!dns_name_matches("*.example.com", "www.example.org"));
+    }
+
+    #[test]
+    fn partial_wildcards_are_not_supported() {
+        assert!(!dns_name_matches("w*.example.com", "www.example.com"));
+        assert!(!dns_name_matches("www.*.com", "www.example.com"));
+        assert!(!dns_name_matches("*", "com"));
+    }
+}
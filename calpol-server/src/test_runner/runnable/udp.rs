@@ -0,0 +1,60 @@
+use crate::test_runner::runnable::payload::{payload_bytes, ResponseMatcher};
+use crate::test_runner::runnable::{Domain, Timings};
+use anyhow::{bail, Context};
+use calpol_model::tests::Udp;
+use std::convert::TryFrom;
+use std::net::SocketAddr;
+use std::time::{Duration, Instant};
+use tokio::net::UdpSocket;
+use tokio::time::timeout;
+
+const MAX_DATAGRAM_SIZE: usize = 65535;
+
+pub async fn test_udp(
+    udp: &Udp,
+    domain: Domain,
+    test_name: &str,
+    timings: &mut Timings,
+) -> anyhow::Result<()> {
+    let started = Instant::now();
+    let addr = domain.resolve(&udp.host, udp.port).await?;
+    timings.dns = Some(started.elapsed());
+
+    let expect = udp
+        .expect
+        .as_ref()
+        .map(ResponseMatcher::try_from)
+        .transpose()?;
+    let payload = payload_bytes(&udp.send)?;
+    // Connecting filters out datagrams from other addresses, and surfaces ICMP unreachable errors
+    let socket = UdpSocket::bind(SocketAddr::new(domain.local_address(), 0))
+        .await
+        .context("Failed to bind UDP socket")?;
+    socket
+        .connect(addr)
+        .await
+        .context("Failed to connect UDP socket")?;
+
+    let started = Instant::now();
+    socket
+        .send(&payload)
+        .await
+        .context("Failed to send payload")?;
+    let mut buf = vec![0; MAX_DATAGRAM_SIZE];
+    let len = timeout(
+        Duration::from_millis(udp.response_timeout_ms as u64),
+        socket.recv(&mut buf),
+    )
+    .await
+    .context("Timed out waiting for reply")?
+    .context("Failed to receive reply")?;
+    timings.first_byte = Some(started.elapsed());
+    log::info!("{}: Received {} byte reply from {}", test_name, len, addr);
+
+    if let Some(expect) = expect {
+        if !expect.is_match(&buf[..len]) {
+            bail!("Reply did not match {}", expect)
+        }
+    }
+    Ok(())
+}
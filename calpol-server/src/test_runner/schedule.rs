@@ -0,0 +1,407 @@
+use crate::database::Test;
+use crate::settings::RunnerSetting;
+use anyhow::{bail, Context};
+use calpol_model::tests::{ActiveHours, Schedule, Weekday};
+use chrono::{DateTime, Datelike, NaiveTime, Utc};
+use chrono_tz::Tz;
+use std::collections::{HashMap, HashSet};
+use std::convert::TryFrom;
+use std::str::FromStr;
+use std::time::Duration;
+
+impl Test {
+    /// How frequently the test should be run, falling back to the runner interval.
+    pub fn interval_duration(&self, runner: &RunnerSetting) -> Duration {
+        self.run_interval
+            .map(|minutes| Duration::from_secs(minutes as u64 * 60))
+            .unwrap_or_else(|| runner.interval_duration())
+    }
+
+    /// Maximum time the test is allowed to run for, if it has its own timeout.
+    pub fn timeout_duration(&self) -> Option<Duration> {
+        self.run_timeout
+            .map(|seconds| Duration::from_secs(seconds as u64))
+    }
+}
+
+/// A test [Schedule] that has been parsed and validated.
+pub struct ParsedSchedule {
+    cron: Option<cron::Schedule>,
+    active_hours: Vec<ActiveWindow>,
+    timezone: Tz,
+}
+
+struct ActiveWindow {
+    days: Vec<chrono::Weekday>,
+    start: NaiveTime,
+    end: NaiveTime,
+}
+
+impl TryFrom<&Schedule> for ParsedSchedule {
+    type Error = anyhow::Error;
+
+    fn try_from(schedule: &Schedule) -> Result<Self, Self::Error> {
+        let cron = schedule.cron.as_deref().map(parse_cron).transpose()?;
+        let active_hours = schedule
+            .active_hours
+            .iter()
+            .map(ActiveWindow::try_from)
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        let timezone = Tz::from_str(&schedule.timezone)
+            .map_err(anyhow::Error::msg)
+            .context("Invalid timezone")?;
+        Ok(Self {
+            cron,
+            active_hours,
+            timezone,
+        })
+    }
+}
+
+impl ParsedSchedule {
+    /// Whether the cron expression has fired since the test was last run.
+    /// Returns `None` if the schedule doesn't have a cron expression.
+    fn cron_fired(&self, last_run: DateTime<Utc>, now: DateTime<Utc>) -> Option<bool> {
+        self.cron.as_ref().map(|cron| {
+            cron.after(&last_run.with_timezone(&self.timezone))
+                .next()
+                .map(|next| next.with_timezone(&Utc) <= now)
+                .unwrap_or(false)
+        })
+    }
+
+    fn is_active(&self, now: DateTime<Utc>) -> bool {
+        if self.active_hours.is_empty() {
+            return true;
+        }
+        let now = now.with_timezone(&self.timezone);
+        let (day, time) = (now.weekday(), now.time());
+        self.active_hours.iter().any(|window| {
+            if window.start <= window.end {
+                window.days.contains(&day) && window.start <= time && time < window.end
+            } else {
+                // The window runs overnight, so the end belongs to the previous day
+                (window.days.contains(&day) && window.start <= time)
+                    || (window.days.contains(&day.pred()) && time < window.end)
+            }
+        })
+    }
+}
+
+impl TryFrom<&ActiveHours> for ActiveWindow {
+    type Error = anyhow::Error;
+
+    fn try_from(active_hours: &ActiveHours) -> Result<Self, Self::Error> {
+        let parse_time = |time: &str| {
+            NaiveTime::parse_from_str(time, "%H:%M")
+                .with_context(|| format!("Invalid time `{}`, expected HH:MM", time))
+        };
+        Ok(Self {
+            days: active_hours
+                .days
+                .iter()
+                .map(|d| to_chrono_weekday(*d))
+                .collect(),
+            start: parse_time(&active_hours.start)?,
+            end: parse_time(&active_hours.end)?,
+        })
+    }
+}
+
+/// Parses a cron expression, the seconds field is optional.
+fn parse_cron(expression: &str) -> anyhow::Result<cron::Schedule> {
+    let expression = match expression.split_whitespace().count() {
+        5 => format!("0 {}", expression),
+        6 | 7 => expression.to_string(),
+        _ => bail!("Invalid cron expression `{}`", expression),
+    };
+    cron::Schedule::from_str(&expression)
+        .with_context(|| format!("Invalid cron expression `{}`", expression))
+}
+
+fn to_chrono_weekday(day: Weekday) -> chrono::Weekday {
+    match day {
+        Weekday::Mon => chrono::Weekday::Mon,
+        Weekday::Tue => chrono::Weekday::Tue,
+        Weekday::Wed => chrono::Weekday::Wed,
+        Weekday::Thu => chrono::Weekday::Thu,
+        Weekday::Fri => chrono::Weekday::Fri,
+        Weekday::Sat => chrono::Weekday::Sat,
+        Weekday::Sun => chrono::Weekday::Sun,
+    }
+}
+
+pub enum Status {
+    /// The test has been run recently enough.
+    NotDue,
+    /// The test should be run.
+    Due,
+    /// The test is due, but the schedule doesn't allow it to be run right now. It remains due, so
+    /// it will be run on the first tick that it is active.
+    Inactive,
+}
+
+/// Keeps track of when each test was last run, so that tests can run on their own schedule.
+#[derive(Default)]
+pub struct Scheduler {
+    last_run: HashMap<i32, DateTime<Utc>>,
+    /// Tests that have been skipped for being inactive since they were last run.
+    skipped: HashSet<i32>,
+}
+
+impl Scheduler {
+    pub fn status(
+        &self,
+        test: &Test,
+        schedule: Option<&ParsedSchedule>,
+        runner: &RunnerSetting,
+        now: DateTime<Utc>,
+    ) -> Status {
+        let due = match self.last_run.get(&test.id) {
+            None => true,
+            Some(last_run) => schedule
+                .and_then(|s| s.cron_fired(*last_run, now))
+                .unwrap_or_else(|| {
+                    (now - *last_run)
+                        .to_std()
+                        .map(|elapsed| elapsed >= test.interval_duration(runner))
+                        .unwrap_or(false)
+                }),
+        };
+        match (due, schedule) {
+            (false, _) => Status::NotDue,
+            (true, Some(s)) if !s.is_active(now) => Status::Inactive,
+            (true, _) => Status::Due,
+        }
+    }
+
+    pub fn mark_run(&mut self, test: &Test, now: DateTime<Utc>) {
+        self.last_run.insert(test.id, now);
+        self.skipped.remove(&test.id);
+    }
+
+    /// Records that an inactive test was skipped, returns `false` if it has already been skipped
+    /// since it was last due, so that each skipped run is only counted once.
+    pub fn mark_skipped(&mut self, test: &Test) -> bool {
+        self.skipped.insert(test.id)
+    }
+
+    /// Forget when tests were last run, so that every test is due on the next tick.
+    pub fn reset(&mut self) {
+        self.last_run.clear();
+        self.skipped.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn window(days: Vec<Weekday>, start: &str, end: &str) -> ActiveHours {
+        ActiveHours {
+            days,
+            start: start.to_string(),
+            end: end.to_string(),
+        }
+    }
+
+    fn parse(cron: Option<&str>, active_hours: Vec<ActiveHours>, timezone: &str) -> ParsedSchedule {
+        ParsedSchedule::try_from(&Schedule {
+            cron: cron.map(str::to_string),
+            active_hours,
+            timezone: timezone.to_string(),
+        })
+        .unwrap()
+    }
+
+    fn test(id: i32, run_interval: Option<i32>) -> Test {
+        Test {
+            id,
+            name: format!("test_{}", id),
+            enabled: true,
+            config: serde_json::Value::Null,
+            failing: false,
+            failure_threshold: 2,
+            run_interval,
+            run_timeout: None,
+            tags: Vec::new(),
+            severity: "medium".to_string(),
+        }
+    }
+
+    fn runner() -> RunnerSetting {
+        RunnerSetting {
+            interval: 15,
+            timeout: 10,
+            concurrency: 4,
+            log_age: 30,
+        }
+    }
+
+    // 2022-06-06 is a Monday
+    fn utc(day: u32, hour: u32, minute: u32) -> DateTime<Utc> {
+        Utc.ymd(2022, 6, day).and_hms(hour, minute, 0)
+    }
+
+    #[test]
+    fn no_active_hours_is_always_active() {
+        let schedule = parse(None, vec![], "UTC");
+        assert!(schedule.is_active(utc(6, 3, 0)));
+    }
+
+    #[test]
+    fn daytime_window() {
+        let schedule = parse(
+            None,
+            vec![window(vec![Weekday::Mon], "09:00", "17:00")],
+            "UTC",
+        );
+        assert!(!schedule.is_active(utc(6, 8, 59)));
+        assert!(schedule.is_active(utc(6, 9, 0)));
+        assert!(schedule.is_active(utc(6, 16, 59)));
+        assert!(!schedule.is_active(utc(6, 17, 0)));
+        assert!(!schedule.is_active(utc(7, 12, 0)));
+    }
+
+    #[test]
+    fn overnight_window_ends_the_next_day() {
+        let schedule = parse(
+            None,
+            vec![window(vec![Weekday::Fri], "22:00", "06:00")],
+            "UTC",
+        );
+        assert!(!schedule.is_active(utc(10, 21, 59)));
+        assert!(schedule.is_active(utc(10, 22, 0)));
+        assert!(schedule.is_active(utc(11, 5, 59)));
+        assert!(!schedule.is_active(utc(11, 6, 0)));
+        // The early hours of Friday belong to Thursday's window
+        assert!(!schedule.is_active(utc(10, 5, 0)));
+        assert!(!schedule.is_active(utc(11, 22, 0)));
+    }
+
+    #[test]
+    fn window_is_evaluated_in_the_timezone() {
+        // London is UTC+1 in June
+        let schedule = parse(
+            None,
+            vec![window(vec![Weekday::Mon], "09:00", "17:00")],
+            "Europe/London",
+        );
+        assert!(!schedule.is_active(utc(6, 7, 59)));
+        assert!(schedule.is_active(utc(6, 8, 0)));
+        assert!(!schedule.is_active(utc(6, 16, 0)));
+    }
+
+    #[test]
+    fn timezone_can_move_the_window_to_another_utc_day() {
+        // 01:00 UTC on Tuesday is 21:00 on Monday in New York
+        let schedule = parse(
+            None,
+            vec![window(vec![Weekday::Mon], "20:00", "23:00")],
+            "America/New_York",
+        );
+        assert!(schedule.is_active(utc(7, 1, 0)));
+        assert!(!schedule.is_active(utc(6, 21, 0)));
+    }
+
+    #[test]
+    fn cron_fired_since_last_run() {
+        let schedule = parse(Some("0 9 * * *"), vec![], "UTC");
+        assert_eq!(
+            schedule.cron_fired(utc(6, 8, 0), utc(6, 8, 59)),
+            Some(false)
+        );
+        assert_eq!(schedule.cron_fired(utc(6, 8, 0), utc(6, 9, 0)), Some(true));
+        assert_eq!(
+            schedule.cron_fired(utc(6, 9, 0), utc(6, 12, 0)),
+            Some(false)
+        );
+        assert_eq!(
+            parse(None, vec![], "UTC").cron_fired(utc(6, 8, 0), utc(6, 9, 0)),
+            None
+        );
+    }
+
+    #[test]
+    fn cron_seconds_field_is_optional() {
+        assert!(parse_cron("*/5 9-17 * * MON-FRI").is_ok());
+        assert!(parse_cron("0 */5 9-17 * * MON-FRI").is_ok());
+        assert!(parse_cron("* * *").is_err());
+        assert!(parse_cron("not a cron expression").is_err());
+    }
+
+    #[test]
+    fn invalid_schedules_are_rejected() {
+        let invalid_time = Schedule {
+            cron: None,
+            active_hours: vec![window(vec![Weekday::Mon], "9am", "17:00")],
+            timezone: "UTC".to_string(),
+        };
+        assert!(ParsedSchedule::try_from(&invalid_time).is_err());
+        let invalid_timezone = Schedule {
+            cron: None,
+            active_hours: vec![],
+            timezone: "Mars/Olympus_Mons".to_string(),
+        };
+        assert!(ParsedSchedule::try_from(&invalid_timezone).is_err());
+    }
+
+    #[test]
+    fn inactive_test_stays_due_until_active() {
+        let schedule = parse(
+            None,
+            vec![window(vec![Weekday::Mon], "09:00", "10:00")],
+            "UTC",
+        );
+        let (test, runner) = (test(1, Some(60 * 24)), runner());
+        let mut scheduler = Scheduler::default();
+        scheduler.mark_run(&test, utc(5, 8, 0));
+
+        let now = utc(6, 8, 0);
+        assert!(matches!(
+            scheduler.status(&test, Some(&schedule), &runner, now),
+            Status::Inactive
+        ));
+        assert!(scheduler.mark_skipped(&test));
+        // Skipping doesn't advance the schedule, but is only counted once
+        assert!(matches!(
+            scheduler.status(&test, Some(&schedule), &runner, utc(6, 8, 1)),
+            Status::Inactive
+        ));
+        assert!(!scheduler.mark_skipped(&test));
+        assert!(matches!(
+            scheduler.status(&test, Some(&schedule), &runner, utc(6, 9, 0)),
+            Status::Due
+        ));
+
+        scheduler.mark_run(&test, utc(6, 9, 0));
+        assert!(matches!(
+            scheduler.status(&test, Some(&schedule), &runner, utc(6, 9, 1)),
+            Status::NotDue
+        ));
+        assert!(scheduler.mark_skipped(&test));
+    }
+
+    #[test]
+    fn test_interval_falls_back_to_runner_interval() {
+        let runner = runner();
+        let mut scheduler = Scheduler::default();
+        let (own_interval, runner_interval) = (test(1, Some(5)), test(2, None));
+        assert!(matches!(
+            scheduler.status(&own_interval, None, &runner, utc(6, 9, 0)),
+            Status::Due
+        ));
+        scheduler.mark_run(&own_interval, utc(6, 9, 0));
+        scheduler.mark_run(&runner_interval, utc(6, 9, 0));
+        let now = utc(6, 9, 5);
+        assert!(matches!(
+            scheduler.status(&own_interval, None, &runner, now),
+            Status::Due
+        ));
+        assert!(matches!(
+            scheduler.status(&runner_interval, None, &runner, now),
+            Status::NotDue
+        ));
+    }
+}
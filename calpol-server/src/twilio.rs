@@ -0,0 +1,111 @@
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+use thiserror::Error;
+use url::Url;
+
+pub const DEFAULT_BASE_URL: &str = "https://api.twilio.com";
+const TIMEOUT_SEC: u64 = 5;
+
+#[derive(Clone)]
+pub struct TwilioClient {
+    inner: Client,
+    account_sid: String,
+    auth_token: String,
+    from: String,
+    messages_url: Url,
+}
+
+#[derive(Error, Debug)]
+pub enum Error {
+    #[error("Bad Base URL: {0}")]
+    BadBaseUrl(#[source] url::ParseError),
+    #[error("Request Error: {0}")]
+    Reqwest(
+        #[from]
+        #[source]
+        reqwest::Error,
+    ),
+    #[error("API Error: {0}")]
+    ApiError(
+        #[from]
+        #[source]
+        ApiError,
+    ),
+    #[error("Received unexpected response: {0}")]
+    UnexpectedResponse(String),
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "PascalCase")]
+struct SendSms<'a> {
+    to: &'a str,
+    from: &'a str,
+    body: &'a str,
+}
+
+#[derive(Error, Deserialize, Debug)]
+#[error("{message} (Code: {code:?}, Status: {status:?})")]
+pub struct ApiError {
+    code: Option<i64>,
+    message: String,
+    status: Option<u16>,
+}
+
+#[derive(Deserialize, Debug)]
+#[allow(unused)]
+pub struct SendSmsResponse {
+    sid: String,
+    status: String,
+}
+
+impl TwilioClient {
+    pub fn new(
+        account_sid: &str,
+        auth_token: &str,
+        from: &str,
+        base_url: &Url,
+    ) -> Result<Self, Error> {
+        let messages_url = Url::parse(&format!(
+            "{}/2010-04-01/Accounts/{}/Messages.json",
+            base_url.as_str().trim_end_matches('/'),
+            account_sid
+        ))
+        .map_err(Error::BadBaseUrl)?;
+        Ok(Self {
+            inner: Client::builder()
+                .timeout(Duration::from_secs(TIMEOUT_SEC))
+                .build()?,
+            account_sid: account_sid.to_string(),
+            auth_token: auth_token.to_string(),
+            from: from.to_string(),
+            messages_url,
+        })
+    }
+
+    /// Twilio only accepts a single recipient per message.
+    pub async fn send_message(&self, body: &str, to: &str) -> Result<SendSmsResponse, Error> {
+        let form = SendSms {
+            to,
+            from: &self.from,
+            body,
+        };
+        let response = self
+            .inner
+            .post(self.messages_url.clone())
+            .basic_auth(&self.account_sid, Some(&self.auth_token))
+            .form(&form)
+            .send()
+            .await?;
+        let status = response.status();
+        let body = response.text().await?;
+        if !status.is_success() {
+            Err(serde_json::from_str::<ApiError>(&body)
+                .map_err(|_| Error::UnexpectedResponse(body))?
+                .into())
+        } else {
+            Ok(serde_json::from_str::<SendSmsResponse>(&body)
+                .map_err(|_| Error::UnexpectedResponse(body))?)
+        }
+    }
+}
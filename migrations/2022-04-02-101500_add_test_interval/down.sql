@@ -0,0 +1,3 @@
+ALTER TABLE tests
+    DROP COLUMN run_interval,
+    DROP COLUMN run_timeout;
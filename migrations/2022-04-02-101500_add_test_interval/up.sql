@@ -0,0 +1,3 @@
+ALTER TABLE tests
+    ADD COLUMN run_interval INT NULL CHECK (run_interval > 0), -- Minutes
+    ADD COLUMN run_timeout  INT NULL CHECK (run_timeout > 0);  -- Seconds
@@ -0,0 +1,2 @@
+DROP TABLE maintenance_window_tests;
+DROP TABLE maintenance_windows;
@@ -0,0 +1,18 @@
+CREATE TABLE maintenance_windows
+(
+    id SERIAL PRIMARY KEY,
+    name VARCHAR(255) NOT NULL,
+    start_time TIMESTAMPTZ NOT NULL,
+    end_time TIMESTAMPTZ NOT NULL,
+    all_tests BOOLEAN NOT NULL,
+    CHECK (end_time > start_time)
+);
+
+CREATE TABLE maintenance_window_tests
+(
+    maintenance_window_id INT NOT NULL,
+    test_id INT NOT NULL,
+    PRIMARY KEY (maintenance_window_id, test_id),
+    FOREIGN KEY (maintenance_window_id) REFERENCES maintenance_windows (id) ON DELETE CASCADE,
+    FOREIGN KEY (test_id) REFERENCES tests (id) ON DELETE CASCADE
+);
@@ -0,0 +1,6 @@
+ALTER TABLE test_results
+    DROP COLUMN dns_ms,
+    DROP COLUMN connect_ms,
+    DROP COLUMN tls_ms,
+    DROP COLUMN first_byte_ms,
+    DROP COLUMN total_ms;
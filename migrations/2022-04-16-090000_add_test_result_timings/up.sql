@@ -0,0 +1,6 @@
+ALTER TABLE test_results
+    ADD COLUMN dns_ms        INT NULL,
+    ADD COLUMN connect_ms    INT NULL,
+    ADD COLUMN tls_ms        INT NULL,
+    ADD COLUMN first_byte_ms INT NULL,
+    ADD COLUMN total_ms      INT NULL;
@@ -0,0 +1,4 @@
+DROP TABLE incidents;
+DROP TABLE escalation_policy_users;
+DROP TABLE escalation_policy_tests;
+DROP TABLE escalation_policies;
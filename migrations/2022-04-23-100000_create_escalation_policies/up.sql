@@ -0,0 +1,40 @@
+CREATE TABLE escalation_policies
+(
+    id SERIAL PRIMARY KEY,
+    name VARCHAR(255) NOT NULL UNIQUE,
+    repeat_interval INT NULL CHECK (repeat_interval > 0),
+    escalate_after INT NULL CHECK (escalate_after > 0),
+    escalate_channels TEXT[] NOT NULL DEFAULT '{}'
+);
+
+CREATE TABLE escalation_policy_tests
+(
+    escalation_policy_id INT NOT NULL,
+    test_id INT NOT NULL UNIQUE,
+    PRIMARY KEY (escalation_policy_id, test_id),
+    FOREIGN KEY (escalation_policy_id) REFERENCES escalation_policies (id) ON DELETE CASCADE,
+    FOREIGN KEY (test_id) REFERENCES tests (id) ON DELETE CASCADE
+);
+
+CREATE TABLE escalation_policy_users
+(
+    escalation_policy_id INT NOT NULL,
+    user_id INT NOT NULL,
+    PRIMARY KEY (escalation_policy_id, user_id),
+    FOREIGN KEY (escalation_policy_id) REFERENCES escalation_policies (id) ON DELETE CASCADE,
+    FOREIGN KEY (user_id) REFERENCES users (id) ON DELETE CASCADE
+);
+
+CREATE TABLE incidents
+(
+    id SERIAL PRIMARY KEY,
+    test_id INT NOT NULL,
+    started TIMESTAMPTZ NOT NULL,
+    ended TIMESTAMPTZ NULL,
+    last_notified TIMESTAMPTZ NOT NULL,
+    escalated TIMESTAMPTZ NULL,
+    FOREIGN KEY (test_id) REFERENCES tests (id) ON DELETE CASCADE
+);
+
+-- A test can only have one ongoing incident
+CREATE UNIQUE INDEX incidents_ongoing_test_id ON incidents (test_id) WHERE ended IS NULL;
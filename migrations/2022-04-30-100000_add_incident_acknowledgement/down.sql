@@ -0,0 +1,3 @@
+ALTER TABLE incidents
+    DROP COLUMN acknowledged_at,
+    DROP COLUMN acknowledged_by;
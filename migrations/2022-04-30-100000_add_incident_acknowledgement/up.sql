@@ -0,0 +1,4 @@
+ALTER TABLE incidents
+    ADD COLUMN acknowledged_at TIMESTAMPTZ NULL,
+    ADD COLUMN acknowledged_by INT NULL,
+    ADD FOREIGN KEY (acknowledged_by) REFERENCES users (id) ON DELETE SET NULL;
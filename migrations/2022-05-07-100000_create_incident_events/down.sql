@@ -0,0 +1,4 @@
+DROP TABLE incident_events;
+
+ALTER TABLE incidents
+    DROP COLUMN failure_reason;
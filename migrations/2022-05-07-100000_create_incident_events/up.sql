@@ -0,0 +1,16 @@
+ALTER TABLE incidents
+    ADD COLUMN failure_reason TEXT NULL;
+
+CREATE TABLE incident_events
+(
+    id SERIAL PRIMARY KEY,
+    incident_id INT NOT NULL,
+    time TIMESTAMPTZ NOT NULL,
+    kind VARCHAR(32) NOT NULL,
+    user_id INT NULL,
+    message TEXT NULL,
+    FOREIGN KEY (incident_id) REFERENCES incidents (id) ON DELETE CASCADE,
+    FOREIGN KEY (user_id) REFERENCES users (id) ON DELETE SET NULL
+);
+
+CREATE INDEX incident_events_incident_id ON incident_events (incident_id);
@@ -0,0 +1,10 @@
+DROP TABLE user_subscriptions;
+
+ALTER TABLE users
+    DROP COLUMN subscribe_all_tests,
+    DROP COLUMN email_min_severity,
+    DROP COLUMN sms_min_severity;
+
+ALTER TABLE tests
+    DROP COLUMN tags,
+    DROP COLUMN severity;
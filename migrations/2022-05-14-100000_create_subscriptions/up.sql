@@ -0,0 +1,24 @@
+ALTER TABLE tests
+    ADD COLUMN tags TEXT[] NOT NULL DEFAULT '{}',
+    ADD COLUMN severity VARCHAR(16) NOT NULL DEFAULT 'medium'
+        CHECK (severity IN ('low', 'medium', 'high', 'critical'));
+
+ALTER TABLE users
+    ADD COLUMN subscribe_all_tests BOOLEAN NOT NULL DEFAULT TRUE,
+    ADD COLUMN email_min_severity VARCHAR(16) NOT NULL DEFAULT 'low'
+        CHECK (email_min_severity IN ('low', 'medium', 'high', 'critical')),
+    ADD COLUMN sms_min_severity VARCHAR(16) NOT NULL DEFAULT 'low'
+        CHECK (sms_min_severity IN ('low', 'medium', 'high', 'critical'));
+
+-- Each row either subscribes a user to (or excludes them from) a single test, or all tests with a tag
+CREATE TABLE user_subscriptions
+(
+    id SERIAL PRIMARY KEY,
+    user_id INT NOT NULL,
+    test_id INT NULL,
+    tag VARCHAR(255) NULL,
+    excluded BOOLEAN NOT NULL,
+    CHECK ((test_id IS NULL) <> (tag IS NULL)),
+    FOREIGN KEY (user_id) REFERENCES users (id) ON DELETE CASCADE,
+    FOREIGN KEY (test_id) REFERENCES tests (id) ON DELETE CASCADE
+);
@@ -0,0 +1,7 @@
+DROP TABLE deferred_sms;
+
+ALTER TABLE users
+    DROP COLUMN timezone,
+    DROP COLUMN quiet_hours_start,
+    DROP COLUMN quiet_hours_end,
+    DROP COLUMN quiet_hours_sms;
@@ -0,0 +1,17 @@
+ALTER TABLE users
+    ADD COLUMN timezone VARCHAR(64) NOT NULL DEFAULT 'UTC',
+    ADD COLUMN quiet_hours_start TIME NULL,
+    ADD COLUMN quiet_hours_end TIME NULL,
+    ADD COLUMN quiet_hours_sms VARCHAR(16) NOT NULL DEFAULT 'defer'
+        CHECK (quiet_hours_sms IN ('defer', 'email')),
+    ADD CHECK ((quiet_hours_start IS NULL) = (quiet_hours_end IS NULL));
+
+-- SMS notifications held back during a user's quiet hours, sent once the quiet hours end
+CREATE TABLE deferred_sms
+(
+    id SERIAL PRIMARY KEY,
+    user_id INT NOT NULL,
+    created TIMESTAMPTZ NOT NULL,
+    body TEXT NOT NULL,
+    FOREIGN KEY (user_id) REFERENCES users (id) ON DELETE CASCADE
+);
@@ -0,0 +1,7 @@
+-- Existing users keep full privileges, new users are viewers unless given another role
+ALTER TABLE users
+    ADD COLUMN role VARCHAR(16) NOT NULL DEFAULT 'admin'
+        CHECK (role IN ('viewer', 'operator', 'admin'));
+
+ALTER TABLE users
+    ALTER COLUMN role SET DEFAULT 'viewer';
@@ -0,0 +1,13 @@
+-- Long lived tokens for automation, limited to a set of scopes
+CREATE TABLE api_keys
+(
+    id SERIAL PRIMARY KEY,
+    user_id INT NOT NULL,
+    name VARCHAR(255) NOT NULL,
+    token VARCHAR(255) NOT NULL UNIQUE,
+    scopes TEXT[] NOT NULL,
+    created TIMESTAMPTZ DEFAULT CURRENT_TIMESTAMP NOT NULL,
+    expires TIMESTAMPTZ NULL,
+    last_used TIMESTAMPTZ NULL,
+    FOREIGN KEY (user_id) REFERENCES users (id) ON DELETE CASCADE
+);
@@ -0,0 +1,13 @@
+-- The hashes can't be turned back into tokens
+DELETE FROM sessions;
+DELETE FROM api_keys;
+UPDATE users
+SET password_reset_token_hash = NULL,
+    password_reset_token_creation = NULL;
+
+ALTER TABLE sessions
+    RENAME COLUMN token_hash TO token;
+ALTER TABLE api_keys
+    RENAME COLUMN token_hash TO token;
+ALTER TABLE users
+    RENAME COLUMN password_reset_token_hash TO password_reset_token;
@@ -0,0 +1,8 @@
+-- Tokens are now stored as a keyed hash, which can't be computed here without the server's token_key. The server
+-- replaces the existing plaintext tokens with their hash before running this migration (see hash_plaintext_tokens).
+ALTER TABLE sessions
+    RENAME COLUMN token TO token_hash;
+ALTER TABLE api_keys
+    RENAME COLUMN token TO token_hash;
+ALTER TABLE users
+    RENAME COLUMN password_reset_token TO password_reset_token_hash;
@@ -0,0 +1,2 @@
+ALTER TABLE deferred_sms
+    DROP COLUMN attempts;
@@ -0,0 +1,3 @@
+-- Number of times sending the SMS has failed, it is retried on the next tick until the limit is reached
+ALTER TABLE deferred_sms
+    ADD COLUMN attempts INT NOT NULL DEFAULT 0;